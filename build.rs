@@ -1,9 +1,16 @@
-use std::{path::Path, process::Command};
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 
     commit_info();
+    embed_wit_and_templates();
 }
 
 fn commit_info() {
@@ -32,3 +39,57 @@ fn commit_info() {
         next()
     );
 }
+
+/// Generate `embedded.rs`, which includes every file of `wit/` and
+/// `templates/` in the binary.
+fn embed_wit_and_templates() {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut code = String::new();
+
+    println!("cargo:rerun-if-changed=wit");
+    code.push_str("pub(crate) const WIT: &[(&str, &str)] = &[\n");
+    for path in files(&root.join("wit")) {
+        if path.extension().map_or(false, |ext| ext == "wit") {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let path = path.to_str().unwrap();
+            writeln!(code, "    ({name:?}, include_str!({path:?})),").unwrap();
+        }
+    }
+    code.push_str("];\n");
+
+    println!("cargo:rerun-if-changed=templates");
+    let templates = root.join("templates");
+    code.push_str("pub(crate) const TEMPLATES: &[(&str, &[u8])] = &[\n");
+    for path in files(&templates) {
+        // e.g., `rust/src/main.rs`
+        let relative = path.strip_prefix(&templates).unwrap();
+        let relative: Vec<&str> = relative.iter().map(|c| c.to_str().unwrap()).collect();
+        let relative = relative.join("/");
+        let path = path.to_str().unwrap();
+        writeln!(code, "    ({relative:?}, include_bytes!({path:?})),").unwrap();
+    }
+    code.push_str("];\n");
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("embedded.rs");
+    fs::write(out, code).unwrap();
+}
+
+/// The files under `dir`, recursively and in a stable order. A missing
+/// directory has no files.
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut paths: Vec<PathBuf> = entries.map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .flat_map(|path| {
+            if path.is_dir() {
+                files(&path)
+            } else {
+                vec![path]
+            }
+        })
+        .collect()
+}
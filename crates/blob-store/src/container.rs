@@ -1,18 +1,22 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use slight_common::BasicState;
+use slight_file::{Policy, Resource};
 
 use crate::{
-    blob_store::{ContainerMetadata, ObjectMetadata, ObjectNameParam, ObjectNameResult},
+    blob_store::{ContainerMetadata, Error, ObjectMetadata, ObjectNameParam, ObjectNameResult},
     implementors::{aws_s3::S3Container, azblob::AzBlobContainer},
     read_stream::{ReadStreamImplementor, ReadStreamInner},
     write_stream::{WriteStreamImplementor, WriteStreamInner},
     BlobStoreImplementors,
 };
 
+#[cfg(feature = "memory")]
+use crate::implementors::memory::MemoryContainer;
+
 pub(crate) type DynW = dyn WriteStreamImplementor + Send + Sync;
 pub(crate) type DynR = dyn ReadStreamImplementor + Send + Sync;
 pub(crate) type DynContainer = dyn ContainerImplementor + Send + Sync;
@@ -40,6 +44,9 @@ impl std::fmt::Debug for DynContainer {
 #[derive(Clone, Debug)]
 pub struct ContainerInner {
     pub implementor: Arc<DynContainer>,
+    pub name: String,
+    pub resource: Resource,
+    pub policy: Option<Policy>,
 }
 
 impl ContainerInner {
@@ -56,11 +63,28 @@ impl ContainerInner {
                 BlobStoreImplementors::AzBlob => {
                     Arc::new(AzBlobContainer::new(slight_state, name).await?)
                 }
-                BlobStoreImplementors::None => {
-                    panic!("No implementor specified")
+                #[cfg(feature = "memory")]
+                BlobStoreImplementors::Memory => {
+                    Arc::new(MemoryContainer::new(slight_state, name).await?)
                 }
+                BlobStoreImplementors::None => bail!("No implementor specified"),
             },
+            name: name.to_string(),
+            resource: slight_state.implementor,
+            policy: slight_state.policy.clone(),
         };
         Ok(container)
     }
+
+    /// Fail with `unexpected-error`, the only error of the interface, unless
+    /// the capability's policy allows access to the object `name`.
+    pub(crate) fn check_object(&self, name: &str) -> Result<(), Error> {
+        match &self.policy {
+            Some(policy) if !policy.allows_object(name) => Err(Error::UnexpectedError(format!(
+                "the policy of '{}' does not allow object '{name}'",
+                self.name
+            ))),
+            _ => Ok(()),
+        }
+    }
 }
@@ -1,16 +1,17 @@
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use aws_config::{from_env, meta::region::RegionProviderChain};
+use aws_config::from_env;
 use aws_sdk_s3::{
     client::fluent_builders::GetObject,
     error::{GetObjectError, GetObjectErrorKind},
     model::{Bucket, Delete, ObjectAttributes::ObjectSize, ObjectIdentifier},
     types::ByteStream,
-    Client,
+    Client, Credentials, Region,
 };
 use slight_common::BasicState;
+use slight_file::{resource::BlobResource, Resource};
 use slight_runtime_configs::get_from_state;
 
 use tracing::info;
@@ -49,28 +50,26 @@ pub struct S3WriteStream {
 
 impl S3Container {
     pub async fn new(slight_state: &BasicState, name: &str) -> Result<Self> {
-        let access_id = get_from_state("AWS_ACCESS_KEY_ID", slight_state)
-            .await
-            .unwrap();
-        std::env::set_var("AWS_ACCESS_KEY_ID", access_id);
-
-        let access_key = get_from_state("AWS_SECRET_ACCESS_KEY", slight_state)
-            .await
-            .unwrap();
-        std::env::set_var("AWS_SECRET_ACCESS_KEY", access_key);
-
-        let region = get_from_state("AWS_REGION", slight_state).await;
-        let default_region = get_from_state("AWS_DEFAULT_REGION", slight_state).await;
-        if region.is_err() && default_region.is_err() {
-            panic!("AWS_REGION or AWS_DEFAULT_REGION must be set");
-        } else if region.is_err() {
-            std::env::set_var("AWS_DEFAULT_REGION", default_region.unwrap());
-        } else {
-            std::env::set_var("AWS_REGION", region.unwrap());
-        }
+        let access_id = get_from_state("AWS_ACCESS_KEY_ID", slight_state).await?;
+        let access_key = get_from_state("AWS_SECRET_ACCESS_KEY", slight_state).await?;
+        let region = match (
+            get_from_state("AWS_REGION", slight_state).await,
+            get_from_state("AWS_DEFAULT_REGION", slight_state).await,
+        ) {
+            (Ok(region), _) => region,
+            (Err(_), Ok(default_region)) => default_region,
+            (Err(_), Err(_)) => bail!("AWS_REGION or AWS_DEFAULT_REGION must be set"),
+        };
 
-        let region = RegionProviderChain::default_provider();
-        let config = from_env().region(region).load().await;
+        // the credentials and region are passed to the client instead of the
+        // process environment, so capabilities (and apps of `slight serve`)
+        // with different accounts do not overwrite each other's
+        let credentials = Credentials::new(access_id, access_key, None, None, "slight");
+        let config = from_env()
+            .credentials_provider(credentials)
+            .region(Region::new(region))
+            .load()
+            .await;
         let client = Arc::new(Client::new(&config));
 
         // perform list buckets, too costly?
@@ -184,13 +183,20 @@ impl ContainerImplementor for S3Container {
             .get_object()
             .bucket(self.name().await?)
             .key(name);
-        let read_stream_inner = ReadStreamInner::new(Box::new(S3ReadStream::new(resp).await)).await;
+        let read_stream_inner = ReadStreamInner::new(
+            Box::new(S3ReadStream::new(resp).await),
+            Resource::Blob(BlobResource::AwsS3),
+        )
+        .await;
         Ok(read_stream_inner)
     }
     async fn write_object(&self, name: ObjectNameParam<'_>) -> Result<WriteStreamInner> {
-        let write_stream_inner = WriteStreamInner::new(Box::new(
-            S3WriteStream::new(self.client.clone(), self.bucket.name().unwrap(), name).await,
-        ))
+        let write_stream_inner = WriteStreamInner::new(
+            Box::new(
+                S3WriteStream::new(self.client.clone(), self.bucket.name().unwrap(), name).await,
+            ),
+            Resource::Blob(BlobResource::AwsS3),
+        )
         .await;
         Ok(write_stream_inner)
     }
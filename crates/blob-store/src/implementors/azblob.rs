@@ -7,6 +7,7 @@ use azure_storage_blobs::{
 };
 use futures::StreamExt;
 use slight_common::BasicState;
+use slight_file::{resource::BlobResource, Resource};
 
 use slight_runtime_configs::get_from_state;
 use tracing::info;
@@ -38,12 +39,8 @@ pub struct AzBlobWriteStream {
 
 impl AzBlobContainer {
     pub async fn new(slight_state: &BasicState, name: &str) -> Result<Self> {
-        let storage_account_name = get_from_state("AZURE_STORAGE_ACCOUNT", slight_state)
-            .await
-            .unwrap();
-        let storage_account_key = get_from_state("AZURE_STORAGE_KEY", slight_state)
-            .await
-            .unwrap();
+        let storage_account_name = get_from_state("AZURE_STORAGE_ACCOUNT", slight_state).await?;
+        let storage_account_key = get_from_state("AZURE_STORAGE_KEY", slight_state).await?;
 
         let storage_credentials =
             StorageCredentials::Key(storage_account_name.clone(), storage_account_key);
@@ -125,8 +122,11 @@ impl ContainerImplementor for AzBlobContainer {
         let client = self.client.blob_client(name);
         if client.exists().await? {
             info!("found blob {name}");
-            let read_stream_inner =
-                ReadStreamInner::new(Box::new(AzBlobReadStream::new(client.clone()).await)).await;
+            let read_stream_inner = ReadStreamInner::new(
+                Box::new(AzBlobReadStream::new(client.clone()).await),
+                Resource::Blob(BlobResource::Azblob),
+            )
+            .await;
             Ok(read_stream_inner)
         } else {
             bail!(format!("blob {name} not found"))
@@ -136,9 +136,10 @@ impl ContainerImplementor for AzBlobContainer {
         // unlike read-object, there is no need for write-object to check if the object exists
         // this is because the write-stream will create the object if it doesn't exist or
         // overwrite it if it does
-        let write_stream_inner = WriteStreamInner::new(Box::new(
-            AzBlobWriteStream::new(self.client.blob_client(name).clone()).await,
-        ))
+        let write_stream_inner = WriteStreamInner::new(
+            Box::new(AzBlobWriteStream::new(self.client.blob_client(name).clone()).await),
+            Resource::Blob(BlobResource::Azblob),
+        )
         .await;
         Ok(write_stream_inner)
     }
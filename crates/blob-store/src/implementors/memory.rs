@@ -0,0 +1,233 @@
+//! An in-process blob store, meant for tests (e.g., `slight test`).
+//!
+//! Containers live for the lifetime of the process, and are shared by every
+//! instance that opens the same name. Written bytes are visible as soon as
+//! `write` returns.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use slight_common::BasicState;
+use slight_file::{resource::BlobResource, Resource};
+
+use crate::{
+    blob_store::{ContainerMetadata, ObjectMetadata, ObjectNameParam, ObjectNameResult},
+    container::ContainerImplementor,
+    read_stream::{ReadStreamImplementor, ReadStreamInner},
+    write_stream::{WriteStreamImplementor, WriteStreamInner},
+};
+
+pub const MEMORY_CAPABILITY_NAME: &str = "blobstore.memory";
+
+#[derive(Debug, Clone, Default)]
+struct Object {
+    data: Vec<u8>,
+    created_at: u64,
+}
+
+type Containers = Mutex<HashMap<String, HashMap<String, Object>>>;
+
+static CONTAINERS: OnceLock<Containers> = OnceLock::new();
+
+fn containers() -> &'static Containers {
+    CONTAINERS.get_or_init(Default::default)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Add `objects` to the container opened under `name`.
+pub fn seed(name: &str, objects: impl IntoIterator<Item = (String, Vec<u8>)>) {
+    let created_at = now();
+    containers()
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_default()
+        .extend(
+            objects
+                .into_iter()
+                .map(|(key, data)| (key, Object { data, created_at })),
+        );
+}
+
+/// Empty every container.
+pub fn reset() {
+    containers().lock().unwrap().clear();
+}
+
+/// A container in the in-process blob store
+#[derive(Debug, Clone)]
+pub struct MemoryContainer {
+    name: String,
+    created_at: u64,
+}
+
+#[derive(Debug)]
+pub struct MemoryReadStream {
+    data: Vec<u8>,
+    offset: Mutex<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryWriteStream {
+    container: String,
+    name: String,
+}
+
+impl MemoryContainer {
+    pub async fn new(_slight_state: &BasicState, name: &str) -> Result<Self> {
+        containers()
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default();
+        Ok(Self {
+            name: name.to_string(),
+            created_at: now(),
+        })
+    }
+
+    fn object(&self, name: &str) -> Result<Object> {
+        containers()
+            .lock()
+            .unwrap()
+            .get(&self.name)
+            .and_then(|objects| objects.get(name))
+            .cloned()
+            .with_context(|| format!("blob {name} not found"))
+    }
+}
+
+#[async_trait]
+impl ContainerImplementor for MemoryContainer {
+    async fn name(&self) -> Result<String> {
+        Ok(self.name.clone())
+    }
+    async fn info(&self) -> Result<ContainerMetadata> {
+        Ok(ContainerMetadata {
+            name: self.name.clone(),
+            created_at: self.created_at,
+        })
+    }
+    async fn list_objects(&self) -> Result<Vec<ObjectNameResult>> {
+        let mut names: Vec<String> = containers()
+            .lock()
+            .unwrap()
+            .get(&self.name)
+            .map(|objects| objects.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        Ok(names)
+    }
+    async fn delete_object(&self, name: ObjectNameParam<'_>) -> Result<()> {
+        if let Some(objects) = containers().lock().unwrap().get_mut(&self.name) {
+            objects.remove(name);
+        }
+        Ok(())
+    }
+    async fn delete_objects(&self, names: Vec<ObjectNameParam<'_>>) -> Result<()> {
+        if let Some(objects) = containers().lock().unwrap().get_mut(&self.name) {
+            for name in names {
+                objects.remove(name);
+            }
+        }
+        Ok(())
+    }
+    async fn has_object(&self, name: ObjectNameParam<'_>) -> Result<bool> {
+        Ok(self.object(name).is_ok())
+    }
+    async fn object_info(&self, name: ObjectNameParam<'_>) -> Result<ObjectMetadata> {
+        let object = self.object(name)?;
+        Ok(ObjectMetadata {
+            name: name.to_string(),
+            container: self.name.clone(),
+            created_at: object.created_at,
+            size: object.data.len() as u64,
+        })
+    }
+    async fn read_object(&self, name: ObjectNameParam<'_>) -> Result<ReadStreamInner> {
+        let object = self.object(name)?;
+        let read_stream_inner = ReadStreamInner::new(
+            Box::new(MemoryReadStream {
+                data: object.data,
+                offset: Mutex::new(0),
+            }),
+            Resource::Blob(BlobResource::Memory),
+        )
+        .await;
+        Ok(read_stream_inner)
+    }
+    async fn write_object(&self, name: ObjectNameParam<'_>) -> Result<WriteStreamInner> {
+        containers()
+            .lock()
+            .unwrap()
+            .entry(self.name.clone())
+            .or_default()
+            .insert(
+                name.to_string(),
+                Object {
+                    data: vec![],
+                    created_at: now(),
+                },
+            );
+        let write_stream_inner = WriteStreamInner::new(
+            Box::new(MemoryWriteStream {
+                container: self.name.clone(),
+                name: name.to_string(),
+            }),
+            Resource::Blob(BlobResource::Memory),
+        )
+        .await;
+        Ok(write_stream_inner)
+    }
+}
+
+#[async_trait]
+impl ReadStreamImplementor for MemoryReadStream {
+    async fn read(&self, size: u64) -> Result<Option<Vec<u8>>> {
+        let mut offset = self.offset.lock().unwrap();
+        if *offset >= self.data.len() {
+            return Ok(None);
+        }
+        let end = self.data.len().min(*offset + size as usize);
+        let chunk = self.data[*offset..end].to_vec();
+        *offset = end;
+        Ok(Some(chunk))
+    }
+    async fn available(&self) -> Result<u64> {
+        let offset = self.offset.lock().unwrap();
+        Ok((self.data.len() - *offset) as u64)
+    }
+}
+
+#[async_trait]
+impl WriteStreamImplementor for MemoryWriteStream {
+    async fn write(&self, data: &[u8]) -> Result<()> {
+        containers()
+            .lock()
+            .unwrap()
+            .entry(self.container.clone())
+            .or_default()
+            .entry(self.name.clone())
+            .or_insert_with(|| Object {
+                data: vec![],
+                created_at: now(),
+            })
+            .data
+            .extend_from_slice(data);
+        Ok(())
+    }
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -2,3 +2,5 @@
 pub mod aws_s3;
 #[cfg(feature = "azblob")]
 pub mod azblob;
+#[cfg(feature = "memory")]
+pub mod memory;
@@ -8,7 +8,12 @@ use async_trait::async_trait;
 
 use container::ContainerInner;
 use read_stream::ReadStreamInner;
-use slight_common::{impl_resource, BasicState};
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, Traced, TracedError, Value},
+    BasicState,
+};
 use slight_file::{capability_store::CapabilityStore, resource::BlobResource::*, Resource};
 
 use blob_store::*;
@@ -17,12 +22,67 @@ wit_bindgen_wasmtime::export!({paths: ["../../wit/blob-store.wit"], async: *});
 wit_error_rs::impl_error!(blob_store::Error);
 wit_error_rs::impl_from!(anyhow::Error, blob_store::Error::UnexpectedError);
 
+impl ErrorVariant for blob_store::Error {
+    fn variant(&self) -> &'static str {
+        match self {
+            blob_store::Error::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for blob_store::Error {
+    fn message(&self) -> String {
+        match self {
+            blob_store::Error::UnexpectedError(message) => message.clone(),
+        }
+    }
+
+    fn from_variant(_variant: &str, message: String) -> Self {
+        blob_store::Error::UnexpectedError(message)
+    }
+}
+
+impl Traced for ContainerMetadata {
+    fn to_trace(&self) -> Value {
+        json!({"name": self.name, "created_at": self.created_at})
+    }
+    fn from_trace(mut value: Value) -> anyhow::Result<Self> {
+        Ok(Self {
+            name: String::from_trace(trace::take_field(&mut value, "name"))?,
+            created_at: u64::from_trace(trace::take_field(&mut value, "created_at"))?,
+        })
+    }
+}
+
+impl Traced for ObjectMetadata {
+    fn to_trace(&self) -> Value {
+        json!({
+            "name": self.name,
+            "container": self.container,
+            "created_at": self.created_at,
+            "size": self.size,
+        })
+    }
+    fn from_trace(mut value: Value) -> anyhow::Result<Self> {
+        Ok(Self {
+            name: String::from_trace(trace::take_field(&mut value, "name"))?,
+            container: String::from_trace(trace::take_field(&mut value, "container"))?,
+            created_at: u64::from_trace(trace::take_field(&mut value, "created_at"))?,
+            size: u64::from_trace(trace::take_field(&mut value, "size"))?,
+        })
+    }
+}
+
 pub const BLOB_STORE_SCHEME_NAME: &str = "blob-store";
 
 #[cfg(feature = "aws_s3")]
 pub use implementors::aws_s3::S3_CAPABILITY_NAME;
 #[cfg(feature = "azblob")]
 pub use implementors::azblob::AZBLOB_CAPABILITY_NAME;
+#[cfg(feature = "memory")]
+pub use implementors::memory;
+#[cfg(feature = "memory")]
+pub use implementors::memory::MEMORY_CAPABILITY_NAME;
 
 /// A BlobStore is a container for storing and retrieving arbitrary data.
 ///
@@ -50,18 +110,24 @@ pub enum BlobStoreImplementors {
     S3,
     #[cfg(feature = "azblob")]
     AzBlob,
+    #[cfg(feature = "memory")]
+    Memory,
     #[default]
     None,
 }
 
-impl From<Resource> for BlobStoreImplementors {
-    fn from(s: Resource) -> Self {
+impl TryFrom<Resource> for BlobStoreImplementors {
+    type Error = anyhow::Error;
+
+    fn try_from(s: Resource) -> anyhow::Result<Self> {
         match s {
             #[cfg(feature = "aws_s3")]
-            Resource::Blob(AwsS3) => Self::S3,
+            Resource::Blob(AwsS3) => Ok(Self::S3),
             #[cfg(feature = "azblob")]
-            Resource::Blob(Azblob) => Self::AzBlob,
-            p => panic!(
+            Resource::Blob(Azblob) => Ok(Self::AzBlob),
+            #[cfg(feature = "memory")]
+            Resource::Blob(Memory) => Ok(Self::Memory),
+            p => anyhow::bail!(
                 "failed to match provided name (i.e., '{p}') to any known host implementations"
             ),
         }
@@ -75,7 +141,9 @@ impl Display for BlobStoreImplementors {
             Self::S3 => write!(f, "{S3_CAPABILITY_NAME}"),
             #[cfg(feature = "azblob")]
             Self::AzBlob => write!(f, "{AZBLOB_CAPABILITY_NAME}"),
-            Self::None => panic!("No implementor specified"),
+            #[cfg(feature = "memory")]
+            Self::Memory => write!(f, "{MEMORY_CAPABILITY_NAME}"),
+            Self::None => write!(f, "none"),
         }
     }
 }
@@ -88,17 +156,17 @@ impl_resource!(
 );
 
 impl BlobStore {
-    fn fetch_state(&mut self, name: &str) -> BasicState {
+    fn fetch_state(&mut self, name: &str) -> Result<BasicState, Error> {
         let s = &self.implementor.to_string();
-        let state = if let Some(r) = self.capability_store.get(name, "blob") {
-            r.clone()
+        if let Some(r) = self.capability_store.get(name, "blob") {
+            Ok(r.clone())
         } else if let Some(r) = self.capability_store.get(s, "blob") {
-            r.clone()
+            Ok(r.clone())
         } else {
-            panic!("could not find capability under name '{name}' for implementor '{s}'");
-        };
-
-        state
+            Err(Error::UnexpectedError(format!(
+                "could not find capability under name '{name}' for implementor '{s}'"
+            )))
+        }
     }
 }
 
@@ -109,94 +177,337 @@ impl blob_store::BlobStore for BlobStore {
     type ReadStream = ReadStreamInner;
     type WriteStream = WriteStreamInner;
 
+    #[tracing::instrument(name = "blob_store.container_open", skip(self), err)]
     async fn container_open(&mut self, name: &str) -> Result<Self::Container, Error> {
-        let state = self.fetch_state(name);
-        tracing::log::info!("Opening implementor {}", &state.implementor);
-        let inner = Self::Container::new(state.implementor.into(), &state, name).await?;
+        observe_capability_call(
+            self.implementor,
+            "container_open",
+            trace::open(
+                "blob-store",
+                "container_open",
+                json!({"name": name}),
+                async {
+                    let state = self.fetch_state(name)?;
+                    tracing::log::info!("Opening implementor {}", &state.implementor);
+                    let implementor: BlobStoreImplementors = state.implementor.try_into()?;
+                    let inner = Self::Container::new(implementor, &state, name).await?;
 
-        Ok(inner)
+                    Ok(inner)
+                },
+            ),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "blob_store.container_name",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_name(&mut self, self_: &Self::Container) -> Result<String, Error> {
-        Ok(self_.implementor.name().await?)
+        observe_capability_call(
+            self_.resource,
+            "container_name",
+            trace::call(
+                "blob-store",
+                "container_name",
+                json!({"container": self_.name}),
+                async { Ok(self_.implementor.name().await?) },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_info",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_info(
         &mut self,
         self_: &Self::Container,
     ) -> Result<ContainerMetadata, Error> {
-        Ok(self_.implementor.info().await?)
+        observe_capability_call(
+            self_.resource,
+            "container_info",
+            trace::call(
+                "blob-store",
+                "container_info",
+                json!({"container": self_.name}),
+                async { Ok(self_.implementor.info().await?) },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_read_object",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_read_object(
         &mut self,
         self_: &Self::Container,
         name: ObjectNameParam<'_>,
     ) -> Result<Self::ReadStream, Error> {
-        let read_stream = self_.implementor.read_object(name).await?;
-        Ok(read_stream)
+        observe_capability_call(
+            self_.resource,
+            "container_read_object",
+            trace::call(
+                "blob-store",
+                "container_read_object",
+                json!({"container": self_.name, "object": name}),
+                async {
+                    self_.check_object(name)?;
+                    let read_stream = self_.implementor.read_object(name).await?;
+                    Ok(read_stream)
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_write_object",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_write_object(
         &mut self,
         self_: &Self::Container,
         name: ObjectNameParam<'_>,
     ) -> Result<Self::WriteStream, Error> {
-        let write_stream = self_.implementor.write_object(name).await?;
-        Ok(write_stream)
+        observe_capability_call(
+            self_.resource,
+            "container_write_object",
+            trace::call(
+                "blob-store",
+                "container_write_object",
+                json!({"container": self_.name, "object": name}),
+                async {
+                    self_.check_object(name)?;
+                    let write_stream = self_.implementor.write_object(name).await?;
+                    Ok(write_stream)
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_list_objects",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_list_objects(
         &mut self,
         self_: &Self::Container,
     ) -> Result<Vec<ObjectNameResult>, Error> {
-        Ok(self_.implementor.list_objects().await?)
+        observe_capability_call(
+            self_.resource,
+            "container_list_objects",
+            trace::call(
+                "blob-store",
+                "container_list_objects",
+                json!({"container": self_.name}),
+                async {
+                    let objects = self_.implementor.list_objects().await?;
+                    // only list the objects the guest may access
+                    Ok(objects
+                        .into_iter()
+                        .filter(|name| self_.check_object(name).is_ok())
+                        .collect())
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_delete_object",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_delete_object(
         &mut self,
         self_: &Self::Container,
         name: ObjectNameParam<'_>,
     ) -> Result<(), Error> {
-        Ok(self_.implementor.delete_object(name).await?)
+        observe_capability_call(
+            self_.resource,
+            "container_delete_object",
+            trace::call(
+                "blob-store",
+                "container_delete_object",
+                json!({"container": self_.name, "object": name}),
+                async {
+                    self_.check_object(name)?;
+                    Ok(self_.implementor.delete_object(name).await?)
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_delete_objects",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_delete_objects(
         &mut self,
         self_: &Self::Container,
         names: Vec<ObjectNameParam<'_>>,
     ) -> Result<(), Error> {
-        Ok(self_.implementor.delete_objects(names).await?)
+        observe_capability_call(
+            self_.resource,
+            "container_delete_objects",
+            trace::call(
+                "blob-store",
+                "container_delete_objects",
+                json!({"container": self_.name, "objects": names}),
+                async {
+                    for name in &names {
+                        self_.check_object(name)?;
+                    }
+                    Ok(self_.implementor.delete_objects(names).await?)
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_has_object",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_has_object(
         &mut self,
         self_: &Self::Container,
         name: ObjectNameParam<'_>,
     ) -> Result<bool, Error> {
-        Ok(self_.implementor.has_object(name).await?)
+        observe_capability_call(
+            self_.resource,
+            "container_has_object",
+            trace::call(
+                "blob-store",
+                "container_has_object",
+                json!({"container": self_.name, "object": name}),
+                async {
+                    self_.check_object(name)?;
+                    Ok(self_.implementor.has_object(name).await?)
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.container_object_info",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.resource),
+        err
+    )]
     async fn container_object_info(
         &mut self,
         self_: &Self::Container,
         name: ObjectNameParam<'_>,
     ) -> Result<ObjectMetadata, Error> {
-        Ok(self_.implementor.object_info(name).await?)
+        observe_capability_call(
+            self_.resource,
+            "container_object_info",
+            trace::call(
+                "blob-store",
+                "container_object_info",
+                json!({"container": self_.name, "object": name}),
+                async {
+                    self_.check_object(name)?;
+                    Ok(self_.implementor.object_info(name).await?)
+                },
+            ),
+        )
+        .await
     }
     async fn container_clear(&mut self, _self_: &Self::Container) -> Result<(), Error> {
         todo!()
     }
+    #[tracing::instrument(
+        name = "blob_store.write_stream_write",
+        skip_all,
+        fields(implementor = %self_.resource),
+        err
+    )]
     async fn write_stream_write(
         &mut self,
         self_: &Self::WriteStream,
         data: &[u8],
     ) -> Result<(), Error> {
-        Ok(self_.implementor.write(data).await?)
+        observe_capability_call(
+            self_.resource,
+            "write_stream_write",
+            trace::call(
+                "blob-store",
+                "write_stream_write",
+                json!({"data": data}),
+                async { Ok(self_.implementor.write(data).await?) },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.write_stream_close",
+        skip_all,
+        fields(implementor = %self_.resource),
+        err
+    )]
     async fn write_stream_close(&mut self, self_: &Self::WriteStream) -> Result<(), Error> {
-        Ok(self_.implementor.close().await?)
+        observe_capability_call(
+            self_.resource,
+            "write_stream_close",
+            trace::call("blob-store", "write_stream_close", json!({}), async {
+                Ok(self_.implementor.close().await?)
+            }),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.read_stream_read",
+        skip_all,
+        fields(implementor = %self_.resource),
+        err
+    )]
     async fn read_stream_read(
         &mut self,
         self_: &Self::ReadStream,
         size: u64,
     ) -> Result<Option<Vec<u8>>, Error> {
-        Ok(self_.implementor.read(size).await?)
+        observe_capability_call(
+            self_.resource,
+            "read_stream_read",
+            trace::call(
+                "blob-store",
+                "read_stream_read",
+                json!({"size": size}),
+                async { Ok(self_.implementor.read(size).await?) },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "blob_store.read_stream_available",
+        skip_all,
+        fields(implementor = %self_.resource),
+        err
+    )]
     async fn read_stream_available(&mut self, self_: &Self::ReadStream) -> Result<u64, Error> {
-        Ok(self_.implementor.available().await?)
+        observe_capability_call(
+            self_.resource,
+            "read_stream_available",
+            trace::call("blob-store", "read_stream_available", json!({}), async {
+                Ok(self_.implementor.available().await?)
+            }),
+        )
+        .await
     }
 }
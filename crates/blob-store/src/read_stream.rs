@@ -1,7 +1,9 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use crate::container::DynR;
 use async_trait::async_trait;
+use slight_common::trace::{Traced, Value};
+use slight_file::{resource::BlobResource, Resource};
 use std::fmt::Debug;
 
 /// A stream of bytes that can be read from
@@ -29,10 +31,40 @@ impl Debug for dyn ReadStreamImplementor + Send + Sync {
 #[derive(Debug)]
 pub struct ReadStreamInner {
     pub implementor: Box<DynR>,
+    pub resource: Resource,
 }
 
 impl ReadStreamInner {
-    pub async fn new(implementor: Box<DynR>) -> Self {
-        Self { implementor }
+    pub async fn new(implementor: Box<DynR>, resource: Resource) -> Self {
+        Self {
+            implementor,
+            resource,
+        }
+    }
+}
+
+/// A read stream opened while replaying a trace. Its reads are answered
+/// from the trace, so it is never read from.
+struct Replayed;
+
+#[async_trait]
+impl ReadStreamImplementor for Replayed {
+    async fn read(&self, _size: u64) -> Result<Option<Vec<u8>>> {
+        bail!("read streams are not available when replaying")
+    }
+    async fn available(&self) -> Result<u64> {
+        bail!("read streams are not available when replaying")
+    }
+}
+
+impl Traced for ReadStreamInner {
+    fn to_trace(&self) -> Value {
+        Value::Null
+    }
+    fn from_trace(_value: Value) -> Result<Self> {
+        Ok(Self {
+            implementor: Box::new(Replayed),
+            resource: Resource::Blob(BlobResource::Memory),
+        })
     }
 }
@@ -1,6 +1,8 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use async_trait::async_trait;
+use slight_common::trace::{Traced, Value};
+use slight_file::{resource::BlobResource, Resource};
 
 use crate::container::DynW;
 
@@ -29,10 +31,40 @@ impl std::fmt::Debug for dyn WriteStreamImplementor + Send + Sync {
 #[derive(Debug)]
 pub struct WriteStreamInner {
     pub implementor: Box<DynW>,
+    pub resource: Resource,
 }
 
 impl WriteStreamInner {
-    pub async fn new(implementor: Box<DynW>) -> Self {
-        Self { implementor }
+    pub async fn new(implementor: Box<DynW>, resource: Resource) -> Self {
+        Self {
+            implementor,
+            resource,
+        }
+    }
+}
+
+/// A write stream opened while replaying a trace. Its writes are answered
+/// from the trace, so it is never written to.
+struct Replayed;
+
+#[async_trait]
+impl WriteStreamImplementor for Replayed {
+    async fn write(&self, _data: &[u8]) -> Result<()> {
+        bail!("write streams are not available when replaying")
+    }
+    async fn close(&self) -> Result<()> {
+        bail!("write streams are not available when replaying")
+    }
+}
+
+impl Traced for WriteStreamInner {
+    fn to_trace(&self) -> Value {
+        Value::Null
+    }
+    fn from_trace(_value: Value) -> Result<Self> {
+        Ok(Self {
+            implementor: Box::new(Replayed),
+            resource: Resource::Blob(BlobResource::Memory),
+        })
     }
 }
@@ -1,4 +1,5 @@
-use slight_http_api::{HttpHandlerData, HttpServerExportData};
+use slight_http_api::{HttpHandlerData, HttpServerExportData, HttpServerShutdownData};
+use slight_messaging_api::MessagingHandlerData;
 
 /// A WebAssembly runtime context to be consumed by the wasm component.
 pub trait Ctx {
@@ -8,6 +9,12 @@ pub trait Ctx {
     /// Get the mutable reference to the http server data.
     fn get_http_server_mut(&mut self) -> &mut HttpServerExportData;
 
+    /// Get the mutable reference to the http server shutdown data.
+    fn get_http_server_shutdown_mut(&mut self) -> &mut HttpServerShutdownData;
+
+    /// Get the mutable reference to the messaging handler data.
+    fn get_messaging_handler_mut(&mut self) -> &mut MessagingHandlerData;
+
     /// Get the runtime host state for a given resource key.
     fn get_host_state<T: 'static, TTable: 'static>(
         &mut self,
@@ -1,5 +1,7 @@
 mod context;
+pub mod metrics;
 mod state;
+pub mod trace;
 #[cfg(feature = "wasmtime")]
 mod wasmtime_runtime;
 
@@ -10,7 +12,7 @@ pub use context::Ctx;
 pub use state::BasicState;
 
 #[cfg(feature = "wasmtime")]
-pub use wasmtime_runtime::{Builder, Linker, WasmtimeBuildable, WasmtimeLinkable};
+pub use wasmtime_runtime::{Builder, LimitExceeded, Linker, WasmtimeBuildable, WasmtimeLinkable};
 
 /// A trait for wit-bindgen capability
 pub trait Capability: AsAny {}
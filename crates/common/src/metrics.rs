@@ -0,0 +1,149 @@
+//! Prometheus metrics of the slight host.
+//!
+//! Metrics are always recorded. They are only served if the slightfile has
+//! a `[metrics]` section.
+
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+/// The content type of [`gather`]'s output.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Names the WIT variant of a capability's error, e.g. `key-not-found`.
+pub trait ErrorVariant {
+    fn variant(&self) -> &'static str;
+}
+
+struct Metrics {
+    registry: Registry,
+    capability_calls: IntCounterVec,
+    capability_errors: IntCounterVec,
+    capability_duration: HistogramVec,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    instance_build_duration: Histogram,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("slight".to_string()), None)?;
+        let capability_calls = IntCounterVec::new(
+            Opts::new("capability_calls_total", "Capability host calls"),
+            &["implementor", "operation"],
+        )?;
+        let capability_errors = IntCounterVec::new(
+            Opts::new(
+                "capability_errors_total",
+                "Capability host calls that returned an error",
+            ),
+            &["implementor", "operation", "variant"],
+        )?;
+        let capability_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "capability_call_duration_seconds",
+                "Latency of capability host calls",
+            ),
+            &["implementor", "operation"],
+        )?;
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Http requests served"),
+            &["route", "method", "status"],
+        )?;
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Latency of http requests"),
+            &["route", "method"],
+        )?;
+        let instance_build_duration = Histogram::with_opts(HistogramOpts::new(
+            "instance_build_duration_seconds",
+            "Time taken to build a guest instance",
+        ))?;
+
+        registry.register(Box::new(capability_calls.clone()))?;
+        registry.register(Box::new(capability_errors.clone()))?;
+        registry.register(Box::new(capability_duration.clone()))?;
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_duration.clone()))?;
+        registry.register(Box::new(instance_build_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            capability_calls,
+            capability_errors,
+            capability_duration,
+            http_requests,
+            http_duration,
+            instance_build_duration,
+        })
+    }
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics::new().expect("metrics are valid"))
+}
+
+/// Run a capability host call, recording its latency and outcome.
+pub async fn observe_capability_call<T, E: ErrorVariant>(
+    implementor: impl Display,
+    operation: &str,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let start = Instant::now();
+    let res = call.await;
+
+    let metrics = metrics();
+    let implementor = implementor.to_string();
+    metrics
+        .capability_calls
+        .with_label_values(&[&implementor, operation])
+        .inc();
+    metrics
+        .capability_duration
+        .with_label_values(&[&implementor, operation])
+        .observe(start.elapsed().as_secs_f64());
+    if let Err(e) = &res {
+        metrics
+            .capability_errors
+            .with_label_values(&[&implementor, operation, e.variant()])
+            .inc();
+    }
+    res
+}
+
+/// Record a served http request.
+pub fn observe_http_request(route: &str, method: &str, status: u16, elapsed: Duration) {
+    let metrics = metrics();
+    metrics
+        .http_requests
+        .with_label_values(&[route, method, &status.to_string()])
+        .inc();
+    metrics
+        .http_duration
+        .with_label_values(&[route, method])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Record the time taken to build a guest instance.
+pub fn observe_instance_build(elapsed: Duration) {
+    metrics()
+        .instance_build_duration
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Encode all metrics in the Prometheus text format.
+pub fn gather() -> String {
+    let mut buf = vec![];
+    TextEncoder::new()
+        .encode(&metrics().registry.gather(), &mut buf)
+        .expect("text encoding does not fail");
+    String::from_utf8(buf).expect("text encoding is utf-8")
+}
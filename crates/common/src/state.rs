@@ -3,7 +3,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use slight_file::{Resource, SecretStoreResource};
+use slight_file::{Policy, Resource, SecretStoreResource};
 
 /// `BasicState` provides an attempt at a "fit-all" for basic scenarios
 /// of a host's state.
@@ -11,7 +11,8 @@ use slight_file::{Resource, SecretStoreResource};
 /// It contains:
 ///     - a `implementor`,
 ///     - a `name`,
-///     - a `configs_map`, and
+///     - a `configs_map`,
+///     - a `policy`, and
 ///     - the `slightfile_path`.
 #[derive(Clone, Default)]
 pub struct BasicState {
@@ -19,6 +20,7 @@ pub struct BasicState {
     pub implementor: Resource,
     pub name: String,
     pub configs_map: Option<HashMap<String, String>>,
+    pub policy: Option<Policy>,
     pub slightfile_path: PathBuf,
 }
 
@@ -28,6 +30,7 @@ impl BasicState {
         implementor: Resource,
         name: String,
         configs_map: Option<HashMap<String, String>>,
+        policy: Option<Policy>,
         slightfile_path: impl AsRef<Path>,
     ) -> Self {
         Self {
@@ -35,6 +38,7 @@ impl BasicState {
             implementor,
             name,
             configs_map,
+            policy,
             slightfile_path: slightfile_path.as_ref().to_owned(),
         }
     }
@@ -0,0 +1,300 @@
+//! Recording and replaying capability host calls.
+//!
+//! When recording, every traced call is appended to a JSON lines file with
+//! its interface, function, arguments and result. When replaying, traced
+//! calls are not run: each one is answered with the result of the first
+//! recorded call with the same interface, function and arguments that has
+//! not been used yet.
+
+use std::{
+    fs::File,
+    future::Future,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+pub use serde_json::{json, Value};
+
+use crate::metrics::ErrorVariant;
+
+/// A value that can be written to, and read back from, a trace.
+pub trait Traced: Sized {
+    fn to_trace(&self) -> Value;
+    fn from_trace(value: Value) -> Result<Self>;
+}
+
+/// A capability error that can be written to, and read back from, a trace.
+pub trait TracedError: ErrorVariant + Sized {
+    fn message(&self) -> String;
+    /// Build the error of `variant`. Unknown variants should map to the
+    /// interface's unexpected error.
+    fn from_variant(variant: &str, message: String) -> Self;
+}
+
+/// A recorded capability call, i.e., a line of a trace file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub interface: String,
+    pub function: String,
+    pub args: Value,
+    pub result: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok(Value),
+    Err { variant: String, message: String },
+}
+
+enum Mode {
+    Record(Mutex<File>),
+    Replay(Mutex<Vec<Option<Entry>>>),
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Append every traced call of this process to `path`.
+pub fn record_to(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path)
+        .with_context(|| format!("could not create trace '{}'", path.display()))?;
+    set_mode(Mode::Record(Mutex::new(file)))
+}
+
+/// Answer every traced call of this process from the trace at `path`.
+pub fn replay_from(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("could not open trace '{}'", path.display()))?;
+    let mut entries = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line)
+            .with_context(|| format!("invalid entry on line {} of '{}'", i + 1, path.display()))?;
+        entries.push(Some(entry));
+    }
+    set_mode(Mode::Replay(Mutex::new(entries)))
+}
+
+fn set_mode(mode: Mode) -> Result<()> {
+    if MODE.set(mode).is_err() {
+        bail!("capability calls are already being recorded or replayed");
+    }
+    Ok(())
+}
+
+/// Run a capability call that returns a value, recording or replaying it.
+pub async fn call<T: Traced, E: TracedError>(
+    interface: &str,
+    function: &str,
+    args: Value,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    match MODE.get() {
+        None => call.await,
+        Some(Mode::Record(file)) => {
+            let res = call.await;
+            let result = match &res {
+                Ok(value) => Outcome::Ok(value.to_trace()),
+                Err(e) => error_outcome(e),
+            };
+            record(file, interface, function, args, result);
+            res
+        }
+        Some(Mode::Replay(entries)) => match take(entries, interface, function, &args)? {
+            Outcome::Ok(value) => T::from_trace(value).map_err(|e| {
+                E::from_variant(
+                    "unexpected-error",
+                    format!("invalid recorded result for {interface}.{function}: {e}"),
+                )
+            }),
+            Outcome::Err { variant, message } => Err(E::from_variant(&variant, message)),
+        },
+    }
+}
+
+/// Run a capability call that opens a resource, recording or replaying
+/// whether it succeeded.
+///
+/// Handles cannot be read back from a trace, so a successful open is run
+/// again when replaying. The capabilities are expected to be backed by
+/// implementors that do not need a real backend (e.g., in-memory ones).
+pub async fn open<T, E: TracedError>(
+    interface: &str,
+    function: &str,
+    args: Value,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    match MODE.get() {
+        None => call.await,
+        Some(Mode::Record(file)) => {
+            let res = call.await;
+            let result = match &res {
+                Ok(_) => Outcome::Ok(Value::Null),
+                Err(e) => error_outcome(e),
+            };
+            record(file, interface, function, args, result);
+            res
+        }
+        Some(Mode::Replay(entries)) => match take(entries, interface, function, &args)? {
+            Outcome::Ok(_) => call.await,
+            Outcome::Err { variant, message } => Err(E::from_variant(&variant, message)),
+        },
+    }
+}
+
+fn error_outcome<E: TracedError>(e: &E) -> Outcome {
+    Outcome::Err {
+        variant: e.variant().to_string(),
+        message: e.message(),
+    }
+}
+
+fn record(file: &Mutex<File>, interface: &str, function: &str, args: Value, result: Outcome) {
+    let entry = Entry {
+        interface: interface.to_string(),
+        function: function.to_string(),
+        args,
+        result,
+    };
+    let line = serde_json::to_string(&entry).expect("trace entries are valid JSON");
+    let mut file = file.lock().unwrap();
+    // A trace is most useful when the process crashes, so don't buffer it.
+    if let Err(e) = writeln!(file, "{line}").and_then(|_| file.flush()) {
+        tracing::error!("failed to record {interface}.{function}: {e}");
+    }
+}
+
+fn take<E: TracedError>(
+    entries: &Mutex<Vec<Option<Entry>>>,
+    interface: &str,
+    function: &str,
+    args: &Value,
+) -> Result<Outcome, E> {
+    let mut entries = entries.lock().unwrap();
+    let entry = entries.iter_mut().find(|entry| {
+        entry.as_ref().map_or(false, |entry| {
+            entry.interface == interface && entry.function == function && &entry.args == args
+        })
+    });
+    match entry.and_then(Option::take) {
+        Some(entry) => Ok(entry.result),
+        None => {
+            tracing::warn!("no recorded call matches {interface}.{function}({args})");
+            Err(E::from_variant(
+                "unexpected-error",
+                format!("no recorded call matches {interface}.{function}({args})"),
+            ))
+        }
+    }
+}
+
+/// Take the field `key` out of a traced object, or `null` if it has none.
+pub fn take_field(value: &mut Value, key: &str) -> Value {
+    value.get_mut(key).map(Value::take).unwrap_or(Value::Null)
+}
+
+impl Traced for () {
+    fn to_trace(&self) -> Value {
+        Value::Null
+    }
+    fn from_trace(_value: Value) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl Traced for bool {
+    fn to_trace(&self) -> Value {
+        json!(self)
+    }
+    fn from_trace(value: Value) -> Result<Self> {
+        value.as_bool().context("expected a boolean")
+    }
+}
+
+impl Traced for String {
+    fn to_trace(&self) -> Value {
+        json!(self)
+    }
+    fn from_trace(value: Value) -> Result<Self> {
+        Ok(value.as_str().context("expected a string")?.to_string())
+    }
+}
+
+macro_rules! impl_traced_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Traced for $ty {
+                fn to_trace(&self) -> Value {
+                    json!(self)
+                }
+                fn from_trace(value: Value) -> Result<Self> {
+                    Ok(serde_json::from_value(value)?)
+                }
+            }
+        )*
+    };
+}
+
+impl_traced_for_int!(u8, u16, u32, u64, i32, i64);
+
+impl Traced for f64 {
+    fn to_trace(&self) -> Value {
+        json!(self)
+    }
+    fn from_trace(value: Value) -> Result<Self> {
+        value.as_f64().context("expected a number")
+    }
+}
+
+impl<T: Traced> Traced for Vec<T> {
+    fn to_trace(&self) -> Value {
+        Value::Array(self.iter().map(Traced::to_trace).collect())
+    }
+    fn from_trace(value: Value) -> Result<Self> {
+        match value {
+            Value::Array(values) => values.into_iter().map(T::from_trace).collect(),
+            _ => bail!("expected an array"),
+        }
+    }
+}
+
+impl<T: Traced> Traced for Option<T> {
+    fn to_trace(&self) -> Value {
+        match self {
+            Some(value) => value.to_trace(),
+            None => Value::Null,
+        }
+    }
+    fn from_trace(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => Ok(Some(T::from_trace(value)?)),
+        }
+    }
+}
+
+impl<A: Traced, B: Traced> Traced for (A, B) {
+    fn to_trace(&self) -> Value {
+        json!([self.0.to_trace(), self.1.to_trace()])
+    }
+    fn from_trace(value: Value) -> Result<Self> {
+        match value {
+            Value::Array(values) if values.len() == 2 => {
+                let mut values = values.into_iter();
+                Ok((
+                    A::from_trace(values.next().unwrap())?,
+                    B::from_trace(values.next().unwrap())?,
+                ))
+            }
+            _ => bail!("expected a pair"),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Result;
 use async_trait::async_trait;
 
 use wasmtime::{Instance, Store};
@@ -9,7 +10,7 @@ use crate::Ctx;
 pub trait WasmtimeBuildable: Clone {
     type Ctx: Ctx + Send + Sync;
 
-    async fn build(self) -> (Store<Self::Ctx>, Instance);
+    async fn build(self) -> Result<(Store<Self::Ctx>, Instance)>;
 }
 
 #[derive(Clone)]
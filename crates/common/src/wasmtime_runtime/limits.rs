@@ -0,0 +1,56 @@
+use std::fmt::Display;
+
+use wasmtime::Trap;
+
+/// A guest resource limit that was exceeded.
+///
+/// Runtimes return this as the error of a failed invocation, so that
+/// hosts like the http server can map it to a proper response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// The guest tried to grow its linear memory past the limit.
+    Memory,
+    /// The guest tried to grow a table past the limit.
+    TableElements,
+    /// The guest consumed all of the fuel of the invocation.
+    Fuel,
+    /// The guest ran past the wall-clock deadline of the invocation.
+    Deadline,
+}
+
+impl LimitExceeded {
+    /// Find the exceeded limit, if any, in the chain of an invocation error.
+    pub fn from_error(err: &anyhow::Error) -> Option<Self> {
+        err.chain().find_map(|cause| {
+            if let Some(limit) = cause.downcast_ref::<LimitExceeded>() {
+                return Some(*limit);
+            }
+            match cause.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => Some(LimitExceeded::Fuel),
+                Some(Trap::Interrupt) => Some(LimitExceeded::Deadline),
+                _ => None,
+            }
+        })
+    }
+
+    /// The HTTP status code to respond with when a handler exceeds this limit.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            LimitExceeded::Deadline => 504,
+            _ => 503,
+        }
+    }
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::Memory => write!(f, "guest exceeded its linear memory limit"),
+            LimitExceeded::TableElements => write!(f, "guest exceeded its table elements limit"),
+            LimitExceeded::Fuel => write!(f, "guest ran out of fuel"),
+            LimitExceeded::Deadline => write!(f, "guest exceeded its execution deadline"),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
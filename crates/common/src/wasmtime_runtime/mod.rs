@@ -1,5 +1,7 @@
 mod builder;
+mod limits;
 mod linker;
 
 pub use builder::{Builder, WasmtimeBuildable};
+pub use limits::LimitExceeded;
 pub use linker::{Linker, WasmtimeLinkable};
@@ -1,122 +1,433 @@
+//! User secrets, encrypted into a slightfile's `secret_settings`.
+//!
+//! Secrets are encrypted with XChaCha20-Poly1305 under a per-project key.
+//! The key is read from the `SLIGHT_SECRET_KEY` environment variable if it
+//! is set, and from the slightfile's `secret_key_file` (by default,
+//! `.slightkey` next to the slightfile) otherwise.
+//!
+//! An encrypted value is `v1:` followed by the base64 of a random 24-byte
+//! nonce and the ciphertext. The name of the secret is authenticated with
+//! it, so a value cannot be moved to another secret.
+
 use std::{
     env,
-    fs::{File, OpenOptions},
+    fs::OpenOptions,
     io::Write,
+    path::{Path, PathBuf},
 };
 
-use anyhow::{bail, Result};
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, OsRng, Payload},
+    AeadCore, Key, XChaCha20Poly1305, XNonce,
+};
 use short_crypt::ShortCrypt;
 use slight_file::{Config, SlightFile};
+use toml_edit::{value, ArrayOfTables, Document, Item, Table};
 
+/// The environment variable a base64-encoded key can be supplied through.
+/// It takes precedence over the key file.
+pub const SLIGHT_SECRET_KEY: &str = "SLIGHT_SECRET_KEY";
+
+/// The key file, next to the slightfile, used when the slightfile does not
+/// set `secret_key_file`.
 pub const SLIGHTKEY: &str = ".slightkey";
 
+/// The prefix of values encrypted in the current format.
+const V1_PREFIX: &str = "v1:";
+
+const NONCE_LEN: usize = 24;
+
+/// A key secrets are encrypted with.
+#[derive(Clone)]
+pub struct SecretKey(Key);
+
+impl SecretKey {
+    pub fn generate() -> Self {
+        Self(XChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .context("the secret key is not valid base64")?;
+        if bytes.len() != 32 {
+            bail!(
+                "the secret key must be 32 bytes long, found {} bytes",
+                bytes.len()
+            );
+        }
+        Ok(Self(*Key::from_slice(&bytes)))
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    /// Encrypt the value of the secret `name`.
+    pub fn encrypt(&self, name: &str, value: &[u8]) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&self.0)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value,
+                    aad: name.as_bytes(),
+                },
+            )
+            .expect("encrypting into a vector does not fail");
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+        format!("{V1_PREFIX}{}", STANDARD.encode(encrypted))
+    }
+
+    /// Decrypt the value of the secret `name`.
+    pub fn decrypt(&self, name: &str, value: &str) -> Result<Vec<u8>> {
+        let Some(encoded) = value.strip_prefix(V1_PREFIX) else {
+            if is_legacy(value) {
+                bail!("secret '{name}' was encrypted by an older version of slight, re-encrypt it with `slight -c <slightfile> secret migrate`");
+            }
+            bail!("secret '{name}' is not in a known format");
+        };
+        let encrypted = STANDARD
+            .decode(encoded)
+            .with_context(|| format!("secret '{name}' is not valid base64"))?;
+        if encrypted.len() < NONCE_LEN {
+            bail!("secret '{name}' is too short");
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        XChaCha20Poly1305::new(&self.0)
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "could not decrypt secret '{name}', it was encrypted with a different key, or modified"
+                )
+            })
+    }
+}
+
+/// Whether `value` was encrypted by `ShortCrypt`, before values had a
+/// version prefix.
+fn is_legacy(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The key file of the slightfile at `toml_file_path`.
+pub fn key_file_path(toml: &SlightFile, toml_file_path: impl AsRef<Path>) -> PathBuf {
+    let dir = toml_file_path
+        .as_ref()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    match &toml.secret_key_file {
+        Some(path) => dir.join(path),
+        None => dir.join(SLIGHTKEY),
+    }
+}
+
+/// Read the key of the slightfile at `toml_file_path`.
+pub fn get_key(toml: &SlightFile, toml_file_path: impl AsRef<Path>) -> Result<SecretKey> {
+    if let Ok(encoded) = env::var(SLIGHT_SECRET_KEY) {
+        return SecretKey::from_base64(&encoded).with_context(|| {
+            format!("invalid key in the {SLIGHT_SECRET_KEY} environment variable")
+        });
+    }
+    let path = key_file_path(toml, toml_file_path);
+    if !path.exists() {
+        bail!(
+            "user secrets haven't been initialized yet, there is no key at '{}'. You can set your user secrets with `slight -c <slightfile> secret -k <some_key> -v <some_value>`.",
+            path.display()
+        );
+    }
+    let encoded = std::fs::read_to_string(&path)
+        .with_context(|| format!("could not read secret key '{}'", path.display()))?;
+    SecretKey::from_base64(&encoded)
+        .with_context(|| format!("invalid secret key '{}'", path.display()))
+}
+
+/// Read the key of the slightfile at `toml_file_path`, or create its key
+/// file if there is no key yet.
+pub fn get_or_create_key(toml: &SlightFile, toml_file_path: impl AsRef<Path>) -> Result<SecretKey> {
+    let path = key_file_path(toml, &toml_file_path);
+    if env::var(SLIGHT_SECRET_KEY).is_err() && !path.exists() {
+        let key = SecretKey::generate();
+        write_key_file(&path, &key)?;
+        println!(
+            "created secret key '{}', keep it out of version control",
+            path.display()
+        );
+        return Ok(key);
+    }
+    get_key(toml, toml_file_path)
+}
+
+/// Create a key file only the current user can read.
+fn write_key_file(path: &Path, key: &SecretKey) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut keyfile = options
+        .open(path)
+        .with_context(|| format!("could not create secret key '{}'", path.display()))?;
+    writeln!(keyfile, "{}", key.to_base64())?;
+    Ok(())
+}
+
+/// Encrypt `value` as the secret `key` of the slightfile at
+/// `toml_file_path`, replacing the secret if it exists, and write the
+/// slightfile.
 pub fn create_secret(
     key: &str,
     value: &str,
     toml: &mut SlightFile,
-    toml_file: &mut File,
+    toml_file_path: impl AsRef<Path>,
 ) -> Result<()> {
-    maybe_set_key()?;
-    let encryption_key = get_key()?;
+    let encryption_key = get_or_create_key(toml, &toml_file_path)?;
+    let secret = Config::new(
+        key.to_string(),
+        encryption_key.encrypt(key, value.as_bytes()),
+    );
+    let secrets = toml.secret_settings.get_or_insert_with(Vec::new);
+    match secrets.iter().position(|s| s.name == key) {
+        // if the secret exists, we modify the existing field
+        Some(p) => secrets[p] = secret,
+        None => secrets.push(secret),
+    }
 
-    toml.secret_settings = if let Some(s) = &toml.secret_settings {
-        // check that the secrets field is present
-        Some(s.to_vec())
-    } else {
-        // if not, instantiate empty
-        Some(vec![])
-    };
+    write_secrets(toml, toml_file_path)
+}
 
-    // find position of secret in toml's secret array
-    let pos = toml
+/// Decrypt the secret `key` of the slightfile at `toml_file_path`.
+pub fn get_secret(
+    key: &str,
+    toml: &SlightFile,
+    toml_file_path: impl AsRef<Path>,
+) -> Result<Vec<u8>> {
+    let Some(secret) = toml
         .secret_settings
-        .as_ref()
-        .unwrap() // note: unwrapping here is fine because it is guaranteed to succeed
         .iter()
-        .position(|s| s.name == key);
-
-    // create encrypter instance w/ our encryption key
-    let sc = ShortCrypt::new(encryption_key);
-    let encrypted_value = sc.encrypt_to_url_component(&value); // encrypt our value to a random-like string
-    let secret = Config::new(key.to_string(), encrypted_value);
-    if let Some(p) = pos {
-        // check if key doesn't already exist
-        // if it does, we want to modify the existing field
-        toml.secret_settings.as_mut().unwrap()[p] = secret;
-        // ^^^ note: both of these unwraps cannot fail
-    } else {
-        // if it isn't, we will just create new
-        toml.secret_settings.as_mut().unwrap().push(secret);
+        .flatten()
+        .find(|s| s.name == key)
+    else {
+        bail!("there is no secret '{key}' in the slightfile");
+    };
+    get_key(toml, toml_file_path)?.decrypt(key, &secret.value)
+}
+
+/// Remove the secret `key` from the slightfile at `toml_file_path`, and
+/// write the slightfile.
+pub fn remove_secret(
+    key: &str,
+    toml: &mut SlightFile,
+    toml_file_path: impl AsRef<Path>,
+) -> Result<()> {
+    let secrets = toml.secret_settings.get_or_insert_with(Vec::new);
+    let len = secrets.len();
+    secrets.retain(|s| s.name != key);
+    if secrets.len() == len {
+        bail!("there is no secret '{key}' in the slightfile");
     }
+    write_secrets(toml, toml_file_path)
+}
 
-    // write to toml file
-    toml_file.write_all(toml::to_string(&toml)?.as_bytes())?;
+/// Re-encrypt every secret of the slightfile at `toml_file_path` under a
+/// new key, and replace its key file. Returns the number of secrets that
+/// were re-encrypted.
+///
+/// Nothing is written unless every secret can be decrypted with the
+/// current key.
+pub fn rotate_key(toml: &mut SlightFile, toml_file_path: impl AsRef<Path>) -> Result<usize> {
+    if env::var(SLIGHT_SECRET_KEY).is_ok() {
+        bail!("the key is supplied through the {SLIGHT_SECRET_KEY} environment variable, unset it to rotate the key file");
+    }
+    let old_key = get_key(toml, &toml_file_path)?;
+    let new_key = SecretKey::generate();
+    let secrets = toml.secret_settings.iter_mut().flatten();
+    let values = secrets
+        .map(|s| {
+            let value = old_key.decrypt(&s.name, &s.value)?;
+            Ok((s, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let rotated = values.len();
+    for (secret, value) in values {
+        secret.value = new_key.encrypt(&secret.name, &value);
+    }
 
-    Ok(())
+    // Write the new key next to the current one first, so that the secrets
+    // are never written under a key that is not on disk.
+    let key_file = key_file_path(toml, &toml_file_path);
+    let mut new_key_file = key_file.clone().into_os_string();
+    new_key_file.push(".new");
+    let new_key_file = PathBuf::from(new_key_file);
+    if new_key_file.exists() {
+        std::fs::remove_file(&new_key_file)?;
+    }
+    write_key_file(&new_key_file, &new_key)?;
+    if let Err(e) = write_secrets(toml, &toml_file_path) {
+        let _ = std::fs::remove_file(&new_key_file);
+        return Err(e);
+    }
+    std::fs::rename(&new_key_file, &key_file).with_context(|| {
+        format!(
+            "the secrets are encrypted with the key at '{}', move it to '{}'",
+            new_key_file.display(),
+            key_file.display()
+        )
+    })?;
+    Ok(rotated)
 }
 
-pub fn generate_key() -> String {
-    thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(30)
-        .map(char::from)
-        .collect()
-}
+/// Re-encrypt the secrets that were encrypted with `ShortCrypt`, under the
+/// key of the slightfile, and write the slightfile. Returns the number of
+/// secrets that were re-encrypted.
+///
+/// The `ShortCrypt` key is read from `.slightkey` in the temporary
+/// directory, where older versions of slight kept it.
+pub fn migrate_secrets(toml: &mut SlightFile, toml_file_path: impl AsRef<Path>) -> Result<usize> {
+    let migrated = |s: &Config| s.value.starts_with(V1_PREFIX);
+    if toml.secret_settings.iter().flatten().all(migrated) {
+        return Ok(0);
+    }
 
-pub fn get_key() -> Result<String> {
-    let slightkey = env::temp_dir().join(SLIGHTKEY);
+    let legacy_key_path = env::temp_dir().join(SLIGHTKEY);
+    let legacy_key = std::fs::read_to_string(&legacy_key_path).with_context(|| {
+        format!(
+            "could not read the key of older versions of slight at '{}'",
+            legacy_key_path.display()
+        )
+    })?;
+    let encryption_key = get_or_create_key(toml, &toml_file_path)?;
+    let secrets = toml.secret_settings.iter_mut().flatten();
+    let migrated = reencrypt_legacy(secrets, &ShortCrypt::new(legacy_key), &encryption_key)?;
+
+    write_secrets(toml, toml_file_path)?;
+    Ok(migrated)
+}
 
-    if slightkey.exists() {
-        Ok(std::fs::read_to_string(slightkey)?)
-    } else {
-        bail!("usersecrets haven't been initialized yet, you can set your user secrets with `slight -c <config_file> -k <some_key> -v <some_value`.")
+fn reencrypt_legacy<'a>(
+    secrets: impl Iterator<Item = &'a mut Config>,
+    legacy_key: &ShortCrypt,
+    encryption_key: &SecretKey,
+) -> Result<usize> {
+    let mut migrated = 0;
+    for secret in secrets.filter(|s| !s.value.starts_with(V1_PREFIX)) {
+        let value = legacy_key
+            .decrypt_url_component(&secret.value)
+            .map_err(|e| anyhow::anyhow!(e))
+            .with_context(|| format!("could not decrypt secret '{}'", secret.name))?;
+        secret.value = encryption_key.encrypt(&secret.name, &value);
+        migrated += 1;
     }
+    Ok(migrated)
 }
 
-pub fn maybe_set_key() -> Result<()> {
-    let slightkey = env::temp_dir().join(SLIGHTKEY);
-
-    let mut keyfile = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(slightkey)?;
+/// Write the secrets of `toml` to the slightfile at `toml_file_path`,
+/// keeping the rest of the file, e.g., its comments, as it is.
+fn write_secrets(toml: &SlightFile, toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let toml_file_path = toml_file_path.as_ref();
+    let content = std::fs::read_to_string(toml_file_path).unwrap_or_default();
+    let mut document: Document = content
+        .parse()
+        .with_context(|| format!("could not parse '{}'", toml_file_path.display()))?;
+
+    if let Some(secret_store) = &toml.secret_store {
+        let secret_store = String::from(secret_store.clone());
+        if document.get("secret_store").and_then(Item::as_str) != Some(secret_store.as_str()) {
+            document["secret_store"] = value(secret_store);
+        }
+    }
 
-    if keyfile.metadata().unwrap().len() == 0 {
-        // check file is empty
-        keyfile.write_all(generate_key().as_bytes())?;
+    let secrets = toml.secret_settings.as_deref().unwrap_or_default();
+    if secrets.is_empty() {
+        document.remove("secret_settings");
+    } else {
+        let item = document
+            .entry("secret_settings")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()));
+        // `secret_settings = [{ ... }]` becomes an array of tables
+        let tables = match std::mem::take(item).into_array_of_tables() {
+            Ok(tables) => tables,
+            Err(_) => bail!(
+                "secret_settings of '{}' must be an array of tables",
+                toml_file_path.display()
+            ),
+        };
+        *item = Item::ArrayOfTables(update_tables(tables, secrets));
     }
 
-    // if not empty, we keep the original key
+    std::fs::write(toml_file_path, document.to_string())
+        .with_context(|| format!("could not write '{}'", toml_file_path.display()))
+}
 
-    Ok(())
+/// Make `tables` hold `secrets`, reusing the table each secret had, to keep
+/// its formatting.
+fn update_tables(tables: ArrayOfTables, secrets: &[Config]) -> ArrayOfTables {
+    let mut existing: Vec<Option<Table>> = tables.into_iter().map(Some).collect();
+    let mut updated = ArrayOfTables::new();
+    for secret in secrets {
+        let reused = existing.iter_mut().find_map(|table| {
+            match table.as_ref()?.get("name").and_then(Item::as_str) {
+                Some(name) if name == secret.name => table.take(),
+                _ => None,
+            }
+        });
+        let mut table = reused.unwrap_or_else(|| {
+            let mut table = Table::new();
+            table["name"] = value(secret.name.as_str());
+            table
+        });
+        if table.get("value").and_then(Item::as_str) != Some(secret.value.as_str()) {
+            table["value"] = value(secret.value.as_str());
+        }
+        updated.push(table);
+    }
+    updated
 }
 
 #[cfg(test)]
 mod unittests {
-    use std::{fs::OpenOptions, io::Write};
+    use std::path::Path;
 
     use anyhow::Result;
+    use short_crypt::ShortCrypt;
     use tempfile::tempdir;
 
-    use super::create_secret;
-    use slight_file::SlightFile;
+    use super::{
+        create_secret, get_key, get_secret, key_file_path, reencrypt_legacy, remove_secret,
+        rotate_key, SecretKey,
+    };
+    use slight_file::{Config, SlightFile};
+
+    fn write_toml(path: &Path, toml_str: &str) -> Result<SlightFile> {
+        std::fs::write(path, toml_str)?;
+        Ok(toml::from_str::<SlightFile>(toml_str)?)
+    }
 
     #[test]
     fn create_secret_test() -> Result<()> {
         let dir = tempdir()?;
-        let toml_file_pathpuf = dir.path().join("slightfile.toml");
-        let toml_file_pathstr = toml_file_pathpuf.to_str().unwrap();
+        let toml_file_path = dir.path().join("slightfile.toml");
+        let mut tmp_toml = write_toml(&toml_file_path, "specversion = \"0.2\"")?;
 
-        let mut tmp_toml = toml::from_str::<SlightFile>("specversion = \"0.2\"")?;
-        let mut toml_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(toml_file_pathstr)?;
+        assert!(create_secret("key", "value", &mut tmp_toml, &toml_file_path).is_ok());
+        assert!(dir.path().join(".slightkey").exists());
 
-        assert!(create_secret("key", "value", &mut tmp_toml, &mut toml_file).is_ok());
+        let written = toml::from_str::<SlightFile>(&std::fs::read_to_string(&toml_file_path)?)?;
+        let secret = &written.secret_settings.unwrap()[0];
+        let key = get_key(&tmp_toml, &toml_file_path)?;
+        assert_eq!(key.decrypt(&secret.name, &secret.value)?, b"value");
 
         Ok(())
     }
@@ -124,8 +435,7 @@ mod unittests {
     #[test]
     fn add_new_secret() -> Result<()> {
         let dir = tempdir()?;
-        let toml_file_pathpuf = dir.path().join("slightfile.toml");
-        let toml_file_pathstr = toml_file_pathpuf.to_str().unwrap();
+        let toml_file_path = dir.path().join("slightfile.toml");
 
         let toml_str = r#"
         specversion = "0.2"
@@ -138,15 +448,8 @@ mod unittests {
         value = "bar_val)_unencrypted"
         "#;
 
-        let mut tmp_toml = toml::from_str::<SlightFile>(toml_str)?;
-        let mut toml_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(toml_file_pathstr)?;
-
-        toml_file.write_all(toml::to_string(&tmp_toml)?.as_bytes())?;
-        create_secret("baz", "baz_val_encrypted", &mut tmp_toml, &mut toml_file)?;
+        let mut tmp_toml = write_toml(&toml_file_path, toml_str)?;
+        create_secret("baz", "baz_val_encrypted", &mut tmp_toml, &toml_file_path)?;
 
         assert!(tmp_toml
             .secret_settings
@@ -175,8 +478,7 @@ mod unittests {
     #[test]
     fn change_existing_secret() -> Result<()> {
         let dir = tempdir()?;
-        let toml_file_pathpuf = dir.path().join("slightfile.toml");
-        let toml_file_pathstr = toml_file_pathpuf.to_str().unwrap();
+        let toml_file_path = dir.path().join("slightfile.toml");
 
         let toml_str = r#"
         specversion = "0.2"
@@ -185,21 +487,14 @@ mod unittests {
         value = "foo_val_unencrypted"
         "#;
 
-        let mut tmp_toml = toml::from_str::<SlightFile>(toml_str)?;
+        let mut tmp_toml = write_toml(&toml_file_path, toml_str)?;
 
         assert_eq!(
             tmp_toml.secret_settings.as_ref().unwrap()[0].value,
             "foo_val_unencrypted"
         );
 
-        let mut toml_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(toml_file_pathstr)?;
-
-        toml_file.write_all(toml::to_string(&tmp_toml)?.as_bytes())?;
-        create_secret("foo", "foo_val_encrypted", &mut tmp_toml, &mut toml_file)?;
+        create_secret("foo", "foo_val_encrypted", &mut tmp_toml, &toml_file_path)?;
 
         assert_ne!(
             tmp_toml.secret_settings.as_ref().unwrap()[0].value,
@@ -212,8 +507,7 @@ mod unittests {
     #[test]
     fn change_duplicate_secret() -> Result<()> {
         let dir = tempdir()?;
-        let toml_file_pathpuf = dir.path().join("slightfile.toml");
-        let toml_file_pathstr = toml_file_pathpuf.to_str().unwrap();
+        let toml_file_path = dir.path().join("slightfile.toml");
 
         let toml_str = r#"
         specversion = "0.2"
@@ -226,7 +520,7 @@ mod unittests {
         value = "duplicate_foo_val_unencrypted"
         "#;
 
-        let mut tmp_toml = toml::from_str::<SlightFile>(toml_str)?;
+        let mut tmp_toml = write_toml(&toml_file_path, toml_str)?;
 
         assert_eq!(
             tmp_toml.secret_settings.as_ref().unwrap()[0].value,
@@ -238,14 +532,7 @@ mod unittests {
             "duplicate_foo_val_unencrypted"
         );
 
-        let mut toml_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(toml_file_pathstr)?;
-
-        toml_file.write_all(toml::to_string(&tmp_toml)?.as_bytes())?;
-        create_secret("foo", "foo_val_encrypted", &mut tmp_toml, &mut toml_file)?;
+        create_secret("foo", "foo_val_encrypted", &mut tmp_toml, &toml_file_path)?;
 
         assert_ne!(
             tmp_toml.secret_settings.as_ref().unwrap()[0].value,
@@ -259,4 +546,125 @@ mod unittests {
 
         Ok(())
     }
+
+    #[test]
+    fn keep_the_rest_of_the_slightfile() -> Result<()> {
+        let dir = tempdir()?;
+        let toml_file_path = dir.path().join("slightfile.toml");
+
+        let toml_str = r#"# my app
+specversion = "0.2"
+
+[[secret_settings]]
+name = "foo" # the first secret
+value = "foo_val_unencrypted"
+
+[[capability]]
+resource = "configs.usersecrets"
+name = "my-secrets"
+"#;
+
+        let mut tmp_toml = write_toml(&toml_file_path, toml_str)?;
+        create_secret("bar", "bar_val", &mut tmp_toml, &toml_file_path)?;
+        let written = std::fs::read_to_string(&toml_file_path)?;
+        assert!(written.starts_with("# my app\nspecversion = \"0.2\"\n"));
+        assert!(written
+            .contains("name = \"foo\" # the first secret\nvalue = \"foo_val_unencrypted\"\n"));
+        assert!(written.contains(
+            "[[capability]]\nresource = \"configs.usersecrets\"\nname = \"my-secrets\"\n"
+        ));
+        assert_eq!(get_secret("bar", &tmp_toml, &toml_file_path)?, b"bar_val");
+
+        remove_secret("foo", &mut tmp_toml, &toml_file_path)?;
+        remove_secret("bar", &mut tmp_toml, &toml_file_path)?;
+        let written = std::fs::read_to_string(&toml_file_path)?;
+        assert!(!written.contains("secret_settings"));
+        assert!(written.contains("[[capability]]"));
+        assert!(remove_secret("foo", &mut tmp_toml, &toml_file_path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_the_key() -> Result<()> {
+        let dir = tempdir()?;
+        let toml_file_path = dir.path().join("slightfile.toml");
+        let mut tmp_toml = write_toml(&toml_file_path, "specversion = \"0.2\"")?;
+        create_secret("foo", "foo_val", &mut tmp_toml, &toml_file_path)?;
+        create_secret("bar", "bar_val", &mut tmp_toml, &toml_file_path)?;
+        let old_key = get_key(&tmp_toml, &toml_file_path)?;
+
+        assert_eq!(rotate_key(&mut tmp_toml, &toml_file_path)?, 2);
+        assert!(!dir.path().join(".slightkey.new").exists());
+        let new_key = get_key(&tmp_toml, &toml_file_path)?;
+        assert_ne!(old_key.to_base64(), new_key.to_base64());
+
+        let written = toml::from_str::<SlightFile>(&std::fs::read_to_string(&toml_file_path)?)?;
+        let secrets = written.secret_settings.unwrap();
+        assert_eq!(new_key.decrypt("foo", &secrets[0].value)?, b"foo_val");
+        assert_eq!(new_key.decrypt("bar", &secrets[1].value)?, b"bar_val");
+        assert!(old_key.decrypt("foo", &secrets[0].value).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn key_file_location() -> Result<()> {
+        let toml = toml::from_str::<SlightFile>("specversion = \"0.2\"")?;
+        assert_eq!(
+            key_file_path(&toml, "app/slightfile.toml"),
+            Path::new("app/.slightkey")
+        );
+        let toml = toml::from_str::<SlightFile>(
+            "specversion = \"0.2\"\nsecret_key_file = \"keys/app.key\"",
+        )?;
+        assert_eq!(
+            key_file_path(&toml, "app/slightfile.toml"),
+            Path::new("app/keys/app.key")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn encrypt_then_decrypt() -> Result<()> {
+        let key = SecretKey::generate();
+        let encrypted = key.encrypt("foo", b"value");
+        assert!(encrypted.starts_with("v1:"));
+        assert_ne!(encrypted, key.encrypt("foo", b"value"));
+        assert_eq!(key.decrypt("foo", &encrypted)?, b"value");
+
+        // the value is bound to the key and to the name of the secret
+        assert!(SecretKey::generate().decrypt("foo", &encrypted).is_err());
+        assert!(key.decrypt("bar", &encrypted).is_err());
+
+        let key = SecretKey::from_base64(&key.to_base64())?;
+        assert_eq!(key.decrypt("foo", &encrypted)?, b"value");
+        assert!(SecretKey::from_base64("dG9vIHNob3J0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_secret() -> Result<()> {
+        let legacy = ShortCrypt::new("legacy-key").encrypt_to_url_component("value");
+        let err = SecretKey::generate().decrypt("foo", &legacy).unwrap_err();
+        assert!(err.to_string().contains("secret migrate"));
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_legacy_secrets() -> Result<()> {
+        let legacy_key = ShortCrypt::new("legacy-key");
+        let key = SecretKey::generate();
+        let current = key.encrypt("bar", b"bar_val");
+        let mut secrets = vec![
+            Config::new(
+                "foo".to_string(),
+                legacy_key.encrypt_to_url_component("foo_val"),
+            ),
+            Config::new("bar".to_string(), current.clone()),
+        ];
+
+        assert_eq!(reencrypt_legacy(secrets.iter_mut(), &legacy_key, &key)?, 1);
+        assert_eq!(key.decrypt("foo", &secrets[0].value)?, b"foo_val");
+        assert_eq!(secrets[1].value, current);
+        Ok(())
+    }
 }
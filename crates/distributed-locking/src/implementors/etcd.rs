@@ -30,17 +30,15 @@ impl std::fmt::Debug for EtcdImplementor {
 }
 
 impl EtcdImplementor {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let endpoint = get_from_state("ETCD_ENDPOINT", slight_state).await.unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let endpoint = get_from_state("ETCD_ENDPOINT", slight_state).await?;
 
         let client = Client::connect([endpoint], None)
             .await
-            .with_context(|| "failed to connect to etcd server")
-            .unwrap();
-        // ^^^ from my tests with localhost client, this never fails
-        Self {
+            .with_context(|| "failed to connect to etcd server")?;
+        Ok(Self {
             client: Arc::new(Mutex::new(client)),
-        }
+        })
     }
 }
 
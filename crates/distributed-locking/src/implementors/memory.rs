@@ -0,0 +1,58 @@
+//! In-process locks, meant for tests (e.g., `slight test`).
+//!
+//! Unlike etcd, locking a lock that is already held fails instead of
+//! waiting, because nothing else could ever release it in a test. Times
+//! to live are not enforced.
+
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+
+use super::DistributedLockingImplementor;
+
+static LOCKS: OnceLock<Mutex<HashSet<Vec<u8>>>> = OnceLock::new();
+
+fn locks() -> &'static Mutex<HashSet<Vec<u8>>> {
+    LOCKS.get_or_init(Default::default)
+}
+
+/// Release every lock.
+pub fn reset() {
+    locks().lock().unwrap().clear();
+}
+
+/// This is the underlying struct behind the `Memory` variant of the `DistributedLockingImplementors` enum.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryImplementor;
+
+#[async_trait]
+impl DistributedLockingImplementor for MemoryImplementor {
+    async fn lock(&self, lock_name: &[u8]) -> Result<Vec<u8>> {
+        if !locks().lock().unwrap().insert(lock_name.to_vec()) {
+            bail!(
+                "lock '{}' is already held",
+                String::from_utf8_lossy(lock_name)
+            );
+        }
+        Ok(lock_name.to_vec())
+    }
+
+    async fn lock_with_time_to_live(
+        &self,
+        lock_name: &[u8],
+        _time_to_live_in_secs: i64,
+    ) -> Result<Vec<u8>> {
+        self.lock(lock_name).await
+    }
+
+    async fn unlock(&self, lock_key: &[u8]) -> Result<()> {
+        if !locks().lock().unwrap().remove(lock_key) {
+            bail!("lock '{}' is not held", String::from_utf8_lossy(lock_key));
+        }
+        Ok(())
+    }
+}
@@ -5,6 +5,8 @@ use async_trait::async_trait;
 
 #[cfg(feature = "etcd")]
 pub mod etcd;
+#[cfg(feature = "memory")]
+pub mod memory;
 
 #[async_trait]
 pub trait DistributedLockingImplementor {
@@ -1,13 +1,21 @@
 mod implementors;
 pub mod providers;
 
+#[cfg(feature = "memory")]
+pub use implementors::memory;
+
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
 
 use implementors::*;
-use slight_common::{impl_resource, BasicState};
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, TracedError},
+    BasicState,
+};
 use slight_file::{
     capability_store::CapabilityStore, resource::DistributedLockingResource::*, Resource,
 };
@@ -27,6 +35,46 @@ wit_error_rs::impl_from!(
     distributed_locking::DistributedLockingError::UnexpectedError
 );
 
+impl ErrorVariant for DistributedLockingError {
+    fn variant(&self) -> &'static str {
+        match self {
+            DistributedLockingError::LockAcquireError(_) => "lock-acquire-error",
+            DistributedLockingError::LockReleaseError(_) => "lock-release-error",
+            DistributedLockingError::LockTimeoutError(_) => "lock-timeout-error",
+            DistributedLockingError::ConnectionError(_) => "connection-error",
+            DistributedLockingError::ProtocolError(_) => "protocol-error",
+            DistributedLockingError::InvalidLockId(_) => "invalid-lock-id",
+            DistributedLockingError::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for DistributedLockingError {
+    fn message(&self) -> String {
+        match self {
+            DistributedLockingError::LockAcquireError(message)
+            | DistributedLockingError::LockReleaseError(message)
+            | DistributedLockingError::LockTimeoutError(message)
+            | DistributedLockingError::ConnectionError(message)
+            | DistributedLockingError::ProtocolError(message)
+            | DistributedLockingError::InvalidLockId(message)
+            | DistributedLockingError::UnexpectedError(message) => message.clone(),
+        }
+    }
+
+    fn from_variant(variant: &str, message: String) -> Self {
+        match variant {
+            "lock-acquire-error" => DistributedLockingError::LockAcquireError(message),
+            "lock-release-error" => DistributedLockingError::LockReleaseError(message),
+            "lock-timeout-error" => DistributedLockingError::LockTimeoutError(message),
+            "connection-error" => DistributedLockingError::ConnectionError(message),
+            "protocol-error" => DistributedLockingError::ProtocolError(message),
+            "invalid-lock-id" => DistributedLockingError::InvalidLockId(message),
+            _ => DistributedLockingError::UnexpectedError(message),
+        }
+    }
+}
+
 /// The `DistributedLocking` structure is what will implement the `distributed_locking::DistributedLocking` trait
 /// coming from the generated code of off `distributed-locking.wit`.
 ///
@@ -63,65 +111,134 @@ impl_resource!(
 impl distributed_locking::DistributedLocking for DistributedLocking {
     type DistributedLocking = DistributedLockingInner;
 
+    #[tracing::instrument(name = "distributed_locking.open", skip(self), err)]
     async fn distributed_locking_open(
         &mut self,
         name: &str,
     ) -> Result<Self::DistributedLocking, distributed_locking::DistributedLockingError> {
-        // populate our inner distributed_locking object w/ the state received from `slight`
-        // (i.e., what type of distributed_locking implementor we are using), and the assigned
-        // name of the object.
-        let s = &self.implementor.to_string();
-        let state = if let Some(r) = self.capability_store.get(name, "distributed_locking") {
-            r.clone()
-        } else if let Some(r) = self.capability_store.get(s, "distributed_locking") {
-            r.clone()
-        } else {
-            panic!(
-                "could not find capability under name '{}' for implementor '{}'",
-                name, &s
-            );
-        };
-
-        tracing::log::info!("Opening implementor {}", &state.implementor);
-
-        let inner = Self::DistributedLocking::new(state.implementor.into(), &state).await;
-
-        Ok(inner)
+        observe_capability_call(
+            self.implementor,
+            "open",
+            trace::open(
+                "distributed-locking",
+                "open",
+                json!({"name": name}),
+                async {
+                    // populate our inner distributed_locking object w/ the state received from `slight`
+                    // (i.e., what type of distributed_locking implementor we are using), and the assigned
+                    // name of the object.
+                    let s = &self.implementor.to_string();
+                    let state = if let Some(r) =
+                        self.capability_store.get(name, "distributed_locking")
+                    {
+                        r.clone()
+                    } else if let Some(r) = self.capability_store.get(s, "distributed_locking") {
+                        r.clone()
+                    } else {
+                        return Err(
+                            distributed_locking::DistributedLockingError::UnexpectedError(format!(
+                        "could not find capability under name '{name}' for implementor '{s}'"
+                    )),
+                        );
+                    };
+
+                    tracing::log::info!("Opening implementor {}", &state.implementor);
+
+                    let implementor: DistributedLockingImplementors =
+                        state.implementor.try_into()?;
+                    let inner = Self::DistributedLocking::new(implementor, &state, name)
+                        .await
+                        .map_err(|e| {
+                            distributed_locking::DistributedLockingError::ConnectionError(format!(
+                                "{e:#}"
+                            ))
+                        })?;
+
+                    Ok(inner)
+                },
+            ),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "distributed_locking.lock",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn distributed_locking_lock(
         &mut self,
         self_: &Self::DistributedLocking,
         lock_name: &[u8],
     ) -> Result<Vec<u8>, distributed_locking::DistributedLockingError> {
-        Ok(self_
-            .distributed_locking_implementor
-            .lock(lock_name)
-            .await?)
+        observe_capability_call(
+            self_.implementor,
+            "lock",
+            trace::call(
+                "distributed-locking",
+                "lock",
+                json!({"name": self_.name, "lock_name": lock_name}),
+                async {
+                    Ok(self_
+                        .distributed_locking_implementor
+                        .lock(lock_name)
+                        .await?)
+                },
+            ),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "distributed_locking.lock_with_time_to_live",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn distributed_locking_lock_with_time_to_live(
         &mut self,
         self_: &Self::DistributedLocking,
         lock_name: &[u8],
         time_to_live_in_secs: i64,
     ) -> Result<Vec<u8>, distributed_locking::DistributedLockingError> {
-        Ok(self_
-            .distributed_locking_implementor
-            .lock_with_time_to_live(lock_name, time_to_live_in_secs)
-            .await?)
+        observe_capability_call(self_.implementor, "lock_with_time_to_live", trace::call("distributed-locking", "lock_with_time_to_live", json!({"name": self_.name, "lock_name": lock_name, "time_to_live_in_secs": time_to_live_in_secs}), async {
+            Ok(self_
+                .distributed_locking_implementor
+                .lock_with_time_to_live(lock_name, time_to_live_in_secs)
+                .await?)
+        }))
+        .await
     }
 
+    #[tracing::instrument(
+        name = "distributed_locking.unlock",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn distributed_locking_unlock(
         &mut self,
         self_: &Self::DistributedLocking,
         lock_key: &[u8],
     ) -> Result<(), DistributedLockingError> {
-        self_
-            .distributed_locking_implementor
-            .unlock(lock_key)
-            .await?;
-        Ok(())
+        observe_capability_call(
+            self_.implementor,
+            "unlock",
+            trace::call(
+                "distributed-locking",
+                "unlock",
+                json!({"name": self_.name, "lock_key": lock_key}),
+                async {
+                    self_
+                        .distributed_locking_implementor
+                        .unlock(lock_key)
+                        .await?;
+                    Ok(())
+                },
+            ),
+        )
+        .await
     }
 }
 
@@ -141,21 +258,28 @@ impl distributed_locking::DistributedLocking for DistributedLocking {
 #[derive(Debug, Clone)]
 pub struct DistributedLockingInner {
     distributed_locking_implementor: Arc<dyn DistributedLockingImplementor + Send + Sync>,
+    name: String,
+    implementor: Resource,
 }
 
 impl DistributedLockingInner {
     async fn new(
         distributed_locking_implementors: DistributedLockingImplementors,
         slight_state: &BasicState,
-    ) -> Self {
-        Self {
+        name: &str,
+    ) -> Result<Self> {
+        Ok(Self {
             distributed_locking_implementor: match distributed_locking_implementors {
                 #[cfg(feature = "etcd")]
                 DistributedLockingImplementors::Etcd => {
-                    Arc::new(etcd::EtcdImplementor::new(slight_state).await)
+                    Arc::new(etcd::EtcdImplementor::new(slight_state).await?)
                 }
+                #[cfg(feature = "memory")]
+                DistributedLockingImplementors::Memory => Arc::new(memory::MemoryImplementor),
             },
-        }
+            name: name.to_string(),
+            implementor: slight_state.implementor,
+        })
     }
 }
 
@@ -164,14 +288,22 @@ impl DistributedLockingInner {
 enum DistributedLockingImplementors {
     #[cfg(feature = "etcd")]
     Etcd,
+    #[cfg(feature = "memory")]
+    Memory,
 }
 
-impl From<Resource> for DistributedLockingImplementors {
-    fn from(s: Resource) -> Self {
+impl TryFrom<Resource> for DistributedLockingImplementors {
+    type Error = anyhow::Error;
+
+    fn try_from(s: Resource) -> Result<Self> {
         match s {
             #[cfg(feature = "etcd")]
-            Resource::DistributedLocking(Etcd) | Resource::DistributedLocking(V1Etcd) => Self::Etcd,
-            p => panic!(
+            Resource::DistributedLocking(Etcd) | Resource::DistributedLocking(V1Etcd) => {
+                Ok(Self::Etcd)
+            }
+            #[cfg(feature = "memory")]
+            Resource::DistributedLocking(Memory) => Ok(Self::Memory),
+            p => bail!(
                 "failed to match provided name (i.e., '{p}') to any known host implementations"
             ),
         }
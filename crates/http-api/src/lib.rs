@@ -1,6 +1,7 @@
 use anyhow::{bail, Result};
 pub use http_handler::{HttpError, HttpHandlerData, Method, Request, Response};
 pub use http_server_export::HttpServerExportData;
+pub use http_server_shutdown::HttpServerShutdownData;
 use hyper::{
     body::HttpBody as HyperHttpBody,
     header::{HeaderName, HeaderValue},
@@ -9,6 +10,7 @@ use hyper::{
 
 wit_bindgen_wasmtime::import!({paths: ["../../wit/http-handler.wit"], async: *});
 wit_bindgen_wasmtime::import!({paths: ["../../wit/http-server-export.wit"], async: *});
+wit_bindgen_wasmtime::import!({paths: ["../../wit/http-server-shutdown.wit"], async: *});
 wit_error_rs::impl_error!(http_handler::HttpError);
 
 /// An exported HTTP server init function from the wasm module
@@ -48,6 +50,41 @@ impl<T: Send> HttpServerInit<T> {
     }
 }
 
+/// An exported HTTP server shutdown function from the wasm module
+///
+/// This is a wrapper implementation of the WIT generated `HttpServerShutdown`.
+/// Exporting it is optional, use `is_exported` to check for it before
+/// calling `new`.
+pub struct HttpServerShutdown<T> {
+    inner: http_server_shutdown::HttpServerShutdown<T>,
+}
+
+impl<T: Send> HttpServerShutdown<T> {
+    pub fn new(
+        store: impl wasmtime::AsContextMut<Data = T>,
+        instance: &wasmtime::Instance,
+        get_state: impl Fn(&mut T) -> &mut HttpServerShutdownData + Send + Sync + Copy + 'static,
+    ) -> Result<Self> {
+        http_server_shutdown::HttpServerShutdown::new(store, instance, get_state)
+            .map(|inner| Self { inner })
+    }
+
+    /// Whether the module exports `on-server-shutdown`.
+    pub fn is_exported(
+        store: impl wasmtime::AsContextMut<Data = T>,
+        instance: &wasmtime::Instance,
+    ) -> bool {
+        instance.get_func(store, "on-server-shutdown").is_some()
+    }
+
+    pub async fn on_server_shutdown(
+        &self,
+        caller: impl wasmtime::AsContextMut<Data = T>,
+    ) -> Result<Result<(), String>, anyhow::Error> {
+        self.inner.on_server_shutdown(caller).await
+    }
+}
+
 /// A HTTP Handler that finds the handler function from the wasm module
 /// and calls it with the HTTP request.
 ///
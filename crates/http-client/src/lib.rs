@@ -1,54 +1,206 @@
+use std::fmt::Display;
+
 use anyhow::Result;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{redirect, Client};
 
 use http_client::*;
-use slight_common::impl_resource;
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, Traced, TracedError, Value},
+};
+use slight_file::Policy;
 wit_bindgen_wasmtime::export!({paths: ["../../wit/http-client.wit"], async: *});
 wit_error_rs::impl_error!(http_client::HttpError);
 wit_error_rs::impl_from!(anyhow::Error, http_client::HttpError::UnexpectedError);
 
+impl ErrorVariant for HttpError {
+    fn variant(&self) -> &'static str {
+        match self {
+            HttpError::InvalidUrl(_) => "invalid-url",
+            HttpError::TimeoutError(_) => "timeout-error",
+            HttpError::ProtocolError(_) => "protocol-error",
+            HttpError::StatusError(_) => "status-error",
+            HttpError::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for HttpError {
+    fn message(&self) -> String {
+        match self {
+            HttpError::InvalidUrl(message)
+            | HttpError::TimeoutError(message)
+            | HttpError::ProtocolError(message)
+            | HttpError::UnexpectedError(message) => message.clone(),
+            HttpError::StatusError(status) => status.to_string(),
+        }
+    }
+
+    fn from_variant(variant: &str, message: String) -> Self {
+        match variant {
+            "invalid-url" => HttpError::InvalidUrl(message),
+            "timeout-error" => HttpError::TimeoutError(message),
+            "protocol-error" => HttpError::ProtocolError(message),
+            "status-error" => match message.parse() {
+                Ok(status) => HttpError::StatusError(status),
+                Err(_) => HttpError::UnexpectedError(message),
+            },
+            _ => HttpError::UnexpectedError(message),
+        }
+    }
+}
+
+impl Traced for Response {
+    fn to_trace(&self) -> Value {
+        json!({
+            "status": self.status,
+            "headers": self.headers.to_trace(),
+            "body": self.body.to_trace(),
+        })
+    }
+    fn from_trace(mut value: Value) -> Result<Self> {
+        Ok(Self {
+            status: u16::from_trace(trace::take_field(&mut value, "status"))?,
+            headers: Traced::from_trace(trace::take_field(&mut value, "headers"))?,
+            body: Traced::from_trace(trace::take_field(&mut value, "body"))?,
+        })
+    }
+}
+
+/// The most redirects a request follows, as reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// The error of a redirect to a url that the policy does not allow.
+#[derive(Debug)]
+struct RedirectDenied(String);
+
+impl Display for RedirectDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the redirect to '{}' is not allowed", self.0)
+    }
+}
+
+impl std::error::Error for RedirectDenied {}
+
+/// A client that checks every redirect it follows against `policy`, so an
+/// allowed url cannot redirect the guest to one that is not.
+fn client(policy: Option<&Policy>) -> Client {
+    let Some(policy) = policy.cloned() else {
+        return Client::new();
+    };
+    Client::builder()
+        .redirect(redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if policy.allows_url(attempt.url().as_str()) {
+                attempt.follow()
+            } else {
+                let url = attempt.url().to_string();
+                attempt.error(RedirectDenied(url))
+            }
+        }))
+        .build()
+        .expect("failed to build the http client")
+}
+
+/// The url of the redirect that failed `e`, if the policy does not allow it.
+fn denied_redirect(e: &reqwest::Error) -> Option<&str> {
+    let source = std::error::Error::source(e)?;
+    source
+        .downcast_ref::<RedirectDenied>()
+        .map(|denied| denied.0.as_str())
+}
+
 #[derive(Clone, Default)]
 pub struct HttpClient {
     client: Client,
+    policy: Option<Policy>,
 }
 
 impl HttpClient {
-    pub fn new() -> Self {
+    pub fn new(policy: Option<Policy>) -> Self {
         Self {
-            client: Client::new(),
+            client: client(policy.as_ref()),
+            policy,
+        }
+    }
+
+    fn denied(url: &str) -> HttpError {
+        HttpError::InvalidUrl(format!(
+            "the policy of 'http-client' does not allow url '{url}'"
+        ))
+    }
+
+    /// Fail with `invalid-url` unless the capability's policy allows
+    /// requests to `url`.
+    fn check_url(&self, url: &str) -> Result<(), HttpError> {
+        match &self.policy {
+            Some(policy) if !policy.allows_url(url) => Err(Self::denied(url)),
+            _ => Ok(()),
         }
     }
 }
 
 #[async_trait]
 impl http_client::HttpClient for HttpClient {
+    #[tracing::instrument(
+        name = "http_client.request",
+        skip_all,
+        fields(
+            implementor = "http-client",
+            http.method = ?req.method,
+            http.url = %req.uri,
+            http.status_code = tracing::field::Empty
+        ),
+        err
+    )]
     async fn request(&mut self, req: Request<'_>) -> Result<Response, HttpError> {
-        let mut builder = self.client.request(req.method.into(), req.uri);
-        for header in req.headers {
-            builder = builder.header(header.0, header.1);
-        }
-        builder = builder.body(if let Some(body) = req.body {
-            String::from_utf8(body.to_vec()).unwrap()
-        } else {
-            "".to_string()
+        let args = json!({
+            "method": format!("{:?}", req.method),
+            "uri": req.uri,
+            "headers": req.headers,
+            "body": req.body,
         });
-        let res = builder.send().await?;
-
-        let status = res.status().as_u16();
-        let mut headers = vec![];
-        for (name, value) in res.headers().iter() {
-            headers.push((
-                name.as_str().to_string(),
-                value.to_str().unwrap().to_string(),
-            ));
-        }
-        let body = Some(res.bytes().await?.to_vec());
-        Ok(Response {
-            status,
-            headers: Some(headers),
-            body,
-        })
+        observe_capability_call(
+            "http-client",
+            "request",
+            trace::call("http-client", "request", args, async {
+                self.check_url(req.uri)?;
+                let mut builder = self.client.request(req.method.into(), req.uri);
+                for header in req.headers {
+                    builder = builder.header(header.0, header.1);
+                }
+                builder = builder.body(req.body.map(<[u8]>::to_vec).unwrap_or_default());
+                let res = builder
+                    .send()
+                    .await
+                    .map_err(|e| match denied_redirect(&e) {
+                        Some(url) => Self::denied(url),
+                        None => e.into(),
+                    })?;
+
+                let status = res.status().as_u16();
+                tracing::Span::current().record("http.status_code", status);
+                let mut headers = vec![];
+                for (name, value) in res.headers().iter() {
+                    let value = value.to_str().map_err(|_| {
+                        HttpError::ProtocolError(format!(
+                            "the value of response header '{name}' is not visible ascii"
+                        ))
+                    })?;
+                    headers.push((name.as_str().to_string(), value.to_string()));
+                }
+                let body = Some(res.bytes().await?.to_vec());
+                Ok(Response {
+                    status,
+                    headers: Some(headers),
+                    body,
+                })
+            }),
+        )
+        .await
     }
 }
 
@@ -77,3 +229,57 @@ impl From<reqwest::Error> for http_client::HttpError {
         Self::UnexpectedError(e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    use slight_file::Policy;
+
+    use super::{client, denied_redirect};
+
+    /// Serve `/allowed`, which redirects to `/denied`, on a local port.
+    fn serve_redirect() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0; 1024];
+                let n = stream.read(&mut request).unwrap();
+                let response = if request[..n].starts_with(b"GET /allowed ") {
+                    format!(
+                        "HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{port}/denied\r\nContent-Length: 0\r\n\r\n"
+                    )
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n".to_string()
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_checked() {
+        let port = serve_redirect();
+        let policy = Policy {
+            urls: Some(vec![format!("http://127.0.0.1:{port}/allowed")]),
+            ..Default::default()
+        };
+        let url = format!("http://127.0.0.1:{port}/allowed");
+
+        let e = client(Some(&policy)).get(&url).send().await.unwrap_err();
+        assert_eq!(
+            denied_redirect(&e),
+            Some(format!("http://127.0.0.1:{port}/denied").as_str())
+        );
+
+        // without a policy, redirects are followed
+        let res = client(None).get(&url).send().await.unwrap();
+        assert_eq!(res.url().path(), "/denied");
+    }
+}
@@ -26,3 +26,29 @@ pub fn on_server_init(_attr: TokenStream, item: TokenStream) -> TokenStream {
     )
     .into()
 }
+
+#[proc_macro_attribute]
+pub fn on_server_shutdown(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    // parse the item as rust Fn
+    let func = syn::parse_macro_input!(item as syn::ItemFn);
+    let func_name = &func.sig.ident;
+
+    // generate rust code
+    quote!(
+
+        struct HttpServerShutdown {}
+
+        impl http_server_shutdown::HttpServerShutdown for HttpServerShutdown {
+            fn on_server_shutdown() -> Result<(), String> {
+
+                #func
+
+                match #func_name() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+        }
+    )
+    .into()
+}
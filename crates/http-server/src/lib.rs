@@ -1,25 +1,29 @@
 #![allow(clippy::upper_case_acronyms)]
 
+use std::future::Future;
 use std::iter::zip;
 use std::net::{SocketAddr, ToSocketAddrs};
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use anyhow::{bail, Result};
 use crossbeam_utils::thread;
 use futures::executor::block_on;
 pub use http_server::add_to_linker;
 use http_server::*;
-use hyper::{Body, Server};
+use hyper::{Body, Server, StatusCode};
 use routerify::ext::RequestExt;
 use routerify::{Router, RouterBuilder, RouterService};
 use routerify_cors::enable_cors_all;
-use slight_common::{impl_resource, Builder, Ctx, WasmtimeBuildable};
+use slight_common::{impl_resource, metrics, Builder, Ctx, LimitExceeded, WasmtimeBuildable};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tracing::log;
+use tokio::sync::{watch, Notify};
+use tokio::task::AbortHandle;
+use tracing::{log, Instrument};
 
-pub use slight_http_api::HttpServerInit;
 use slight_http_api::{HttpBody, HttpHandler, HttpHeader, Method, Request};
+pub use slight_http_api::{HttpServerInit, HttpServerShutdown};
 
 wit_bindgen_wasmtime::export!("../../wit/http-server.wit");
 wit_error_rs::impl_error!(http_server::HttpRouterError);
@@ -131,11 +135,57 @@ impl ServerInner {
     }
 }
 
+/// How long in-flight requests may run after a shutdown signal, unless
+/// configured otherwise.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Spawns the connections of the server on tokio, keeping a handle to each
+/// one so the connections still open after the drain timeout can be
+/// aborted. Dropping the server future alone leaves them running.
+#[derive(Clone, Default)]
+struct ConnectionExecutor {
+    connections: Arc<Mutex<Vec<AbortHandle>>>,
+}
+
+impl ConnectionExecutor {
+    /// Abort the connections that are still open, and return how many.
+    fn abort_all(&self) -> usize {
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|connection| !connection.is_finished());
+        for connection in connections.iter() {
+            connection.abort();
+        }
+        connections.drain(..).count()
+    }
+}
+
+impl<F> hyper::rt::Executor<F> for ConnectionExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        let handle = tokio::spawn(fut).abort_handle();
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|connection| !connection.is_finished());
+        connections.push(handle);
+    }
+}
+
+/// The builder used to instantiate the guest for each request.
+///
+/// It is shared between the `HttpServer` and the running server, so that
+/// `update_state` can swap it while the server is listening.
+type SharedBuilder<T> = Arc<RwLock<Option<Builder<T>>>>;
+
 /// HttpServer capability
 #[derive(Clone)]
 pub struct HttpServer<T: WasmtimeBuildable> {
-    builder: Option<Builder<T>>,
+    builder: SharedBuilder<T>,
     closer: Option<Arc<Mutex<UnboundedSender<()>>>>,
+    drain_timeout: Duration,
+    stopped: Option<watch::Receiver<bool>>,
+    address: Option<String>,
 }
 
 impl<T> Default for HttpServer<T>
@@ -144,24 +194,61 @@ where
 {
     fn default() -> Self {
         Self {
-            builder: None,
+            builder: Arc::new(RwLock::new(None)),
             closer: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            stopped: None,
+            address: None,
         }
     }
 }
 
 impl<T: WasmtimeBuildable + Send + Sync + 'static> HttpServer<T> {
+    /// Set the builder used to instantiate the guest for each request.
+    ///
+    /// If the server is already running, new requests use the new builder
+    /// while in-flight requests finish on the instance they started with.
     pub fn update_state(&mut self, builder: Builder<T>) -> Result<()> {
-        self.builder = Some(builder);
+        *self.builder.write().unwrap() = Some(builder);
         Ok(())
     }
 
+    /// The builder new requests are instantiated with, if one is set.
+    pub fn guest_builder(&self) -> Option<Builder<T>> {
+        self.builder.read().unwrap().clone()
+    }
+
+    /// Set how long in-flight requests may run once the server is asked to
+    /// shut down. Must be called before the guest starts the server.
+    pub fn set_drain_timeout(&mut self, drain_timeout: Duration) {
+        self.drain_timeout = drain_timeout;
+    }
+
+    /// Serve on `address` instead of the address the guest asks for. Must be
+    /// called before the guest starts the server.
+    pub fn set_address(&mut self, address: String) {
+        self.address = Some(address);
+    }
+
     pub fn close(&mut self) {
         if let Some(c) = self.closer.clone() {
             // server was started, so send the termination message
             let _ = c.lock().unwrap().send(());
         }
     }
+
+    /// Stop accepting connections and wait until in-flight requests have
+    /// finished or the drain timeout has elapsed.
+    pub async fn shutdown(&mut self) {
+        self.close();
+        if let Some(mut stopped) = self.stopped.clone() {
+            while !*stopped.borrow() {
+                if stopped.changed().await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
 }
 
 impl<T: WasmtimeBuildable + Send + Sync + 'static> http_server::HttpServer for HttpServer<T> {
@@ -233,14 +320,27 @@ impl<T: WasmtimeBuildable + Send + Sync + 'static> http_server::HttpServer for H
         router: &Self::Router,
     ) -> Result<Self::Server, HttpRouterError> {
         // Shared states for all routes
-        let instance_builder = self.builder.as_ref().unwrap().clone();
+        if self.builder.read().unwrap().is_none() {
+            return Err(anyhow::anyhow!("http server has no guest builder").into());
+        }
+        let instance_builder = self.builder.clone();
+        let address = match &self.address {
+            Some(override_address) => {
+                log::info!("serving on {override_address} instead of {address}");
+                override_address.as_str()
+            }
+            None => address,
+        };
 
         // The outer builder is used to define the route paths, while creating a scope
         // for the inner builder which passes states to the route handler.
         let mut outer_builder: RouterBuilder<Body, http_server::HttpRouterError> =
             Router::builder()
                 .middleware(enable_cors_all())
-                .data(instance_builder);
+                .data(instance_builder)
+                // Request spans are children of the span that started the
+                // server, e.g. the span of the app when running several apps.
+                .data(tracing::Span::current());
 
         // There is a one-to-one mapping between the outer router's scope and inner router builder.
         let mut inner_routes = vec![];
@@ -278,15 +378,44 @@ impl<T: WasmtimeBuildable + Send + Sync + 'static> http_server::HttpServer for H
         // Defines the server
         let service = RouterService::new(built).map_err(|e| anyhow::anyhow!(e))?;
         let addr = str_to_socket_address(address)?;
-        let server = Server::bind(&addr).serve(service);
+        let executor = ConnectionExecutor::default();
+        let server = Server::bind(&addr)
+            .executor(executor.clone())
+            .serve(service);
         // Create a channel to send the termination message
         let (tx, rx) = unbounded_channel();
-        let graceful = server.with_graceful_shutdown(shutdown_signal(rx));
-        // Start the server in a separate thread
-        tokio::task::spawn(graceful);
+        let draining = Arc::new(Notify::new());
+        let graceful = server.with_graceful_shutdown(shutdown_signal(rx, draining.clone()));
+        let (stopped_tx, stopped_rx) = watch::channel(false);
+        let drain_timeout = self.drain_timeout;
+        // Start the server in a separate thread. Once a shutdown is requested,
+        // the server stops accepting connections and in-flight requests get
+        // `drain_timeout` to finish before their connections are aborted.
+        tokio::task::spawn(async move {
+            tokio::select! {
+                res = graceful => {
+                    if let Err(e) = res {
+                        log::error!("http server error: {}", e);
+                    }
+                }
+                _ = async {
+                    draining.notified().await;
+                    tokio::time::sleep(drain_timeout).await;
+                } => {
+                    let aborted = executor.abort_all();
+                    log::warn!(
+                        "in-flight requests did not finish within {:?}, aborted {} connection(s)",
+                        drain_timeout,
+                        aborted
+                    );
+                }
+            }
+            let _ = stopped_tx.send(true);
+        });
 
         let arc_tx = Arc::new(Mutex::new(tx));
         self.closer = Some(arc_tx.clone());
+        self.stopped = Some(stopped_rx);
         Ok(ServerInner { closer: arc_tx })
     }
 
@@ -300,6 +429,35 @@ impl<T: WasmtimeBuildable + Send + Sync + 'static> http_server::HttpServer for H
 
 async fn handler<T: WasmtimeBuildable + Send + Sync + 'static>(
     request: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, http_server::HttpRouterError> {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .data::<Route>()
+        .map(|route| route.route.clone())
+        .unwrap_or_default();
+    let parent = request.data::<tracing::Span>().and_then(|span| span.id());
+    let span = tracing::info_span!(
+        parent: parent,
+        "http.request",
+        http.method = %method,
+        http.target = %request.uri().path(),
+        http.route = %route,
+        http.status_code = tracing::field::Empty,
+    );
+    let res = handle_request::<T>(request).instrument(span.clone()).await;
+
+    let status = match &res {
+        Ok(res) => res.status(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    span.record("http.status_code", status.as_u16());
+    metrics::observe_http_request(&route, &method, status.as_u16(), start.elapsed());
+    res
+}
+
+async fn handle_request<T: WasmtimeBuildable + Send + Sync + 'static>(
+    request: hyper::Request<Body>,
 ) -> Result<hyper::Response<Body>, http_server::HttpRouterError> {
     log::debug!("received request: {:?}", &request);
     let (parts, body) = request.into_parts();
@@ -309,11 +467,26 @@ async fn handler<T: WasmtimeBuildable + Send + Sync + 'static>(
         .data::<Route>()
         .ok_or_else(|| http_server::HttpRouterError::InvalidUrl("missing route".to_owned()))?;
 
+    // Clone the current builder, so that a concurrent `update_state` does
+    // not affect this request.
     let instance_builder = parts
-        .data::<Builder<T>>()
+        .data::<SharedBuilder<T>>()
+        .and_then(|builder| builder.read().unwrap().clone())
         .ok_or_else(|| anyhow::anyhow!("missing builder".to_owned()))?;
-    let instance_builder = instance_builder.clone();
-    let (mut store, instance) = instance_builder.owned_inner().build().await;
+    let (mut store, instance) = match instance_builder.owned_inner().build().await {
+        Ok(built) => built,
+        Err(e) => match LimitExceeded::from_error(&e) {
+            Some(limit) => {
+                log::warn!(
+                    "building the instance for {} failed: {}",
+                    route.handler,
+                    limit
+                );
+                return Ok(limit_exceeded_response(limit));
+            }
+            None => return Err(e.into()),
+        },
+    };
     // Perform conversion from the `hyper::Request` to `handle_http_server::Request`.
     let params = parts.params();
     let params: Vec<(&str, &str)> = params
@@ -342,23 +515,53 @@ async fn handler<T: WasmtimeBuildable + Send + Sync + 'static>(
     // Invoke the handler with http request
     log::debug!("invoking handler: {}", handler_name);
 
-    let res = handler
-        .handle_http(&mut store, req)
-        .await
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    let res = match handler.handle_http(&mut store, req).await {
+        Ok(res) => res.map_err(|e| anyhow::anyhow!(e.to_string()))?,
+        Err(e) => match LimitExceeded::from_error(&e) {
+            Some(limit) => {
+                log::warn!("handler {} failed: {}", handler_name, limit);
+                return Ok(limit_exceeded_response(limit));
+            }
+            None => return Err(anyhow::anyhow!(e.to_string()).into()),
+        },
+    };
 
     // Perform the conversion from `handle_http::Response` to `hyper::Response`.
     log::debug!("response: {:?}", res);
     Ok(res.into())
 }
 
-async fn shutdown_signal(mut rx: UnboundedReceiver<()>) {
+/// Build the response for a handler that exceeded a guest resource limit.
+fn limit_exceeded_response(limit: LimitExceeded) -> hyper::Response<Body> {
+    let mut response = hyper::Response::new(Body::from(limit.to_string()));
+    *response.status_mut() =
+        StatusCode::from_u16(limit.status_code()).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+    response
+}
+
+async fn shutdown_signal(mut rx: UnboundedReceiver<()>, draining: Arc<Notify>) {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                log::warn!("failed to install SIGTERM signal handler: {}", e);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
     tokio::select! {
         _ = tokio::signal::ctrl_c() => {},
+        _ = terminate => {},
         _ = rx.recv() => {},
     }
-    log::info!("shutting down the server")
+    log::info!("shutting down the server, draining in-flight requests");
+    draining.notify_one();
 }
 
 fn str_to_socket_address(s: &str) -> Result<SocketAddr> {
@@ -379,10 +582,28 @@ impl_resource!(
 
 #[cfg(test)]
 mod unittests {
-    use super::str_to_socket_address;
+    use super::{limit_exceeded_response, str_to_socket_address};
     use anyhow::Result;
+    use hyper::StatusCode;
+    use slight_common::LimitExceeded;
     use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
+    #[test]
+    fn test_limit_exceeded_response() {
+        assert_eq!(
+            limit_exceeded_response(LimitExceeded::Deadline).status(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert_eq!(
+            limit_exceeded_response(LimitExceeded::Fuel).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            limit_exceeded_response(LimitExceeded::Memory).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
     #[test]
     fn test_str_to_socket_address() -> Result<()> {
         assert_eq!(
@@ -1,8 +1,8 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
-use aws_config::{from_env, meta::region::RegionProviderChain};
+use aws_config::from_env;
 use aws_sdk_dynamodb::model::{AttributeValue, Select};
-use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::{Client, Credentials, Region};
 
 use slight_common::BasicState;
 use slight_runtime_configs::get_from_state;
@@ -25,8 +25,9 @@ pub struct AwsDynamoDbImplementor {
 impl AwsDynamoDbImplementor {
     /// Creates a new `AwsDynamoDbImplementor` instance.
     ///
-    /// It uses the `aws_config::from_env()` for AWS Configuration.
-    /// It will access the AWS Configuration environment variables:
+    /// It reads the AWS credentials and region from the capability's
+    /// configs, and leaves the rest of the AWS configuration to
+    /// `aws_config::from_env()`:
     ///   - `AWS_ACCESS_KEY_ID`, and
     ///   - `AWS_SECRET_ACCESS_KEY`, and
     ///   - `AWS_REGION`, or `AWS_DEFAULT_REGION`
@@ -45,36 +46,34 @@ impl AwsDynamoDbImplementor {
     ///   }
     /// }
     /// ```
-    pub async fn new(slight_state: &BasicState, name: &str) -> Self {
-        let access_id = get_from_state("AWS_ACCESS_KEY_ID", slight_state)
-            .await
-            .unwrap();
-        std::env::set_var("AWS_ACCESS_KEY_ID", access_id);
+    pub async fn new(slight_state: &BasicState, name: &str) -> Result<Self> {
+        let access_id = get_from_state("AWS_ACCESS_KEY_ID", slight_state).await?;
+        let access_key = get_from_state("AWS_SECRET_ACCESS_KEY", slight_state).await?;
+        let region = match (
+            get_from_state("AWS_REGION", slight_state).await,
+            get_from_state("AWS_DEFAULT_REGION", slight_state).await,
+        ) {
+            (Ok(region), _) => region,
+            (Err(_), Ok(default_region)) => default_region,
+            (Err(_), Err(_)) => bail!("AWS_REGION or AWS_DEFAULT_REGION must be set"),
+        };
 
-        let access_key = get_from_state("AWS_SECRET_ACCESS_KEY", slight_state)
-            .await
-            .unwrap();
-        std::env::set_var("AWS_SECRET_ACCESS_KEY", access_key);
-
-        let region = get_from_state("AWS_REGION", slight_state).await;
-        let default_region = get_from_state("AWS_DEFAULT_REGION", slight_state).await;
-        if region.is_err() && default_region.is_err() {
-            panic!("AWS_REGION or AWS_DEFAULT_REGION must be set");
-        } else if region.is_err() {
-            std::env::set_var("AWS_DEFAULT_REGION", default_region.unwrap());
-        } else {
-            std::env::set_var("AWS_REGION", region.unwrap());
-        }
-
-        let region = RegionProviderChain::default_provider();
-        let config = from_env().region(region).load().await;
+        // the credentials and region are passed to the client instead of the
+        // process environment, so capabilities (and apps of `slight serve`)
+        // with different accounts do not overwrite each other's
+        let credentials = Credentials::new(access_id, access_key, None, None, "slight");
+        let config = from_env()
+            .credentials_provider(credentials)
+            .region(Region::new(region))
+            .load()
+            .await;
         let client = Client::new(&config);
         let table_name = name.into();
         log::info!(
             "Creating a new AWS DynamoDB resource with table name: {}",
             name
         );
-        Self { client, table_name }
+        Ok(Self { client, table_name })
     }
 }
 
@@ -95,8 +94,10 @@ impl KeyvalueImplementor for AwsDynamoDbImplementor {
             .await?;
         match res.items.unwrap_or_default().pop() {
             Some(item) => {
-                let value = item.get("value").unwrap();
-                let value = value.as_s().unwrap();
+                let value = item
+                    .get("value")
+                    .and_then(|value| value.as_s().ok())
+                    .with_context(|| format!("item for key {key} has no string value"))?;
                 Ok(value.as_bytes().to_vec())
             }
             None => bail!("no value found for key: {}", key),
@@ -106,7 +107,7 @@ impl KeyvalueImplementor for AwsDynamoDbImplementor {
     async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
         let key_attribute = AttributeValue::S(key.into());
         let value = AttributeValue::S(
-            String::from_utf8(value.to_vec()).expect("failed to convert value to String"),
+            String::from_utf8(value.to_vec()).context("failed to convert value to String")?,
         );
         log::info!("Setting key value pair: ({}, {:#?})", key, value);
 
@@ -129,11 +130,15 @@ impl KeyvalueImplementor for AwsDynamoDbImplementor {
             .send()
             .await?;
         let items = res.items.unwrap_or_default();
-        let keys = items
+        items
             .iter()
-            .map(|item| item.get("key").unwrap().as_s().unwrap().to_string())
-            .collect();
-        Ok(keys)
+            .map(|item| {
+                item.get("key")
+                    .and_then(|key| key.as_s().ok())
+                    .map(|key| key.to_string())
+                    .context("item has no string key")
+            })
+            .collect()
     }
 
     /// FIXME: should delete return a success if it is a noop
@@ -23,20 +23,16 @@ pub struct AzBlobImplementor {
 }
 
 impl AzBlobImplementor {
-    pub async fn new(slight_state: &BasicState, name: &str) -> Self {
-        let storage_account_name = get_from_state("AZURE_STORAGE_ACCOUNT", slight_state)
-            .await
-            .unwrap();
-        let storage_account_key = get_from_state("AZURE_STORAGE_KEY", slight_state)
-            .await
-            .unwrap();
+    pub async fn new(slight_state: &BasicState, name: &str) -> Result<Self> {
+        let storage_account_name = get_from_state("AZURE_STORAGE_ACCOUNT", slight_state).await?;
+        let storage_account_key = get_from_state("AZURE_STORAGE_KEY", slight_state).await?;
 
         let storage_credentials =
             StorageCredentials::Key(storage_account_name.clone(), storage_account_key);
         let service_client = BlobServiceClient::new(storage_account_name, storage_credentials);
 
         let container_client = service_client.container_client(name);
-        Self { container_client }
+        Ok(Self { container_client })
     }
 }
 
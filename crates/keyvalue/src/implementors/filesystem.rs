@@ -25,10 +25,14 @@ pub struct FilesystemImplementor {
 }
 
 impl FilesystemImplementor {
-    pub async fn new(_slight_state: &BasicState, name: &str) -> Self {
-        Self {
-            base: env::temp_dir().join(name).to_str().unwrap().to_owned(),
-        }
+    pub async fn new(_slight_state: &BasicState, name: &str) -> Result<Self> {
+        Ok(Self {
+            base: env::temp_dir()
+                .join(name)
+                .to_str()
+                .context("keyvalue name is not valid UTF-8")?
+                .to_owned(),
+        })
     }
 }
 
@@ -65,7 +69,7 @@ impl KeyvalueImplementor for FilesystemImplementor {
         let mut keys = Vec::new();
         for entry in fs::read_dir(&self.base).with_context(|| "failed to read base directory")? {
             let entry = entry.with_context(|| "failed to read base directory entry")?;
-            keys.push(entry.file_name().to_str().unwrap().to_owned());
+            keys.push(entry.file_name().to_string_lossy().into_owned());
         }
         Ok(keys)
     }
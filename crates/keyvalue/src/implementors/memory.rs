@@ -0,0 +1,95 @@
+//! An in-process keyvalue store, meant for tests (e.g., `slight test`).
+//!
+//! Stores live for the lifetime of the process, and are shared by every
+//! instance that opens the same name.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use slight_common::BasicState;
+
+use super::KeyvalueImplementor;
+
+type Stores = Mutex<HashMap<String, HashMap<String, Vec<u8>>>>;
+
+static STORES: OnceLock<Stores> = OnceLock::new();
+
+fn stores() -> &'static Stores {
+    STORES.get_or_init(Default::default)
+}
+
+/// Add `entries` to the store opened under `name`.
+pub fn seed(name: &str, entries: impl IntoIterator<Item = (String, Vec<u8>)>) {
+    stores()
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_default()
+        .extend(entries);
+}
+
+/// Empty every store.
+pub fn reset() {
+    stores().lock().unwrap().clear();
+}
+
+/// This is the underlying struct behind the `Memory` variant of the `KeyvalueImplementor` enum.
+#[derive(Debug, Clone)]
+pub struct MemoryImplementor {
+    name: String,
+}
+
+impl MemoryImplementor {
+    pub async fn new(_slight_state: &BasicState, name: &str) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl KeyvalueImplementor for MemoryImplementor {
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        match stores()
+            .lock()
+            .unwrap()
+            .get(&self.name)
+            .and_then(|store| store.get(key))
+        {
+            Some(value) => Ok(value.clone()),
+            None => bail!("key '{key}' not found"),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<()> {
+        stores()
+            .lock()
+            .unwrap()
+            .entry(self.name.clone())
+            .or_default()
+            .insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    async fn keys(&self) -> Result<Vec<String>> {
+        let mut keys: Vec<String> = stores()
+            .lock()
+            .unwrap()
+            .get(&self.name)
+            .map(|store| store.keys().cloned().collect())
+            .unwrap_or_default();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        if let Some(store) = stores().lock().unwrap().get_mut(&self.name) {
+            store.remove(key);
+        }
+        Ok(())
+    }
+}
@@ -7,6 +7,8 @@ pub mod awsdynamodb;
 pub mod azblob;
 #[cfg(feature = "filesystem")]
 pub mod filesystem;
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(feature = "redis")]
 pub mod redis;
 
@@ -20,14 +20,14 @@ pub struct RedisImplementor {
 }
 
 impl RedisImplementor {
-    pub async fn new(slight_state: &BasicState, name: &str) -> Self {
-        let connection_string = get_from_state("REDIS_ADDRESS", slight_state).await.unwrap();
-        let client = redis::Client::open(connection_string).unwrap();
+    pub async fn new(slight_state: &BasicState, name: &str) -> Result<Self> {
+        let connection_string = get_from_state("REDIS_ADDRESS", slight_state).await?;
+        let client = redis::Client::open(connection_string)?;
         let container_name = name.to_string();
-        Self {
+        Ok(Self {
             client,
             container_name,
-        }
+        })
     }
 }
 
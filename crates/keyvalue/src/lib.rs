@@ -1,9 +1,12 @@
 mod implementors;
 pub mod providers;
 
+#[cfg(feature = "memory")]
+pub use implementors::memory;
+
 use std::{fmt::Debug, sync::Arc};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
 use implementors::*;
 
@@ -11,14 +14,62 @@ use implementors::*;
 /// That is because `impl_resource!` accesses the `crate`'s
 /// `add_to_linker`, and not the `<interface>::add_to_linker` directly.
 use keyvalue::*;
-use slight_common::{impl_resource, BasicState};
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, TracedError},
+    BasicState,
+};
 use slight_file::capability_store::CapabilityStore;
 use slight_file::resource::KeyvalueResource::*;
-use slight_file::Resource;
+use slight_file::{Policy, Resource};
 wit_bindgen_wasmtime::export!({paths: ["../../wit/keyvalue.wit"], async: *});
 wit_error_rs::impl_error!(keyvalue::KeyvalueError);
 wit_error_rs::impl_from!(anyhow::Error, keyvalue::KeyvalueError::UnexpectedError);
 
+impl ErrorVariant for KeyvalueError {
+    fn variant(&self) -> &'static str {
+        match self {
+            KeyvalueError::KeyNotFound(_) => "key-not-found",
+            KeyvalueError::InvalidKey(_) => "invalid-key",
+            KeyvalueError::InvalidValue(_) => "invalid-value",
+            KeyvalueError::ConnectionError(_) => "connection-error",
+            KeyvalueError::AuthenticationError(_) => "authentication-error",
+            KeyvalueError::TimeoutError(_) => "timeout-error",
+            KeyvalueError::IoError(_) => "io-error",
+            KeyvalueError::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for KeyvalueError {
+    fn message(&self) -> String {
+        match self {
+            KeyvalueError::KeyNotFound(message)
+            | KeyvalueError::InvalidKey(message)
+            | KeyvalueError::InvalidValue(message)
+            | KeyvalueError::ConnectionError(message)
+            | KeyvalueError::AuthenticationError(message)
+            | KeyvalueError::TimeoutError(message)
+            | KeyvalueError::IoError(message)
+            | KeyvalueError::UnexpectedError(message) => message.clone(),
+        }
+    }
+
+    fn from_variant(variant: &str, message: String) -> Self {
+        match variant {
+            "key-not-found" => KeyvalueError::KeyNotFound(message),
+            "invalid-key" => KeyvalueError::InvalidKey(message),
+            "invalid-value" => KeyvalueError::InvalidValue(message),
+            "connection-error" => KeyvalueError::ConnectionError(message),
+            "authentication-error" => KeyvalueError::AuthenticationError(message),
+            "timeout-error" => KeyvalueError::TimeoutError(message),
+            "io-error" => KeyvalueError::IoError(message),
+            _ => KeyvalueError::UnexpectedError(message),
+        }
+    }
+}
+
 /// The `Keyvalue` structure is what will implement the `keyvalue::Keyvalue` trait
 /// coming from the generated code of off `keyvalue.wit`.
 ///
@@ -62,6 +113,9 @@ impl Keyvalue {
 #[derive(Clone, Debug)]
 pub struct KeyvalueInner {
     keyvalue_implementor: Arc<dyn KeyvalueImplementor + Send + Sync>,
+    name: String,
+    implementor: Resource,
+    policy: Option<Policy>,
 }
 
 impl KeyvalueInner {
@@ -69,26 +123,44 @@ impl KeyvalueInner {
         keyvalue_implementor: KeyvalueImplementors,
         slight_state: &BasicState,
         name: &str,
-    ) -> Self {
-        Self {
+    ) -> Result<Self> {
+        Ok(Self {
             keyvalue_implementor: match keyvalue_implementor {
                 #[cfg(feature = "filesystem")]
                 KeyvalueImplementors::Filesystem => {
-                    Arc::new(filesystem::FilesystemImplementor::new(slight_state, name).await)
+                    Arc::new(filesystem::FilesystemImplementor::new(slight_state, name).await?)
                 }
                 #[cfg(feature = "azblob")]
                 KeyvalueImplementors::AzBlob => {
-                    Arc::new(azblob::AzBlobImplementor::new(slight_state, name).await)
+                    Arc::new(azblob::AzBlobImplementor::new(slight_state, name).await?)
                 }
                 #[cfg(feature = "awsdynamodb")]
                 KeyvalueImplementors::AwsDynamoDb => {
-                    Arc::new(awsdynamodb::AwsDynamoDbImplementor::new(slight_state, name).await)
+                    Arc::new(awsdynamodb::AwsDynamoDbImplementor::new(slight_state, name).await?)
                 }
                 #[cfg(feature = "redis")]
                 KeyvalueImplementors::Redis => {
-                    Arc::new(redis::RedisImplementor::new(slight_state, name).await)
+                    Arc::new(redis::RedisImplementor::new(slight_state, name).await?)
+                }
+                #[cfg(feature = "memory")]
+                KeyvalueImplementors::Memory => {
+                    Arc::new(memory::MemoryImplementor::new(slight_state, name).await?)
                 }
             },
+            name: name.to_string(),
+            implementor: slight_state.implementor,
+            policy: slight_state.policy.clone(),
+        })
+    }
+
+    /// Fail with `authentication-error` unless the capability's policy
+    /// allows access to `key`.
+    fn check_key(&self, key: &str) -> Result<(), KeyvalueError> {
+        match &self.policy {
+            Some(policy) if !policy.allows_key(key) => Err(KeyvalueError::AuthenticationError(
+                format!("the policy of '{}' does not allow key '{key}'", self.name),
+            )),
+            _ => Ok(()),
         }
     }
 }
@@ -107,22 +179,30 @@ pub enum KeyvalueImplementors {
     AwsDynamoDb,
     #[cfg(feature = "redis")]
     Redis,
+    #[cfg(feature = "memory")]
+    Memory,
 }
 
-impl From<Resource> for KeyvalueImplementors {
-    fn from(s: Resource) -> Self {
+impl TryFrom<Resource> for KeyvalueImplementors {
+    type Error = anyhow::Error;
+
+    fn try_from(s: Resource) -> Result<Self> {
         match s {
             #[cfg(feature = "filesystem")]
-            Resource::Keyvalue(Filesystem) | Resource::Keyvalue(V1Filesystem) => Self::Filesystem,
+            Resource::Keyvalue(Filesystem) | Resource::Keyvalue(V1Filesystem) => {
+                Ok(Self::Filesystem)
+            }
             #[cfg(feature = "azblob")]
-            Resource::Keyvalue(Azblob) | Resource::Keyvalue(V1Azblob) => Self::AzBlob,
+            Resource::Keyvalue(Azblob) | Resource::Keyvalue(V1Azblob) => Ok(Self::AzBlob),
             #[cfg(feature = "awsdynamodb")]
             Resource::Keyvalue(AwsDynamoDb) | Resource::Keyvalue(V1AwsDynamoDb) => {
-                Self::AwsDynamoDb
+                Ok(Self::AwsDynamoDb)
             }
             #[cfg(feature = "redis")]
-            Resource::Keyvalue(Redis) | Resource::Keyvalue(V1Redis) => Self::Redis,
-            p => panic!(
+            Resource::Keyvalue(Redis) | Resource::Keyvalue(V1Redis) => Ok(Self::Redis),
+            #[cfg(feature = "memory")]
+            Resource::Keyvalue(Memory) => Ok(Self::Memory),
+            p => bail!(
                 "failed to match provided name (i.e., '{p}') to any known host implementations"
             ),
         }
@@ -150,60 +230,145 @@ impl_resource!(
 impl keyvalue::Keyvalue for Keyvalue {
     type Keyvalue = KeyvalueInner;
 
+    #[tracing::instrument(name = "keyvalue.open", skip(self), err)]
     async fn keyvalue_open(&mut self, name: &str) -> Result<Self::Keyvalue, KeyvalueError> {
-        // populate our inner keyvalue object w/ the state received from `slight`
-        // (i.e., what type of keyvalue implementor we are using), and the assigned
-        // name of the object.
-        let s = self.implementor.to_string();
-        let state = if let Some(r) = self.capability_store.get(name, "keyvalue") {
-            r.clone()
-        } else if let Some(r) = self.capability_store.get(&s, "keyvalue") {
-            r.clone()
-        } else {
-            panic!(
-                "could not find capability under name '{}' for implementor '{}'",
-                name, &s
-            );
-        };
-
-        tracing::log::info!("Opening implementor {}", &state.implementor);
-
-        let inner = Self::Keyvalue::new(state.implementor.into(), &state, name).await;
-
-        Ok(inner)
+        observe_capability_call(
+            self.implementor,
+            "open",
+            trace::open("keyvalue", "open", json!({"name": name}), async {
+                // populate our inner keyvalue object w/ the state received from `slight`
+                // (i.e., what type of keyvalue implementor we are using), and the assigned
+                // name of the object.
+                let s = self.implementor.to_string();
+                let state = if let Some(r) = self.capability_store.get(name, "keyvalue") {
+                    r.clone()
+                } else if let Some(r) = self.capability_store.get(&s, "keyvalue") {
+                    r.clone()
+                } else {
+                    return Err(KeyvalueError::UnexpectedError(format!(
+                        "could not find capability under name '{name}' for implementor '{s}'"
+                    )));
+                };
+
+                tracing::log::info!("Opening implementor {}", &state.implementor);
+
+                let implementor: KeyvalueImplementors = state.implementor.try_into()?;
+                let inner = Self::Keyvalue::new(implementor, &state, name)
+                    .await
+                    .map_err(|e| KeyvalueError::ConnectionError(format!("{e:#}")))?;
+
+                Ok(inner)
+            }),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "keyvalue.get",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn keyvalue_get(
         &mut self,
         self_: &Self::Keyvalue,
         key: &str,
     ) -> Result<Vec<u8>, KeyvalueError> {
-        Ok(self_.keyvalue_implementor.get(key).await?)
+        observe_capability_call(
+            self_.implementor,
+            "get",
+            trace::call(
+                "keyvalue",
+                "get",
+                json!({"name": self_.name, "key": key}),
+                async {
+                    self_.check_key(key)?;
+                    Ok(self_.keyvalue_implementor.get(key).await?)
+                },
+            ),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "keyvalue.set",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn keyvalue_set(
         &mut self,
         self_: &Self::Keyvalue,
         key: &str,
         value: &[u8],
     ) -> Result<(), KeyvalueError> {
-        self_.keyvalue_implementor.set(key, value).await?;
-        Ok(())
+        observe_capability_call(
+            self_.implementor,
+            "set",
+            trace::call(
+                "keyvalue",
+                "set",
+                json!({"name": self_.name, "key": key, "value": value}),
+                async {
+                    self_.check_key(key)?;
+                    self_.keyvalue_implementor.set(key, value).await?;
+                    Ok(())
+                },
+            ),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "keyvalue.keys",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn keyvalue_keys(
         &mut self,
         self_: &Self::Keyvalue,
     ) -> Result<Vec<String>, KeyvalueError> {
-        Ok(self_.keyvalue_implementor.keys().await?)
+        observe_capability_call(
+            self_.implementor,
+            "keys",
+            trace::call("keyvalue", "keys", json!({"name": self_.name}), async {
+                let keys = self_.keyvalue_implementor.keys().await?;
+                // only list the keys the guest may access
+                Ok(keys
+                    .into_iter()
+                    .filter(|key| self_.check_key(key).is_ok())
+                    .collect())
+            }),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "keyvalue.delete",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn keyvalue_delete(
         &mut self,
         self_: &Self::Keyvalue,
         key: &str,
     ) -> Result<(), KeyvalueError> {
-        self_.keyvalue_implementor.delete(key).await?;
-        Ok(())
+        observe_capability_call(
+            self_.implementor,
+            "delete",
+            trace::call(
+                "keyvalue",
+                "delete",
+                json!({"name": self_.name, "key": key}),
+                async {
+                    self_.check_key(key)?;
+                    self_.keyvalue_implementor.delete(key).await?;
+                    Ok(())
+                },
+            ),
+        )
+        .await
     }
 }
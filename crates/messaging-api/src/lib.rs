@@ -0,0 +1,52 @@
+use anyhow::Result;
+pub use messaging_handler::MessagingHandlerData;
+
+wit_bindgen_wasmtime::import!({paths: ["../../wit/messaging-handler.wit"], async: *});
+
+/// An exported messaging handler function from the wasm module
+///
+/// This is a wrapper implementation of the WIT generated `MessagingHandler`.
+/// Use `is_exported` to check for it before calling `new`.
+pub struct MessagingHandler<T> {
+    inner: messaging_handler::MessagingHandler<T>,
+}
+
+impl<T> AsRef<messaging_handler::MessagingHandler<T>> for MessagingHandler<T> {
+    fn as_ref(&self) -> &messaging_handler::MessagingHandler<T> {
+        &self.inner
+    }
+}
+
+impl<T> AsMut<messaging_handler::MessagingHandler<T>> for MessagingHandler<T> {
+    fn as_mut(&mut self) -> &mut messaging_handler::MessagingHandler<T> {
+        &mut self.inner
+    }
+}
+
+impl<T: Send> MessagingHandler<T> {
+    pub fn new(
+        store: impl wasmtime::AsContextMut<Data = T>,
+        instance: &wasmtime::Instance,
+        get_state: impl Fn(&mut T) -> &mut MessagingHandlerData + Send + Sync + Copy + 'static,
+    ) -> Result<Self> {
+        messaging_handler::MessagingHandler::new(store, instance, get_state)
+            .map(|inner| Self { inner })
+    }
+
+    /// Whether the module exports `on-message`.
+    pub fn is_exported(
+        store: impl wasmtime::AsContextMut<Data = T>,
+        instance: &wasmtime::Instance,
+    ) -> bool {
+        instance.get_func(store, "on-message").is_some()
+    }
+
+    pub async fn on_message(
+        &self,
+        caller: impl wasmtime::AsContextMut<Data = T>,
+        topic: &str,
+        payload: &[u8],
+    ) -> Result<Result<(), String>, anyhow::Error> {
+        self.inner.on_message(caller, topic, payload).await
+    }
+}
@@ -27,8 +27,8 @@ impl std::fmt::Debug for Pub {
 }
 
 impl Pub {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let akc = ApacheKafkaConfigs::from_state(slight_state).await.unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let akc = ApacheKafkaConfigs::from_state(slight_state).await?;
         let producer: BaseProducer = ClientConfig::new()
             .set("bootstrap.servers", &akc.bootstap_servers)
             .set("security.protocol", &akc.security_protocol)
@@ -36,14 +36,13 @@ impl Pub {
             .set("sasl.username", &akc.sasl_username)
             .set("sasl.password", &akc.sasl_password)
             .create()
-            .with_context(|| "failed to create producer client")
-            .unwrap(); // panic if we fail to create client
+            .with_context(|| "failed to create producer client")?;
 
         tracing::debug!("created producer client");
 
-        Self {
+        Ok(Self {
             producer: Arc::new(producer),
-        }
+        })
     }
 }
 
@@ -80,15 +79,15 @@ impl std::fmt::Debug for Sub {
 }
 
 impl Sub {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let akc = ApacheKafkaConfigs::from_state(slight_state).await.unwrap();
-        let group_id = get_from_state("CAK_GROUP_ID", slight_state).await.unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let akc = ApacheKafkaConfigs::from_state(slight_state).await?;
+        let group_id = get_from_state("CAK_GROUP_ID", slight_state).await?;
 
-        Self {
+        Ok(Self {
             apache_kafka_config: akc,
             group_id,
             consumers: Arc::new(Mutex::new(HashMap::new())),
-        }
+        })
     }
 }
 
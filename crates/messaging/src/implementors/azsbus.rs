@@ -28,29 +28,24 @@ impl std::fmt::Debug for AzSbusImplementor {
 }
 
 impl AzSbusImplementor {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let service_bus_namespace = get_from_state("AZURE_SERVICE_BUS_NAMESPACE", slight_state)
-            .await
-            .unwrap();
-        let policy_name = get_from_state("AZURE_POLICY_NAME", slight_state)
-            .await
-            .unwrap();
-        let policy_key = get_from_state("AZURE_POLICY_KEY", slight_state)
-            .await
-            .unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let service_bus_namespace =
+            get_from_state("AZURE_SERVICE_BUS_NAMESPACE", slight_state).await?;
+        let policy_name = get_from_state("AZURE_POLICY_NAME", slight_state).await?;
+        let policy_key = get_from_state("AZURE_POLICY_KEY", slight_state).await?;
 
         let http_client = azure_core::new_http_client();
 
-        Self {
+        Ok(Self {
             service_bus_namespace,
             policy_name,
             policy_key,
             http_client,
             subscription_tokens: Arc::new(Mutex::new(HashMap::new())),
-        }
+        })
     }
 
-    fn make_topic_client(&self, topic: &str) -> TopicClient {
+    fn make_topic_client(&self, topic: &str) -> Result<TopicClient> {
         TopicClient::new(
             self.http_client.clone(),
             &self.service_bus_namespace,
@@ -58,19 +53,18 @@ impl AzSbusImplementor {
             &self.policy_name,
             &self.policy_key,
         )
-        .unwrap()
+        .with_context(|| format!("failed to create a client for topic '{topic}'"))
     }
 }
 
 #[async_trait]
 impl PubImplementor for AzSbusImplementor {
     async fn publish(&self, msg: &[u8], topic: &str) -> Result<()> {
-        let topic_client = self.make_topic_client(topic);
+        let topic_client = self.make_topic_client(topic)?;
+        let msg =
+            std::str::from_utf8(msg).context("azure service bus messages must be valid utf-8")?;
 
-        topic_client
-            .topic_sender()
-            .send_message(std::str::from_utf8(msg).unwrap())
-            .await?;
+        topic_client.topic_sender().send_message(msg).await?;
 
         Ok(())
     }
@@ -81,7 +75,7 @@ impl SubImplementor for AzSbusImplementor {
     async fn subscribe(&self, topic: &str) -> Result<String> {
         let sub_tok = uuid::Uuid::new_v4().to_string();
 
-        let topic_client = self.make_topic_client(topic);
+        let topic_client = self.make_topic_client(topic)?;
 
         let receiver = topic_client.subscription_receiver(topic);
 
@@ -13,10 +13,10 @@ pub struct FilesystemImplementor {
 }
 
 impl FilesystemImplementor {
-    pub fn new(name: &str) -> Self {
-        Self {
-            pubsub: Pubsub::open(name).unwrap(),
-        }
+    pub fn new(name: &str) -> Result<Self> {
+        Ok(Self {
+            pubsub: Pubsub::open(name)?,
+        })
     }
 }
 
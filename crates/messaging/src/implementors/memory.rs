@@ -0,0 +1,102 @@
+//! An in-process message broker, meant for tests (e.g., `slight test`).
+//!
+//! Every topic keeps all the messages published to it. A new subscription
+//! starts at the first message of its topic, so seeded messages can be
+//! received by subscribers that come after them.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::{PubImplementor, SubImplementor};
+
+#[derive(Debug, Default)]
+struct Broker {
+    topics: HashMap<String, Vec<Vec<u8>>>,
+    /// subscription token -> (topic, index of the next message)
+    subscriptions: HashMap<String, (String, usize)>,
+}
+
+static BROKERS: OnceLock<Mutex<HashMap<String, Broker>>> = OnceLock::new();
+
+fn brokers() -> &'static Mutex<HashMap<String, Broker>> {
+    BROKERS.get_or_init(Default::default)
+}
+
+/// Publish `messages` to `topic` on the broker opened under `name`.
+pub fn seed(name: &str, topic: &str, messages: impl IntoIterator<Item = Vec<u8>>) {
+    brokers()
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_default()
+        .topics
+        .entry(topic.to_string())
+        .or_default()
+        .extend(messages);
+}
+
+/// Drop every topic and subscription.
+pub fn reset() {
+    brokers().lock().unwrap().clear();
+}
+
+/// This is the underlying struct behind the `Memory` variant of the implementors enum.
+#[derive(Debug, Clone)]
+pub struct MemoryImplementor {
+    name: String,
+}
+
+impl MemoryImplementor {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl PubImplementor for MemoryImplementor {
+    async fn publish(&self, msg: &[u8], topic: &str) -> Result<()> {
+        seed(&self.name, topic, [msg.to_vec()]);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SubImplementor for MemoryImplementor {
+    async fn subscribe(&self, topic: &str) -> Result<String> {
+        let sub_token = uuid::Uuid::new_v4().to_string();
+        brokers()
+            .lock()
+            .unwrap()
+            .entry(self.name.clone())
+            .or_default()
+            .subscriptions
+            .insert(sub_token.clone(), (topic.to_string(), 0));
+        Ok(sub_token)
+    }
+
+    async fn receive(&self, sub_tok: &str) -> Result<Vec<u8>> {
+        let mut brokers = brokers().lock().unwrap();
+        let broker = brokers
+            .get_mut(&self.name)
+            .context("no subscription found per given token")?;
+        let (topic, next) = broker
+            .subscriptions
+            .get_mut(sub_tok)
+            .context("no subscription found per given token")?;
+        let msg = broker
+            .topics
+            .get(topic.as_str())
+            .and_then(|messages| messages.get(*next))
+            .with_context(|| format!("no messages left on topic '{topic}'"))?
+            .clone();
+        *next += 1;
+        Ok(msg)
+    }
+}
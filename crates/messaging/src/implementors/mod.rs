@@ -7,6 +7,8 @@ pub mod apache_kafka;
 pub mod azsbus;
 #[cfg(feature = "filesystem")]
 pub mod filesystem;
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(feature = "mosquitto")]
 pub mod mosquitto;
 #[cfg(feature = "natsio")]
@@ -44,32 +44,31 @@ impl std::fmt::Debug for Sub {
 }
 
 impl Pub {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let host = get_from_state("MOSQUITTO_HOST", slight_state)
-            .await
-            .unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let host = get_from_state("MOSQUITTO_HOST", slight_state).await?;
         let port = get_from_state("MOSQUITTO_PORT", slight_state)
-            .await
-            .unwrap()
+            .await?
             .parse::<i32>()
-            .unwrap();
+            .context("MOSQUITTO_PORT is not a valid port number")?;
 
         tracing::debug!("Connecting to Mosquitto broker at {}:{}", host, port);
 
         let producer = block_in_place(|| {
             Handle::current().block_on(async move {
-                let mut client = Client::with_auto_id().unwrap();
+                let mut client = Client::with_auto_id()?;
 
                 client
                     .connect(&host, port, std::time::Duration::from_secs(5), None)
                     .await
-                    .unwrap();
+                    .with_context(|| {
+                        format!("failed to connect to Mosquitto broker at {host}:{port}")
+                    })?;
 
-                Arc::new(Mutex::new(client))
+                Ok::<_, anyhow::Error>(Arc::new(Mutex::new(client)))
             })
-        });
+        })?;
 
-        Self { producer }
+        Ok(Self { producer })
     }
 }
 
@@ -92,23 +91,20 @@ impl PubImplementor for Pub {
 }
 
 impl Sub {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let host = get_from_state("MOSQUITTO_HOST", slight_state)
-            .await
-            .unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let host = get_from_state("MOSQUITTO_HOST", slight_state).await?;
         let port = get_from_state("MOSQUITTO_PORT", slight_state)
-            .await
-            .unwrap()
+            .await?
             .parse::<i32>()
-            .unwrap();
+            .context("MOSQUITTO_PORT is not a valid port number")?;
 
         tracing::info!("Connecting to Mosquitto broker at {}:{}", host, port);
 
-        Self {
+        Ok(Self {
             host,
             port,
             consumers: Arc::new(Mutex::new(HashMap::new())),
-        }
+        })
     }
 }
 
@@ -19,19 +19,19 @@ pub struct NatsIoImplementor {
 }
 
 impl NatsIoImplementor {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        let nats_creds = get_from_state("NATS_CREDS", slight_state).await.unwrap();
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        let nats_creds = get_from_state("NATS_CREDS", slight_state).await?;
 
         let connection = nats::Options::with_static_credentials(&nats_creds)
-            .unwrap()
+            .context("NATS_CREDS are not valid credentials")?
             .connect("connect.ngs.global")
-            .unwrap();
+            .context("failed to connect to NATS")?;
         let subscription_tokens = Arc::new(Mutex::new(HashMap::new()));
 
-        Self {
+        Ok(Self {
             connection,
             subscription_tokens,
-        }
+        })
     }
 }
 
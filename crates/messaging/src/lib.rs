@@ -1,15 +1,27 @@
 mod implementors;
 pub mod providers;
+mod trigger;
+
+#[cfg(feature = "memory")]
+pub use implementors::memory;
+pub use slight_messaging_api::MessagingHandler;
+pub use trigger::MessagingTrigger;
+
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 
 use implementors::{PubImplementor, SubImplementor, *};
-use slight_common::{impl_resource, BasicState};
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, TracedError},
+    BasicState,
+};
 use slight_file::capability_store::CapabilityStore;
 use slight_file::resource::MessagingResource::*;
-use slight_file::Resource;
+use slight_file::{Policy, Resource};
 
 /// It is mandatory to `use <interface>::*` due to `impl_resource!`.
 /// That is because `impl_resource!` accesses the `crate`'s
@@ -24,6 +36,49 @@ wit_error_rs::impl_from!(
     messaging::MessagingError::UnexpectedError
 );
 
+impl ErrorVariant for MessagingError {
+    fn variant(&self) -> &'static str {
+        match self {
+            MessagingError::PayloadTooLarge(_) => "payload-too-large",
+            MessagingError::QueueOrTopicNotFound(_) => "queue-or-topic-not-found",
+            MessagingError::InsufficientPermissions(_) => "insufficient-permissions",
+            MessagingError::ServiceUnavailable(_) => "service-unavailable",
+            MessagingError::DeliveryFailed(_) => "delivery-failed",
+            MessagingError::ConnectionLost(_) => "connection-lost",
+            MessagingError::UnsupportedMessageFormat(_) => "unsupported-message-format",
+            MessagingError::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for MessagingError {
+    fn message(&self) -> String {
+        match self {
+            MessagingError::PayloadTooLarge(message)
+            | MessagingError::QueueOrTopicNotFound(message)
+            | MessagingError::InsufficientPermissions(message)
+            | MessagingError::ServiceUnavailable(message)
+            | MessagingError::DeliveryFailed(message)
+            | MessagingError::ConnectionLost(message)
+            | MessagingError::UnsupportedMessageFormat(message)
+            | MessagingError::UnexpectedError(message) => message.clone(),
+        }
+    }
+
+    fn from_variant(variant: &str, message: String) -> Self {
+        match variant {
+            "payload-too-large" => MessagingError::PayloadTooLarge(message),
+            "queue-or-topic-not-found" => MessagingError::QueueOrTopicNotFound(message),
+            "insufficient-permissions" => MessagingError::InsufficientPermissions(message),
+            "service-unavailable" => MessagingError::ServiceUnavailable(message),
+            "delivery-failed" => MessagingError::DeliveryFailed(message),
+            "connection-lost" => MessagingError::ConnectionLost(message),
+            "unsupported-message-format" => MessagingError::UnsupportedMessageFormat(message),
+            _ => MessagingError::UnexpectedError(message),
+        }
+    }
+}
+
 /// The `Messaging` structure is what will implement the `messaging::Messaging` trait
 /// coming from the generated code of off `messaging.wit`.
 ///
@@ -42,6 +97,9 @@ pub struct Messaging {
 #[derive(Clone, Debug)]
 pub struct PubInner {
     pub_implementor: Arc<dyn PubImplementor + Send + Sync>,
+    name: String,
+    implementor: Resource,
+    policy: Option<Policy>,
 }
 
 impl PubInner {
@@ -54,25 +112,30 @@ impl PubInner {
             pub_implementor: match messaging_implementor {
                 #[cfg(feature = "filesystem")]
                 MessagingImplementors::Filesystem => {
-                    Arc::new(filesystem::FilesystemImplementor::new(name))
+                    Arc::new(filesystem::FilesystemImplementor::new(name)?)
                 }
                 #[cfg(feature = "mosquitto")]
                 MessagingImplementors::Mosquitto => {
-                    Arc::new(mosquitto::Pub::new(slight_state).await)
+                    Arc::new(mosquitto::Pub::new(slight_state).await?)
                 }
                 #[cfg(feature = "apache_kafka")]
                 MessagingImplementors::ConfluentApacheKafka => {
-                    Arc::new(apache_kafka::Pub::new(slight_state).await)
+                    Arc::new(apache_kafka::Pub::new(slight_state).await?)
                 }
                 #[cfg(feature = "azsbus")]
                 MessagingImplementors::AzSbus => {
-                    Arc::new(azsbus::AzSbusImplementor::new(slight_state).await)
+                    Arc::new(azsbus::AzSbusImplementor::new(slight_state).await?)
                 }
                 #[cfg(feature = "natsio")]
                 MessagingImplementors::Nats => {
-                    Arc::new(natsio::NatsIoImplementor::new(slight_state).await)
+                    Arc::new(natsio::NatsIoImplementor::new(slight_state).await?)
                 }
+                #[cfg(feature = "memory")]
+                MessagingImplementors::Memory => Arc::new(memory::MemoryImplementor::new(name)),
             },
+            name: name.to_string(),
+            implementor: slight_state.implementor,
+            policy: slight_state.policy.clone(),
         })
     }
 }
@@ -80,6 +143,9 @@ impl PubInner {
 #[derive(Clone, Debug)]
 pub struct SubInner {
     sub_implementor: Arc<dyn SubImplementor + Send + Sync>,
+    name: String,
+    implementor: Resource,
+    policy: Option<Policy>,
 }
 
 impl SubInner {
@@ -92,31 +158,49 @@ impl SubInner {
             sub_implementor: match messaging_implementor {
                 #[cfg(feature = "filesystem")]
                 MessagingImplementors::Filesystem => {
-                    Arc::new(filesystem::FilesystemImplementor::new(name))
+                    Arc::new(filesystem::FilesystemImplementor::new(name)?)
                 }
                 #[cfg(feature = "mosquitto")]
                 MessagingImplementors::Mosquitto => {
-                    Arc::new(mosquitto::Sub::new(slight_state).await)
+                    Arc::new(mosquitto::Sub::new(slight_state).await?)
                 }
                 #[cfg(feature = "apache_kafka")]
                 MessagingImplementors::ConfluentApacheKafka => {
-                    Arc::new(apache_kafka::Sub::new(slight_state).await)
+                    Arc::new(apache_kafka::Sub::new(slight_state).await?)
                 }
                 #[cfg(feature = "azsbus")]
                 MessagingImplementors::AzSbus => {
-                    Arc::new(azsbus::AzSbusImplementor::new(slight_state).await)
+                    Arc::new(azsbus::AzSbusImplementor::new(slight_state).await?)
                 }
                 #[cfg(feature = "natsio")]
                 MessagingImplementors::Nats => {
-                    Arc::new(natsio::NatsIoImplementor::new(slight_state).await)
+                    Arc::new(natsio::NatsIoImplementor::new(slight_state).await?)
                 }
+                #[cfg(feature = "memory")]
+                MessagingImplementors::Memory => Arc::new(memory::MemoryImplementor::new(name)),
             },
+            name: name.to_string(),
+            implementor: slight_state.implementor,
+            policy: slight_state.policy.clone(),
         };
 
         Ok(sub_implementor)
     }
 }
 
+/// Fail with `insufficient-permissions` unless the capability's policy
+/// allows access to `topic`.
+fn check_topic(policy: &Option<Policy>, name: &str, topic: &str) -> Result<(), MessagingError> {
+    match policy {
+        Some(policy) if !policy.allows_topic(topic) => {
+            Err(MessagingError::InsufficientPermissions(format!(
+                "the policy of '{name}' does not allow topic '{topic}'"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
 #[derive(Clone, Debug)]
 struct MessagingState {
     pub_implementor: PubInner,
@@ -125,18 +209,21 @@ struct MessagingState {
 
 impl Messaging {
     pub async fn new(name: &str, capability_store: CapabilityStore<BasicState>) -> Result<Self> {
-        let state = capability_store.get(name, "messaging").unwrap().clone();
+        let state = capability_store
+            .get(name, "messaging")
+            .with_context(|| format!("could not find messaging capability under name '{name}'"))?
+            .clone();
 
         tracing::log::info!("Opening implementor {}", &state.implementor);
 
-        let p = PubInner::new(state.implementor.into(), &state, name).await?;
-        let s = SubInner::new(state.implementor.into(), &state, name).await?;
+        let p = PubInner::new(state.implementor.try_into()?, &state, name).await?;
+        let s = SubInner::new(state.implementor.try_into()?, &state, name).await?;
 
         let mut messaging_store: CapabilityStore<MessagingState> = CapabilityStore::new();
         capability_store
             .as_ref()
             .get("messaging")
-            .unwrap()
+            .context("no messaging capabilities found")?
             .iter()
             .for_each(|c| {
                 messaging_store.insert(
@@ -176,14 +263,33 @@ impl messaging::Messaging for Messaging {
         }
     }
 
+    #[tracing::instrument(
+        name = "messaging.publish",
+        skip(self, self_, message),
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn pub_publish(
         &mut self,
         self_: &Self::Pub,
         message: &[u8],
         topic: &str,
     ) -> Result<(), MessagingError> {
-        self_.pub_implementor.publish(message, topic).await?;
-        Ok(())
+        observe_capability_call(
+            self_.implementor,
+            "publish",
+            trace::call(
+                "messaging",
+                "publish",
+                json!({"name": self_.name, "topic": topic, "message": message}),
+                async {
+                    check_topic(&self_.policy, &self_.name, topic)?;
+                    self_.pub_implementor.publish(message, topic).await?;
+                    Ok(())
+                },
+            ),
+        )
+        .await
     }
 
     async fn sub_open(&mut self, name: &str) -> Result<Self::Sub, MessagingError> {
@@ -195,21 +301,58 @@ impl messaging::Messaging for Messaging {
         }
     }
 
+    #[tracing::instrument(
+        name = "messaging.subscribe",
+        skip(self, self_),
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn sub_subscribe(
         &mut self,
         self_: &Self::Sub,
         topic: &str,
     ) -> Result<String, MessagingError> {
-        Ok(self_.sub_implementor.subscribe(topic).await?)
+        observe_capability_call(
+            self_.implementor,
+            "subscribe",
+            trace::call(
+                "messaging",
+                "subscribe",
+                json!({"name": self_.name, "topic": topic}),
+                async {
+                    check_topic(&self_.policy, &self_.name, topic)?;
+                    Ok(self_.sub_implementor.subscribe(topic).await?)
+                },
+            ),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "messaging.receive",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn sub_receive(
         &mut self,
         self_: &Self::Sub,
         sub_tok: SubscriptionTokenParam<'_>,
     ) -> Result<Vec<u8>, MessagingError> {
-        info!("token: {:?}", sub_tok);
-        Ok(self_.sub_implementor.receive(sub_tok).await?)
+        observe_capability_call(
+            self_.implementor,
+            "receive",
+            trace::call(
+                "messaging",
+                "receive",
+                json!({"name": self_.name, "sub_tok": sub_tok}),
+                async {
+                    info!("token: {:?}", sub_tok);
+                    Ok(self_.sub_implementor.receive(sub_tok).await?)
+                },
+            ),
+        )
+        .await
     }
 }
 
@@ -228,22 +371,30 @@ pub enum MessagingImplementors {
     AzSbus,
     #[cfg(feature = "natsio")]
     Nats,
+    #[cfg(feature = "memory")]
+    Memory,
 }
 
-impl From<Resource> for MessagingImplementors {
-    fn from(s: Resource) -> Self {
+impl TryFrom<Resource> for MessagingImplementors {
+    type Error = anyhow::Error;
+
+    fn try_from(s: Resource) -> Result<Self> {
         match s {
             #[cfg(feature = "apache_kafka")]
-            Resource::Messaging(ConfluentApacheKafka) => Self::ConfluentApacheKafka,
+            Resource::Messaging(ConfluentApacheKafka) => Ok(Self::ConfluentApacheKafka),
             #[cfg(feature = "mosquitto")]
-            Resource::Messaging(Mosquitto) => Self::Mosquitto,
+            Resource::Messaging(Mosquitto) => Ok(Self::Mosquitto),
             #[cfg(feature = "filesystem")]
-            Resource::Messaging(Filesystem) | Resource::Messaging(V1Filesystem) => Self::Filesystem,
+            Resource::Messaging(Filesystem) | Resource::Messaging(V1Filesystem) => {
+                Ok(Self::Filesystem)
+            }
             #[cfg(feature = "azsbus")]
-            Resource::Messaging(Azsbus) | Resource::Messaging(V1Azsbus) => Self::AzSbus,
+            Resource::Messaging(Azsbus) | Resource::Messaging(V1Azsbus) => Ok(Self::AzSbus),
             #[cfg(feature = "natsio")]
-            Resource::Messaging(Nats) => Self::Nats,
-            p => panic!(
+            Resource::Messaging(Nats) => Ok(Self::Nats),
+            #[cfg(feature = "memory")]
+            Resource::Messaging(Memory) => Ok(Self::Memory),
+            p => bail!(
                 "failed to match provided name (i.e., '{p}') to any known host implementations"
             ),
         }
@@ -0,0 +1,248 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use slight_common::{BasicState, Ctx as _, LimitExceeded, WasmtimeBuildable};
+use slight_file::{capability_store::CapabilityStore, MessagingTrigger as TriggerConfig};
+use slight_messaging_api::MessagingHandler;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::Instrument;
+
+use crate::{check_topic, PubInner, SubInner};
+
+/// How long a subscription waits before receiving again after a receive
+/// failed, e.g., because no message was available.
+const RECEIVE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The delay before the first retry of a message, if the slightfile does
+/// not set one.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A host-driven messaging trigger.
+///
+/// The trigger subscribes to the topics of the slightfile's
+/// `[messaging_trigger]` section, and calls the guest's `on-message` export
+/// with each message it receives. Every message is handled by a fresh
+/// instance built from the guest builder.
+pub struct MessagingTrigger<T: WasmtimeBuildable> {
+    builder: T,
+    subscriptions: Vec<Subscription>,
+    concurrency: usize,
+    retry: RetryPolicy,
+}
+
+struct Subscription {
+    capability: String,
+    topic: String,
+    token: String,
+    sub: SubInner,
+    /// Publishes to the dead letter topic, if there is one.
+    dead_letter: Option<PubInner>,
+}
+
+#[derive(Clone)]
+struct RetryPolicy {
+    max_retries: u32,
+    delay: Duration,
+    dead_letter_topic: Option<String>,
+}
+
+impl<T: WasmtimeBuildable + Send + Sync + 'static> MessagingTrigger<T> {
+    /// Subscribe to the topics of `config`, using the messaging capabilities
+    /// of `capability_store`.
+    pub async fn new(
+        config: &TriggerConfig,
+        capability_store: &CapabilityStore<BasicState>,
+        builder: T,
+    ) -> Result<Self> {
+        let mut subscriptions = vec![];
+        for subscription in &config.subscriptions {
+            let name = &subscription.capability;
+            let topic = &subscription.topic;
+            let state = capability_store.get(name, "messaging").with_context(|| {
+                format!("could not find messaging capability under name '{name}'")
+            })?;
+            check_topic(&state.policy, name, topic)?;
+
+            let sub = SubInner::new(state.implementor.try_into()?, state, name).await?;
+            let token = sub
+                .sub_implementor
+                .subscribe(topic)
+                .await
+                .with_context(|| format!("failed to subscribe to topic '{topic}' with '{name}'"))?;
+            let dead_letter = match &config.dead_letter_topic {
+                Some(_) => Some(PubInner::new(state.implementor.try_into()?, state, name).await?),
+                None => None,
+            };
+            tracing::info!("messaging trigger subscribed to topic '{topic}' with '{name}'");
+            subscriptions.push(Subscription {
+                capability: name.clone(),
+                topic: topic.clone(),
+                token,
+                sub,
+                dead_letter,
+            });
+        }
+
+        Ok(Self {
+            builder,
+            subscriptions,
+            concurrency: config.concurrency.unwrap_or(1).max(1),
+            retry: RetryPolicy {
+                max_retries: config.max_retries.unwrap_or(0),
+                delay: config
+                    .retry_delay_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_RETRY_DELAY),
+                dead_letter_topic: config.dead_letter_topic.clone(),
+            },
+        })
+    }
+
+    /// Handle messages until `shutdown` completes, then wait for the
+    /// messages that are being handled.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let mut receivers = JoinSet::new();
+        for subscription in self.subscriptions {
+            receivers.spawn(receive(
+                Arc::new(subscription),
+                self.builder.clone(),
+                semaphore.clone(),
+                self.retry.clone(),
+            ));
+        }
+
+        shutdown.await;
+        tracing::info!("stopping the messaging trigger");
+        receivers.abort_all();
+        // every permit is back once the last message is handled
+        let _ = semaphore.acquire_many(self.concurrency as u32).await?;
+        Ok(())
+    }
+}
+
+/// Receive the messages of `subscription`, and handle each one on its own
+/// task once there is room for it.
+async fn receive<T: WasmtimeBuildable + Send + Sync + 'static>(
+    subscription: Arc<Subscription>,
+    builder: T,
+    semaphore: Arc<Semaphore>,
+    retry: RetryPolicy,
+) {
+    loop {
+        // Wait for room before taking a message off the broker, so that
+        // messages are not received faster than they are handled.
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        let payload = match subscription
+            .sub
+            .sub_implementor
+            .receive(&subscription.token)
+            .await
+        {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::trace!(
+                    "no message received on topic '{}': {e:#}",
+                    subscription.topic
+                );
+                drop(permit);
+                tokio::time::sleep(RECEIVE_BACKOFF).await;
+                continue;
+            }
+        };
+
+        let subscription = subscription.clone();
+        let builder = builder.clone();
+        let retry = retry.clone();
+        let span = tracing::info_span!(
+            "messaging.on_message",
+            resource = %subscription.capability,
+            topic = %subscription.topic,
+        );
+        tokio::spawn(
+            async move {
+                handle(&subscription, builder, &retry, &payload).await;
+                drop(permit);
+            }
+            .instrument(span),
+        );
+    }
+}
+
+/// Call `on-message` with `payload`, retrying according to `retry`.
+async fn handle<T: WasmtimeBuildable + Send + Sync + 'static>(
+    subscription: &Subscription,
+    builder: T,
+    retry: &RetryPolicy,
+    payload: &[u8],
+) {
+    let topic = &subscription.topic;
+    let attempts = retry.max_retries.saturating_add(1);
+    let mut delay = retry.delay;
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            tokio::time::sleep(delay).await;
+            delay = delay.saturating_mul(2);
+        }
+        let start = Instant::now();
+        match on_message(builder.clone(), topic, payload).await {
+            Ok(()) => {
+                tracing::info!(
+                    "handled message on topic '{topic}' in {:?} (attempt {attempt} of {attempts})",
+                    start.elapsed()
+                );
+                return;
+            }
+            Err(e) => tracing::warn!(
+                "failed to handle message on topic '{topic}' (attempt {attempt} of {attempts}): {e:#}"
+            ),
+        }
+    }
+
+    match (&retry.dead_letter_topic, &subscription.dead_letter) {
+        (Some(dead_letter_topic), Some(publisher)) => {
+            let res = match check_topic(&publisher.policy, &publisher.name, dead_letter_topic) {
+                Ok(()) => {
+                    publisher
+                        .pub_implementor
+                        .publish(payload, dead_letter_topic)
+                        .await
+                }
+                Err(e) => Err(anyhow!(e)),
+            };
+            match res {
+                Ok(()) => tracing::warn!(
+                    "moved message on topic '{topic}' to '{dead_letter_topic}' after {attempts} failed attempt(s)"
+                ),
+                Err(e) => tracing::error!(
+                    "dropping message on topic '{topic}', publishing it to '{dead_letter_topic}' failed: {e:#}"
+                ),
+            }
+        }
+        _ => tracing::error!(
+            "dropping message on topic '{topic}' after {attempts} failed attempt(s)"
+        ),
+    }
+}
+
+/// Call `on-message` on a fresh instance of the guest.
+async fn on_message<T: WasmtimeBuildable>(builder: T, topic: &str, payload: &[u8]) -> Result<()> {
+    let (mut store, instance) = builder.build().await?;
+    let handler =
+        MessagingHandler::new(&mut store, &instance, |ctx| ctx.get_messaging_handler_mut())?;
+    match handler.on_message(&mut store, topic, payload).await {
+        Ok(res) => res.map_err(|e| anyhow!(e)),
+        Err(e) => Err(match LimitExceeded::from_error(&e) {
+            Some(limit) => anyhow!(limit),
+            None => e,
+        }),
+    }
+}
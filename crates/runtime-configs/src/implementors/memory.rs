@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+
+static CONFIGS: OnceLock<Mutex<HashMap<String, Vec<u8>>>> = OnceLock::new();
+
+fn configs() -> &'static Mutex<HashMap<String, Vec<u8>>> {
+    CONFIGS.get_or_init(Default::default)
+}
+
+/// In-process configs, meant for tests (e.g., `slight test`).
+///
+/// Configs are shared by every `configs.memory` capability of the process.
+pub struct Memory;
+
+impl Memory {
+    pub fn get(key: &str) -> Result<Vec<u8>> {
+        configs()
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .with_context(|| format!("no config named '{key}' found"))
+    }
+
+    pub fn set(key: &str, value: &[u8]) -> Result<()> {
+        configs()
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    /// Add `entries` to the configs.
+    pub fn seed(entries: impl IntoIterator<Item = (String, Vec<u8>)>) {
+        configs().lock().unwrap().extend(entries);
+    }
+
+    /// Remove every config.
+    pub fn reset() {
+        configs().lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod unittests {
+    use anyhow::Result;
+
+    use super::Memory;
+
+    #[test]
+    fn set_then_get_test() -> Result<()> {
+        Memory::set("memory-key", "value".as_bytes())?;
+        assert_eq!(Memory::get("memory-key")?, b"value");
+        assert!(Memory::get("memory-missing-key").is_err());
+        Ok(())
+    }
+}
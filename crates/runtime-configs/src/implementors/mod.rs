@@ -1,3 +1,4 @@
 pub mod azapp;
 pub mod envvars;
+pub mod memory;
 pub mod usersecrets;
@@ -1,65 +1,25 @@
-use std::{fs::OpenOptions, path::Path};
+use std::path::Path;
 
-use anyhow::{bail, Result};
-use short_crypt::ShortCrypt;
-use slight_core::secret::{create_secret, get_key};
+use anyhow::Result;
+use slight_core::secret::{create_secret, get_secret};
 use slight_file::SlightFileBuilder;
 
 pub struct UserSecrets;
 
 impl UserSecrets {
     pub fn get(key: &str, toml_file_path: impl AsRef<Path>) -> Result<Vec<u8>> {
-        // check if encryption key env var is present
-        let encryption_key = if let Ok(s) = get_key() {
-            s
-        } else {
-            bail!("failed because user secrets has never been initialized")
-        };
-
-        // serialize toml file to get key
-        let toml_file_path = toml_file_path;
-        let toml = SlightFileBuilder::new().path(toml_file_path)?.build()?;
-        if toml.as_ref().secret_settings.is_none() {
-            bail!("failed because toml file has no secrets");
-        }
-
-        // get env var encryption key
-        let pos = toml
-            .as_ref()
-            .secret_settings
-            .as_ref()
-            .unwrap() // note: this unwrap will never fail, so it's ok
-            .iter()
-            .position(|s| s.name == key);
-
-        let value = if let Some(value) = pos {
-            &toml.as_ref().secret_settings.as_ref().unwrap()[value].value
-            // ^^^ note: the unwrap cannot fail
-        } else {
-            // if it isn't, we will just create new
-            bail!("failed because this secret isn't encrypted in the toml file")
-        };
-
-        // decrypt key and return value
-        let sc = ShortCrypt::new(encryption_key);
-        sc.decrypt_url_component(value)
-            .map_err(|err| anyhow::anyhow!(err))
+        let toml = SlightFileBuilder::new().path(&toml_file_path)?.build()?;
+        get_secret(key, toml.as_ref(), toml_file_path)
     }
 
     pub fn set(key: &str, value: &[u8], toml_file_path: impl AsRef<Path>) -> Result<()> {
         // call in to slight to handle config creation
-        let mut toml_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&toml_file_path)?;
         let mut toml = SlightFileBuilder::new().path(&toml_file_path)?.build()?;
-        toml_file.set_len(0)?;
         create_secret(
             key,
             std::str::from_utf8(value)?,
             toml.as_mut(),
-            &mut toml_file,
+            toml_file_path,
         )
     }
 }
@@ -90,7 +50,7 @@ mod unittests {
         toml_file.write_all(toml::to_string(&tmp_toml)?.as_bytes())?;
 
         UserSecrets::set("key", "value".as_bytes(), toml_file_pathstr)?;
-        assert!(UserSecrets::get("key", toml_file_pathstr).is_ok());
+        assert_eq!(UserSecrets::get("key", toml_file_pathstr)?, b"value");
         Ok(())
     }
 }
@@ -6,16 +6,57 @@ use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use regex::Regex;
 
-use implementors::{azapp::AzApp, envvars::EnvVars, usersecrets::UserSecrets};
-use slight_common::{impl_resource, BasicState};
+use implementors::{azapp::AzApp, envvars::EnvVars, memory::Memory, usersecrets::UserSecrets};
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, TracedError},
+    BasicState,
+};
 use slight_file::capability_store::CapabilityStore;
-use slight_file::resource::ConfigsResource::{Azapp, Envvars, Usersecrets};
+use slight_file::resource::ConfigsResource::{
+    Azapp, Envvars, Memory as MemoryConfigs, Usersecrets,
+};
 use slight_file::{Resource, SecretStoreResource};
 
 wit_bindgen_wasmtime::export!({paths: ["../../wit/configs.wit"], async: *});
 wit_error_rs::impl_error!(configs::ConfigsError);
 wit_error_rs::impl_from!(anyhow::Error, configs::ConfigsError::UnexpectedError);
 
+impl ErrorVariant for configs::ConfigsError {
+    fn variant(&self) -> &'static str {
+        match self {
+            configs::ConfigsError::NotFound(_) => "not-found",
+            configs::ConfigsError::InvalidValue(_) => "invalid-value",
+            configs::ConfigsError::PermissionDenied(_) => "permission-denied",
+            configs::ConfigsError::IoError(_) => "io-error",
+            configs::ConfigsError::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for configs::ConfigsError {
+    fn message(&self) -> String {
+        match self {
+            configs::ConfigsError::NotFound(message)
+            | configs::ConfigsError::InvalidValue(message)
+            | configs::ConfigsError::PermissionDenied(message)
+            | configs::ConfigsError::IoError(message)
+            | configs::ConfigsError::UnexpectedError(message) => message.clone(),
+        }
+    }
+
+    fn from_variant(variant: &str, message: String) -> Self {
+        match variant {
+            "not-found" => configs::ConfigsError::NotFound(message),
+            "invalid-value" => configs::ConfigsError::InvalidValue(message),
+            "permission-denied" => configs::ConfigsError::PermissionDenied(message),
+            "io-error" => configs::ConfigsError::IoError(message),
+            _ => configs::ConfigsError::UnexpectedError(message),
+        }
+    }
+}
+
 /// The `Configs` structure is what will implement the `configs::Configs` trait
 /// coming from the generated code of off `configs.wit`.
 ///
@@ -52,57 +93,106 @@ impl_resource!(
 impl configs::Configs for Configs {
     type Configs = ConfigsInner;
 
+    #[tracing::instrument(name = "configs.open", skip(self), err)]
     async fn configs_open(&mut self, name: &str) -> Result<Self::Configs, configs::ConfigsError> {
-        // populate our inner configs object w/ the state received from `slight`
-        // (i.e., what type of configs implementor we are using), and the assigned
-        // name of the object.
-        let s = self.implementor.to_string();
-        let state = if let Some(r) = self.capability_store.get(name, "configs") {
-            r.clone()
-        } else if let Some(r) = self.capability_store.get(&s, "configs") {
-            r.clone()
-        } else {
-            panic!(
-                "could not find capability under name '{}' for implementor '{}'",
-                name, &s
-            );
-        };
-
-        tracing::log::info!("Opening implementor {}", &state.implementor);
-
-        let inner = Self::Configs::new(state.implementor, &state);
-
-        Ok(inner)
+        observe_capability_call(
+            self.implementor,
+            "open",
+            trace::open("configs", "open", json!({"name": name}), async {
+                // populate our inner configs object w/ the state received from `slight`
+                // (i.e., what type of configs implementor we are using), and the assigned
+                // name of the object.
+                let s = self.implementor.to_string();
+                let state = if let Some(r) = self.capability_store.get(name, "configs") {
+                    r.clone()
+                } else if let Some(r) = self.capability_store.get(&s, "configs") {
+                    r.clone()
+                } else {
+                    return Err(configs::ConfigsError::NotFound(format!(
+                        "could not find capability under name '{name}' for implementor '{s}'"
+                    )));
+                };
+
+                tracing::log::info!("Opening implementor {}", &state.implementor);
+
+                let inner = Self::Configs::new(state.implementor, &state)?;
+
+                Ok(inner)
+            }),
+        )
+        .await
     }
 
+    #[tracing::instrument(
+        name = "configs.get",
+        skip_all,
+        fields(
+            resource = %self_.slight_state.name,
+            implementor = %self_.slight_state.implementor
+        ),
+        err
+    )]
     async fn configs_get(
         &mut self,
         self_: &Self::Configs,
         key: &str,
     ) -> Result<Vec<u8>, configs::ConfigsError> {
-        Ok(get(
-            self_.configs_implementor.clone(),
-            key,
-            &self_.slight_state.slightfile_path,
+        observe_capability_call(
+            self_.slight_state.implementor,
+            "get",
+            trace::call(
+                "configs",
+                "get",
+                json!({"name": self_.slight_state.name, "key": key}),
+                async {
+                    Ok(get(
+                        self_.configs_implementor.clone(),
+                        key,
+                        &self_.slight_state.slightfile_path,
+                    )
+                    .await?)
+                },
+            ),
         )
-        .await?)
+        .await
     }
 
+    #[tracing::instrument(
+        name = "configs.set",
+        skip_all,
+        fields(
+            resource = %self_.slight_state.name,
+            implementor = %self_.slight_state.implementor
+        ),
+        err
+    )]
     async fn configs_set(
         &mut self,
         self_: &Self::Configs,
         key: &str,
         value: &[u8],
     ) -> Result<(), configs::ConfigsError> {
-        set(
-            self_.configs_implementor.clone(),
-            key,
-            value,
-            &self_.slight_state.slightfile_path,
-        )
-        .await?;
+        observe_capability_call(
+            self_.slight_state.implementor,
+            "set",
+            trace::call(
+                "configs",
+                "set",
+                json!({"name": self_.slight_state.name, "key": key, "value": value}),
+                async {
+                    set(
+                        self_.configs_implementor.clone(),
+                        key,
+                        value,
+                        &self_.slight_state.slightfile_path,
+                    )
+                    .await?;
 
-        Ok(())
+                    Ok(())
+                },
+            ),
+        )
+        .await
     }
 }
 
@@ -126,11 +216,11 @@ pub struct ConfigsInner {
 }
 
 impl ConfigsInner {
-    fn new(configs_implementor: Resource, slight_state: &BasicState) -> Self {
-        Self {
-            configs_implementor: configs_implementor.into(),
+    fn new(configs_implementor: Resource, slight_state: &BasicState) -> Result<Self> {
+        Ok(Self {
+            configs_implementor: configs_implementor.try_into()?,
             slight_state: slight_state.clone(),
-        }
+        })
     }
 }
 
@@ -144,26 +234,33 @@ pub enum ConfigsImplementor {
     EnvVars,
     UserSecrets, // user creates configs at compile time that are encrypted and stored in their slightfile
     AzApp,
+    Memory, // in-process configs, used by `slight test`
 }
 
-impl From<ConfigsImplementor> for Resource {
-    fn from(c: ConfigsImplementor) -> Resource {
+impl TryFrom<ConfigsImplementor> for Resource {
+    type Error = anyhow::Error;
+
+    fn try_from(c: ConfigsImplementor) -> Result<Resource> {
         match c {
-            ConfigsImplementor::UserSecrets => Resource::Configs(Usersecrets),
-            ConfigsImplementor::EnvVars => Resource::Configs(Envvars),
-            ConfigsImplementor::AzApp => Resource::Configs(Azapp),
-            _ => panic!("unknown configuration type"),
+            ConfigsImplementor::UserSecrets => Ok(Resource::Configs(Usersecrets)),
+            ConfigsImplementor::EnvVars => Ok(Resource::Configs(Envvars)),
+            ConfigsImplementor::AzApp => Ok(Resource::Configs(Azapp)),
+            ConfigsImplementor::Memory => Ok(Resource::Configs(MemoryConfigs)),
+            ConfigsImplementor::Local => bail!("'configs.local' is not a configs capability"),
         }
     }
 }
 
-impl From<Resource> for ConfigsImplementor {
-    fn from(from: Resource) -> Self {
+impl TryFrom<Resource> for ConfigsImplementor {
+    type Error = anyhow::Error;
+
+    fn try_from(from: Resource) -> Result<Self> {
         match from {
-            Resource::Configs(Usersecrets) => ConfigsImplementor::UserSecrets,
-            Resource::Configs(Azapp) => ConfigsImplementor::AzApp,
-            Resource::Configs(Envvars) => ConfigsImplementor::EnvVars,
-            _ => panic!("unknown configuration type '{from}'"),
+            Resource::Configs(Usersecrets) => Ok(ConfigsImplementor::UserSecrets),
+            Resource::Configs(Azapp) => Ok(ConfigsImplementor::AzApp),
+            Resource::Configs(Envvars) => Ok(ConfigsImplementor::EnvVars),
+            Resource::Configs(MemoryConfigs) => Ok(ConfigsImplementor::Memory),
+            _ => bail!("unknown configuration type '{from}'"),
         }
     }
 }
@@ -179,14 +276,17 @@ impl From<SecretStoreResource> for ConfigsImplementor {
     }
 }
 
-impl From<&str> for ConfigsImplementor {
-    fn from(from_str: &str) -> Self {
+impl TryFrom<&str> for ConfigsImplementor {
+    type Error = anyhow::Error;
+
+    fn try_from(from_str: &str) -> Result<Self> {
         match from_str {
-            "configs.usersecrets" => ConfigsImplementor::UserSecrets,
-            "configs.envvars" => ConfigsImplementor::EnvVars,
-            "configs.azapp" => ConfigsImplementor::AzApp,
-            "configs.local" => ConfigsImplementor::Local,
-            _ => panic!("unknown configuration type '{from_str}'"),
+            "configs.usersecrets" => Ok(ConfigsImplementor::UserSecrets),
+            "configs.envvars" => Ok(ConfigsImplementor::EnvVars),
+            "configs.azapp" => Ok(ConfigsImplementor::AzApp),
+            "configs.local" => Ok(ConfigsImplementor::Local),
+            "configs.memory" => Ok(ConfigsImplementor::Memory),
+            _ => bail!("unknown configuration type '{from_str}'"),
         }
     }
 }
@@ -198,6 +298,7 @@ impl From<ConfigsImplementor> for String {
             ConfigsImplementor::EnvVars => "configs.envvars".to_string(),
             ConfigsImplementor::UserSecrets => "configs.usersecrets".to_string(),
             ConfigsImplementor::AzApp => "configs.azapp".to_string(),
+            ConfigsImplementor::Memory => "configs.memory".to_string(),
         }
     }
 }
@@ -213,6 +314,7 @@ pub async fn get(
         ConfigsImplementor::UserSecrets => Ok(UserSecrets::get(key, toml_file_path)?),
         ConfigsImplementor::AzApp => Ok(AzApp::get(key).await?),
         ConfigsImplementor::Local => Ok(key.as_bytes().to_vec()),
+        ConfigsImplementor::Memory => Ok(Memory::get(key)?),
     }
 }
 
@@ -226,6 +328,7 @@ pub async fn set(
         ConfigsImplementor::EnvVars => Ok(EnvVars::set(key, value)?),
         ConfigsImplementor::UserSecrets => Ok(UserSecrets::set(key, value, toml_file_path)?),
         ConfigsImplementor::AzApp => Ok(AzApp::set(key, value).await?),
+        ConfigsImplementor::Memory => Ok(Memory::set(key, value)?),
         _ => bail!("unknown configuration type"),
     }
 }
@@ -245,14 +348,19 @@ pub async fn get_from_state(config_name: &str, state: &BasicState) -> Result<Str
         let c = state
             .configs_map
             .as_ref()
-            .expect("this capability needs a [capability.configs] section...")
+            .with_context(|| {
+                format!(
+                    "capability '{}' needs a [capability.configs] section with '{config_name}'",
+                    state.name
+                )
+            })?
             .get(config_name)
             .with_context(|| format!("no config named '{config_name}' found"))?;
 
         let (store, name) = maybe_get_config_store_and_value(c)?;
 
         let config = String::from_utf8(
-            get(store.as_str().into(), &name, &state.slightfile_path)
+            get(store.as_str().try_into()?, &name, &state.slightfile_path)
                 .await
                 .with_context(|| {
                     format!(
@@ -271,7 +379,7 @@ fn maybe_get_config_store_and_value(c: &str) -> Result<(String, String)> {
         if let Some(cap) = regex_match.captures(&prelim_cap[1]) {
             Ok((format!("configs.{}", &cap[1]), cap[2].to_string()))
         } else {
-            panic!("failed to get value for config '{c}'");
+            bail!("failed to get value for config '{c}'");
         }
     } else {
         Ok(("configs.local".to_string(), c.to_string()))
@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use wasmtime::{Config, Engine, Module};
+
+/// The file extension used for serialized modules in the cache directory.
+const ARTIFACT_EXTENSION: &str = "cwasm";
+
+/// Engines shared by every builder in this process, keyed by engine options.
+///
+/// There is one engine per distinct set of options, so reloads with the same
+/// options reuse their engine.
+static ENGINES: OnceLock<Mutex<HashMap<String, Engine>>> = OnceLock::new();
+
+/// Compiled modules shared by every builder in this process, keyed by
+/// engine options and module path.
+static MODULES: OnceLock<Mutex<HashMap<(String, PathBuf), ModuleEntry>>> = OnceLock::new();
+
+/// The latest module compiled from a path, and the hash of its bytes.
+///
+/// A module whose hash does not match the bytes on disk is stale, e.g.,
+/// after a reload, and its entry is replaced.
+struct ModuleEntry {
+    hash: String,
+    compiled: Arc<Mutex<Option<Module>>>,
+}
+
+/// Get the `Engine` for the given options, creating it on first use.
+///
+/// Sharing engines allows builders with the same options to share
+/// compiled modules. `on_create` runs once, right after a new engine
+/// is created.
+pub(crate) fn engine(
+    engine_key: &str,
+    config: impl FnOnce() -> Result<Config>,
+    on_create: impl FnOnce(&Engine),
+) -> Result<Engine> {
+    let mut engines = ENGINES.get_or_init(Default::default).lock().unwrap();
+    if let Some(engine) = engines.get(engine_key) {
+        return Ok(engine.clone());
+    }
+    let engine = Engine::new(&config()?)?;
+    on_create(&engine);
+    engines.insert(engine_key.to_owned(), engine.clone());
+    Ok(engine)
+}
+
+/// Compile a module, or load it from the in-process or on-disk cache.
+///
+/// Each module is compiled at most once per process. If `cache_dir` is set,
+/// the compiled artifact is also serialized to disk and reused across runs.
+pub(crate) fn module(
+    engine: &Engine,
+    engine_key: &str,
+    module: impl AsRef<Path>,
+    cache_dir: Option<&Path>,
+) -> Result<Module> {
+    let module = module.as_ref();
+    let bytes = fs::read(module)
+        .with_context(|| format!("could not read module '{}'", module.display()))?;
+    let hash = to_hex(&Sha256::digest(&bytes));
+    let path = fs::canonicalize(module).unwrap_or_else(|_| module.to_owned());
+
+    let compiled = {
+        let mut modules = MODULES.get_or_init(Default::default).lock().unwrap();
+        let entry = modules
+            .entry((engine_key.to_owned(), path))
+            .or_insert_with(|| ModuleEntry {
+                hash: hash.clone(),
+                compiled: Default::default(),
+            });
+        if entry.hash != hash {
+            *entry = ModuleEntry {
+                hash: hash.clone(),
+                compiled: Default::default(),
+            };
+        }
+        entry.compiled.clone()
+    };
+
+    // Only the entry is locked while compiling, so that concurrent builders
+    // of the same module wait for the first compilation instead of
+    // compiling it again, and builders of other modules do not wait.
+    let mut compiled = compiled.lock().unwrap();
+    if let Some(compiled) = compiled.as_ref() {
+        return Ok(compiled.clone());
+    }
+    let key = (engine_key.to_owned(), hash);
+    let module = match cache_dir {
+        Some(dir) => load_or_compile(engine, &artifact_path(dir, &key), &bytes)?,
+        None => Module::new(engine, &bytes)?,
+    };
+    *compiled = Some(module.clone());
+    Ok(module)
+}
+
+/// The default directory for compiled modules.
+///
+/// It follows `XDG_CACHE_HOME`, then `$HOME/.cache`, and falls back to the
+/// system's temporary directory.
+pub fn default_cache_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("slight").join("modules")
+}
+
+fn load_or_compile(engine: &Engine, artifact: &Path, bytes: &[u8]) -> Result<Module> {
+    if artifact.exists() {
+        // SAFETY: artifacts in the cache directory are only written by
+        // `Module::serialize` below. wasmtime checks that the artifact was
+        // produced by a compatible engine version and configuration.
+        match unsafe { Module::deserialize_file(engine, artifact) } {
+            Ok(compiled) => {
+                tracing::info!("loaded compiled module from {}", artifact.display());
+                return Ok(compiled);
+            }
+            Err(e) => {
+                tracing::warn!("ignoring compiled module {}: {}", artifact.display(), e);
+            }
+        }
+    }
+
+    let compiled = Module::new(engine, bytes)?;
+    if let Err(e) = write_artifact(&compiled, artifact) {
+        tracing::warn!(
+            "could not write compiled module to {}: {}",
+            artifact.display(),
+            e
+        );
+    }
+    Ok(compiled)
+}
+
+fn write_artifact(compiled: &Module, artifact: &Path) -> Result<()> {
+    let dir = artifact
+        .parent()
+        .context("compiled module path has no parent directory")?;
+    fs::create_dir_all(dir)?;
+    // Write to a temporary file first so that a concurrent `slight run`
+    // never reads a partially written artifact.
+    let tmp = artifact.with_extension(format!("{ARTIFACT_EXTENSION}.{}", std::process::id()));
+    fs::write(&tmp, compiled.serialize()?)?;
+    fs::rename(&tmp, artifact)?;
+    tracing::info!("wrote compiled module to {}", artifact.display());
+    Ok(())
+}
+
+fn artifact_path(cache_dir: &Path, key: &(String, String)) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(key.0.as_bytes());
+    hasher.update(key.1.as_bytes());
+    cache_dir.join(format!(
+        "{}.{ARTIFACT_EXTENSION}",
+        to_hex(&hasher.finalize())
+    ))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod unittest {
+    use std::fs;
+
+    use tempfile::tempdir;
+    use wasmtime::Engine;
+
+    use super::{load_or_compile, module, MODULES};
+    use crate::default_config;
+
+    #[test]
+    fn test_load_or_compile_reuses_artifact() -> anyhow::Result<()> {
+        let tmp_dir = tempdir()?;
+        let artifact = tmp_dir.path().join("module.cwasm");
+        let bytes = fs::read("./test/keyvalue-test.wasm")?;
+        let engine = Engine::new(&default_config()?)?;
+
+        load_or_compile(&engine, &artifact, &bytes)?;
+        assert!(artifact.exists());
+        let modified = fs::metadata(&artifact)?.modified()?;
+
+        load_or_compile(&engine, &artifact, &bytes)?;
+        assert_eq!(fs::metadata(&artifact)?.modified()?, modified);
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_module_replaces_stale_entry() -> anyhow::Result<()> {
+        let tmp_dir = tempdir()?;
+        let path = tmp_dir.path().join("module.wat");
+        let engine = Engine::new(&default_config()?)?;
+        let engine_key = "test_changed_module_replaces_stale_entry";
+        let entries = || {
+            MODULES
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|(key, _)| key == engine_key)
+                .count()
+        };
+
+        fs::write(&path, "(module)")?;
+        let first = module(&engine, engine_key, &path, None)?;
+        assert!(first.get_export("run").is_none());
+
+        fs::write(&path, r#"(module (func (export "run")))"#)?;
+        let reloaded = module(&engine, engine_key, &path, None)?;
+        assert!(reloaded.get_export("run").is_some());
+        assert_eq!(entries(), 1);
+        Ok(())
+    }
+}
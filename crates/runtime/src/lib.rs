@@ -1,23 +1,38 @@
+mod cache;
 mod ctx;
+mod limits;
+mod pool;
 pub mod resource;
+mod wasi;
 
 use std::{
     fs::{File, OpenOptions},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use ctx::SlightCtxBuilder;
-use resource::{get_host_state, HttpData, HttpServerExportData};
-use slight_common::{CapabilityBuilder, WasmtimeBuildable, WasmtimeLinkable};
+use limits::StoreLimiter;
+use pool::InstancePool;
+use resource::{
+    get_host_state, HttpData, HttpServerExportData, HttpServerShutdownData, MessagingHandlerData,
+};
+use slight_common::{metrics, CapabilityBuilder, WasmtimeBuildable, WasmtimeLinkable};
 use tracing::info;
+use wasi::ReadOnlyDir;
 use wasi_cap_std_sync::{ambient_authority, Dir, WasiCtxBuilder};
 use wasi_common::pipe::{ReadPipe, WritePipe};
-use wasi_common::WasiCtx;
+use wasi_common::{dir::WasiDir, WasiCtx};
 use wasmtime::{Config, Engine, Instance, Linker, Module, Store};
 
+pub use cache::default_cache_dir;
 pub use ctx::SlightCtx;
+pub use limits::Limits;
+pub use pool::InstancePoolConfig;
+pub use wasi::{Preopen, WasiOptions};
 /// Runtime Context for the wasm module
 pub type Ctx = RuntimeContext;
 
@@ -27,6 +42,7 @@ pub type Ctx = RuntimeContext;
 ///    - `wasi`: a wasi context
 ///    - `slight`: a slight context
 ///    - `http_state`: http handler's data
+///    - `messaging_handler_state`: messaging handler's data
 ///
 /// The runtime context will be used inside of the `Builder`
 /// to build a `Store` and `Instance` for the wasm module.
@@ -36,6 +52,9 @@ pub struct RuntimeContext {
     pub slight: SlightCtx,
     pub http_state: HttpData,
     pub http_server_state: HttpServerExportData,
+    pub http_server_shutdown_state: HttpServerShutdownData,
+    pub messaging_handler_state: MessagingHandlerData,
+    limiter: StoreLimiter,
 }
 
 impl slight_common::Ctx for RuntimeContext {
@@ -53,6 +72,14 @@ impl slight_common::Ctx for RuntimeContext {
     fn get_http_server_mut(&mut self) -> &mut slight_http_api::HttpServerExportData {
         &mut self.http_server_state
     }
+
+    fn get_http_server_shutdown_mut(&mut self) -> &mut HttpServerShutdownData {
+        &mut self.http_server_shutdown_state
+    }
+
+    fn get_messaging_handler_mut(&mut self) -> &mut MessagingHandlerData {
+        &mut self.messaging_handler_state
+    }
 }
 
 /// Input and output redirects to be used for the running module
@@ -63,6 +90,40 @@ pub struct IORedirects {
     pub stdin_path: Option<PathBuf>,
 }
 
+/// Options used to create the `Engine` and compile the `Module` of a `Builder`.
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeOptions {
+    /// Keep pre-instantiated instances warm, optionally using wasmtime's
+    /// pooling allocator. No instances are kept warm if the size is 0.
+    pub instance_pool: Option<InstancePoolConfig>,
+    /// Directory used to cache compiled modules across runs.
+    pub module_cache_dir: Option<PathBuf>,
+    /// Resource limits applied to each guest instance.
+    pub limits: Limits,
+}
+
+impl RuntimeOptions {
+    /// The wasmtime configuration for these options.
+    pub fn engine_config(&self) -> Result<Config> {
+        let mut config = default_config()?;
+        if let Some(pool_config) = &self.instance_pool {
+            pool_config.apply(&mut config, &self.limits)?;
+        }
+        self.limits.apply(&mut config);
+        Ok(config)
+    }
+
+    /// A key identifying the engine configuration. Builders with the same
+    /// key share an `Engine` and its compiled modules.
+    fn engine_key(&self) -> String {
+        let allocator = match &self.instance_pool {
+            Some(pool_config) => pool_config.engine_key(&self.limits),
+            None => InstancePoolConfig::default().engine_key(&self.limits),
+        };
+        format!("{allocator};{}", self.limits.engine_key())
+    }
+}
+
 /// A wasmtime-based runtime builder.
 ///
 /// It knows how to build a `Store` and `Instance` for a wasm module, given
@@ -74,15 +135,51 @@ pub struct Builder {
     module: Module,
     state_builder: SlightCtxBuilder,
     io_redirects: IORedirects,
+    wasi: WasiOptions,
+    pool: Option<Arc<InstancePool>>,
+    limits: Limits,
 }
 
 impl Builder {
     /// Create a new runtime builder.
     pub fn from_module(module: impl AsRef<Path>) -> Result<Self> {
-        let engine = Engine::new(&default_config()?)?;
+        Self::from_module_with_options(module, &RuntimeOptions::default())
+    }
+
+    /// Create a new runtime builder with the given runtime options.
+    ///
+    /// Builders created with the same engine options share an `Engine`, so
+    /// each module is compiled at most once per process. If an instance pool
+    /// is configured, it stays empty until `prewarm` is called. Every `build`
+    /// then hands out a warm instance if one is available and instantiates a
+    /// replacement in the background.
+    pub fn from_module_with_options(
+        module: impl AsRef<Path>,
+        options: &RuntimeOptions,
+    ) -> Result<Self> {
+        let engine_key = options.engine_key();
+        let engine = cache::engine(
+            &engine_key,
+            || options.engine_config(),
+            |engine| {
+                if options.limits.needs_epoch_ticker() {
+                    limits::start_epoch_ticker(engine.clone());
+                }
+            },
+        )?;
         let mut linker = Linker::new(&engine);
         linker.allow_shadowing(true);
-        let module = Module::from_file(&engine, module)?;
+        let module = cache::module(
+            &engine,
+            &engine_key,
+            module,
+            options.module_cache_dir.as_deref(),
+        )?;
+        let pool = options
+            .instance_pool
+            .as_ref()
+            .filter(|pool_config| pool_config.size > 0)
+            .map(|pool_config| Arc::new(InstancePool::new(pool_config.size)));
 
         Ok(Self {
             linker,
@@ -90,15 +187,45 @@ impl Builder {
             module,
             state_builder: SlightCtxBuilder::default(),
             io_redirects: IORedirects::default(),
+            wasi: WasiOptions::default(),
+            pool,
+            limits: options.limits,
         })
     }
 
+    /// Fill the instance pool, if any, with pre-instantiated instances.
+    ///
+    /// This must be called after all capabilities and I/O redirects are
+    /// set on the builder, because warm instances are created from the
+    /// builder's current state.
+    pub async fn prewarm(&self) -> Result<()> {
+        if let Some(pool) = &self.pool {
+            while !pool.is_full() {
+                let warm = self.clone().instantiate().await?;
+                pool.put(warm);
+            }
+            tracing::info!("instance pool is warm");
+        }
+        Ok(())
+    }
+
+    /// The compiled guest module.
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
     /// Set the I/O redirects for the module
     pub fn set_io(mut self, io_redirects: IORedirects) -> Self {
         self.io_redirects = io_redirects;
         self
     }
 
+    /// Set the WASI preopens, environment and arguments for the module
+    pub fn set_wasi(mut self, wasi: WasiOptions) -> Self {
+        self.wasi = wasi;
+        self
+    }
+
     /// Link wasi to the wasmtime::Linker
     pub fn link_wasi(&mut self) -> Result<&mut Self> {
         wasmtime_wasi::add_to_linker(&mut self.linker, |cx: &mut Ctx| cx.wasi.as_mut().unwrap())?;
@@ -121,16 +248,43 @@ impl Builder {
         });
         self
     }
+
+    /// Instantiate a fresh `Store` and `Instance` for the guest module.
+    async fn instantiate(self) -> Result<(Store<Ctx>, Instance)> {
+        let wasi = build_wasi_context(self.io_redirects, self.wasi)?;
+        let ctx = RuntimeContext {
+            wasi: Some(wasi),
+            slight: self.state_builder.build(),
+            http_state: HttpData::default(),
+            http_server_state: HttpServerExportData::default(),
+            http_server_shutdown_state: HttpServerShutdownData::default(),
+            messaging_handler_state: MessagingHandlerData::default(),
+            limiter: self.limits.limiter(),
+        };
+
+        let mut store = Store::new(&self.engine, ctx);
+        store.limiter(|ctx| &mut ctx.limiter);
+        self.limits.arm(&mut store)?;
+        let instance = self
+            .linker
+            .instantiate_async(&mut store, &self.module)
+            .await?;
+        Ok((store, instance))
+    }
 }
 
-fn maybe_open_stdio(pipe_path: &Path) -> Option<File> {
+/// Open the pipe at `pipe_path`, if there is one. Every instance opens the
+/// pipes again, so output pipes are opened to `append` instead of
+/// overwriting the output of earlier instances.
+fn maybe_open_stdio(pipe_path: &Path, append: bool) -> Option<File> {
     if pipe_path.as_os_str().is_empty() {
         None
     } else {
         Some(
             OpenOptions::new()
                 .read(true)
-                .write(true)
+                .write(!append)
+                .append(append)
                 .open(pipe_path)
                 .unwrap_or_else(|_| {
                     panic!(
@@ -146,34 +300,72 @@ fn maybe_open_stdio(pipe_path: &Path) -> Option<File> {
 impl WasmtimeBuildable for Builder {
     type Ctx = Ctx;
 
-    /// Instantiate the guest module.
-    async fn build(self) -> (Store<Self::Ctx>, Instance) {
-        let wasi = build_wasi_context(self.io_redirects).unwrap();
-        let ctx = RuntimeContext {
-            wasi: Some(wasi),
-            slight: self.state_builder.build(),
-            http_state: HttpData::default(),
-            http_server_state: HttpServerExportData::default(),
-        };
-
-        let mut store = Store::new(&self.engine, ctx);
-        let instance = self
-            .linker
-            .instantiate_async(&mut store, &self.module)
-            .await
-            .unwrap();
-        (store, instance)
+    /// Instantiate the guest module, or take a warm instance from the pool.
+    async fn build(self) -> Result<(Store<Self::Ctx>, Instance)> {
+        let start = Instant::now();
+        if let Some(pool) = self.pool.clone() {
+            if let Some((mut store, instance)) = pool.take() {
+                // only replace the instance that was taken, so an empty
+                // pool does not pile up refills
+                let builder = self.clone();
+                tokio::spawn(async move {
+                    match builder.instantiate().await {
+                        Ok(replacement) => pool.put(replacement),
+                        Err(e) => tracing::error!("failed to refill the instance pool: {e:?}"),
+                    }
+                });
+                self.limits.arm(&mut store)?;
+                metrics::observe_instance_build(start.elapsed());
+                return Ok((store, instance));
+            }
+        }
+        let built = self.instantiate().await?;
+        metrics::observe_instance_build(start.elapsed());
+        Ok(built)
     }
 }
 
-fn build_wasi_context(io_redirects: IORedirects) -> Result<WasiCtx> {
+fn build_wasi_context(io_redirects: IORedirects, wasi: WasiOptions) -> Result<WasiCtx> {
     let mut ctx: WasiCtxBuilder = WasiCtxBuilder::new();
     ctx = add_io_redirects_to_wasi_context(ctx, io_redirects)?;
-    let dir = Dir::open_ambient_dir(".", ambient_authority())?;
-    // get pwd
-    let path = std::env::current_dir()?;
-    info!("Currnet dir: {:?}", path);
-    Ok(ctx.inherit_args()?.preopened_dir(dir, ".")?.build())
+    ctx = match &wasi.args {
+        Some(args) => ctx.args(args)?,
+        None => ctx.inherit_args()?,
+    };
+    ctx = ctx.envs(&wasi.env)?;
+
+    let preopens = match wasi.preopens {
+        Some(preopens) => preopens,
+        None => {
+            let dir = Dir::open_ambient_dir(".", ambient_authority())?;
+            // get pwd
+            let path = std::env::current_dir()?;
+            info!("Currnet dir: {:?}", path);
+            return Ok(ctx.preopened_dir(dir, ".")?.build());
+        }
+    };
+
+    let mut ctx = ctx.build();
+    for preopen in preopens {
+        let dir = Dir::open_ambient_dir(&preopen.host_path, ambient_authority())
+            .with_context(|| format!("could not preopen {}", preopen.host_path.display()))?;
+        let mut dir: Box<dyn WasiDir> = Box::new(wasi_cap_std_sync::dir::Dir::from_cap_std(dir));
+        if preopen.read_only {
+            dir = Box::new(ReadOnlyDir(dir));
+        }
+        info!(
+            "Preopening {:?} as {} ({})",
+            preopen.host_path,
+            preopen.guest_path,
+            if preopen.read_only {
+                "read-only"
+            } else {
+                "read-write"
+            }
+        );
+        ctx.push_preopened_dir(dir, &preopen.guest_path)?;
+    }
+    Ok(ctx)
 }
 
 /// add_io_redirects_to_wasi_context inherits existing stdio and overrides stdio as available.
@@ -183,19 +375,19 @@ fn add_io_redirects_to_wasi_context(
 ) -> Result<WasiCtxBuilder> {
     ctx = ctx.inherit_stdio();
     if let Some(stdout_path) = io_redirects.stdout_path {
-        if let Some(stdout_file) = maybe_open_stdio(&stdout_path) {
+        if let Some(stdout_file) = maybe_open_stdio(&stdout_path, true) {
             ctx = ctx.stdout(Box::new(WritePipe::new(stdout_file)));
         }
     }
 
     if let Some(stderr_path) = io_redirects.stderr_path {
-        if let Some(stderr_file) = maybe_open_stdio(&stderr_path) {
+        if let Some(stderr_file) = maybe_open_stdio(&stderr_path, true) {
             ctx = ctx.stderr(Box::new(WritePipe::new(stderr_file)));
         }
     }
 
     if let Some(stdin_path) = io_redirects.stdin_path {
-        if let Some(stdin_file) = maybe_open_stdio(&stdin_path) {
+        if let Some(stdin_file) = maybe_open_stdio(&stdin_path, false) {
             ctx = ctx.stdin(Box::new(ReadPipe::new(stdin_file)));
         }
     }
@@ -237,7 +429,7 @@ mod unittest {
     //         .link_capability::<Keyvalue>()?
     //         .add_to_builder("keyvalue".to_string(), keyvalue);
     //
-    //     let (_, _) = builder.build().await;
+    //     let (_, _) = builder.build().await?;
     //     Ok(())
     // }
 
@@ -247,8 +439,8 @@ mod unittest {
         let existing_file_path = tmp_dir.path().join("testpath");
         let empty_file_path = PathBuf::new();
         let _ = File::create(&existing_file_path)?;
-        assert!(crate::maybe_open_stdio(&existing_file_path).is_some());
-        assert!(crate::maybe_open_stdio(&empty_file_path).is_none());
+        assert!(crate::maybe_open_stdio(&existing_file_path, false).is_some());
+        assert!(crate::maybe_open_stdio(&empty_file_path, false).is_none());
         Ok(())
     }
 
@@ -256,6 +448,29 @@ mod unittest {
     #[should_panic]
     fn test_maybe_open_stdio_with_missing_file() {
         let missing_file_path = PathBuf::from("missing");
-        let _ = crate::maybe_open_stdio(&missing_file_path);
+        let _ = crate::maybe_open_stdio(&missing_file_path, false);
+    }
+
+    #[tokio::test]
+    async fn test_build_with_unlinked_imports_fails() -> anyhow::Result<()> {
+        use slight_common::WasmtimeBuildable;
+
+        // nothing is linked, so instantiating the module's imports fails
+        let builder = crate::Builder::from_module("./test/keyvalue-test.wasm")?;
+        assert!(builder.build().await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pooling_allocator_config() -> anyhow::Result<()> {
+        let pool_config = crate::InstancePoolConfig {
+            size: 4,
+            max_instances: Some(16),
+            pooling_allocator: true,
+        };
+        let mut config = crate::default_config()?;
+        pool_config.apply(&mut config, &crate::Limits::default())?;
+        assert!(wasmtime::Engine::new(&config).is_ok());
+        Ok(())
     }
 }
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use slight_common::LimitExceeded;
+use wasmtime::{Config, Engine, ResourceLimiter, Store};
+
+use crate::Ctx;
+
+/// How often the epoch of an engine with a deadline is incremented.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Resource limits applied to each guest instance.
+///
+/// `fuel` and `timeout` are applied per invocation. For the http server,
+/// every request gets a fresh instance with its own fuel and deadline.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// The maximum size of a linear memory, in bytes.
+    pub max_memory_bytes: Option<usize>,
+    /// The maximum number of elements in a table.
+    pub max_table_elements: Option<u32>,
+    /// The amount of fuel available to an invocation.
+    pub fuel: Option<u64>,
+    /// The wall-clock deadline of an invocation.
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    /// Enable fuel and epoch interruption in the engine configuration.
+    pub(crate) fn apply(&self, config: &mut Config) {
+        if self.fuel.is_some() {
+            config.consume_fuel(true);
+        }
+        if self.timeout.is_some() {
+            config.epoch_interruption(true);
+        }
+    }
+
+    /// A key for the limits that are part of the engine configuration.
+    pub(crate) fn engine_key(&self) -> String {
+        format!(
+            "fuel={},epoch={}",
+            self.fuel.is_some(),
+            self.timeout.is_some()
+        )
+    }
+
+    /// Whether the engine needs a background thread incrementing its epoch.
+    pub(crate) fn needs_epoch_ticker(&self) -> bool {
+        self.timeout.is_some()
+    }
+
+    /// The limiter to install in a new `Store`.
+    pub(crate) fn limiter(&self) -> StoreLimiter {
+        StoreLimiter {
+            max_memory_bytes: self.max_memory_bytes,
+            max_table_elements: self.max_table_elements,
+        }
+    }
+
+    /// Refill the fuel and reset the deadline of a store before an invocation.
+    ///
+    /// Stores are armed when they are built. A store that calls the guest
+    /// again later, e.g. its `on-server-shutdown` export, has to be armed
+    /// again first, or the deadline may already have passed.
+    pub fn arm(&self, store: &mut Store<Ctx>) -> Result<()> {
+        if let Some(fuel) = self.fuel {
+            let remaining = store.consume_fuel(0)?;
+            if remaining < fuel {
+                store.add_fuel(fuel - remaining)?;
+            }
+        }
+        if let Some(timeout) = self.timeout {
+            let ticks = timeout.as_millis() / EPOCH_TICK.as_millis();
+            store.set_epoch_deadline(ticks.max(1) as u64);
+        }
+        Ok(())
+    }
+}
+
+/// Start a background thread that increments the epoch of `engine`.
+pub(crate) fn start_epoch_ticker(engine: Engine) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(EPOCH_TICK);
+        engine.increment_epoch();
+    });
+}
+
+/// A `ResourceLimiter` that fails memory and table growth past the limits
+/// with a `LimitExceeded` error.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StoreLimiter {
+    max_memory_bytes: Option<usize>,
+    max_table_elements: Option<u32>,
+}
+
+impl ResourceLimiter for StoreLimiter {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> Result<bool> {
+        match self.max_memory_bytes {
+            Some(max) if desired > max => Err(LimitExceeded::Memory.into()),
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        desired: u32,
+        _maximum: Option<u32>,
+    ) -> Result<bool> {
+        match self.max_table_elements {
+            Some(max) if desired > max => Err(LimitExceeded::TableElements.into()),
+            _ => Ok(true),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Mutex;
+
+use anyhow::Result;
+use wasmtime::{Config, Instance, InstanceAllocationStrategy, PoolingAllocationConfig, Store};
+
+use crate::{Ctx, Limits};
+
+/// The size of a wasm page.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Configuration for keeping pre-instantiated guest instances warm.
+///
+/// `size` is the number of warm instances the pool tries to hold at any
+/// time. `max_instances` and `pooling_allocator` configure wasmtime's
+/// pooling instance allocator, which has to be set up when the `Engine`
+/// is created.
+///
+/// The pooling allocator reserves the address space of every linear memory
+/// up front, so the pages of a memory slot come from `max_memory_bytes` of
+/// the limits, rounded up to whole pages. Without it, wasmtime's default of
+/// 160 pages (10 MiB) is used.
+#[derive(Clone, Debug, Default)]
+pub struct InstancePoolConfig {
+    pub size: usize,
+    pub max_instances: Option<u32>,
+    pub pooling_allocator: bool,
+}
+
+impl InstancePoolConfig {
+    /// Apply the allocator settings to a wasmtime `Config`.
+    pub fn apply(&self, config: &mut Config, limits: &Limits) -> Result<()> {
+        if self.pooling_allocator {
+            let mut pooling = PoolingAllocationConfig::default();
+            if let Some(pages) = memory_pages(limits) {
+                pooling.instance_memory_pages(pages);
+            }
+            if let Some(max_instances) = self.max_instances {
+                pooling.instance_count(max_instances);
+            }
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
+        } else if self.max_instances.is_some() {
+            tracing::warn!("`max_instances` is ignored when the pooling allocator is disabled");
+        }
+        Ok(())
+    }
+
+    /// A key for the allocator settings, which are part of the engine
+    /// configuration. The pool size does not affect the engine.
+    pub(crate) fn engine_key(&self, limits: &Limits) -> String {
+        if self.pooling_allocator {
+            format!(
+                "pooling(max_instances={:?},memory_pages={:?})",
+                self.max_instances,
+                memory_pages(limits)
+            )
+        } else {
+            "on-demand".to_string()
+        }
+    }
+}
+
+/// The number of wasm pages needed to hold `max_memory_bytes`, if set.
+fn memory_pages(limits: &Limits) -> Option<u64> {
+    limits
+        .max_memory_bytes
+        .map(|bytes| (bytes as u64 + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE)
+}
+
+/// A pool of pre-instantiated `Store`s and `Instance`s.
+///
+/// Instances are never handed out twice. Once an instance served a
+/// request it is dropped with its `Store`, and a fresh one is instantiated
+/// in the background to take its place. This keeps guest state from
+/// leaking between requests.
+pub(crate) struct InstancePool {
+    size: usize,
+    warm: Mutex<Vec<(Store<Ctx>, Instance)>>,
+}
+
+impl InstancePool {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            size,
+            warm: Mutex::new(Vec::with_capacity(size)),
+        }
+    }
+
+    /// Take a warm instance out of the pool, if there is one.
+    pub(crate) fn take(&self) -> Option<(Store<Ctx>, Instance)> {
+        self.warm.lock().unwrap().pop()
+    }
+
+    /// Put a freshly instantiated instance into the pool. The instance
+    /// is dropped if the pool is already full.
+    pub(crate) fn put(&self, warm: (Store<Ctx>, Instance)) {
+        let mut pool = self.warm.lock().unwrap();
+        if pool.len() < self.size {
+            pool.push(warm);
+        }
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.warm.lock().unwrap().len() >= self.size
+    }
+}
@@ -3,7 +3,8 @@ pub use crate::RuntimeContext;
 
 use as_any::Downcast;
 
-pub use slight_http_api::{HttpHandlerData, HttpServerExportData};
+pub use slight_http_api::{HttpHandlerData, HttpServerExportData, HttpServerShutdownData};
+pub use slight_messaging_api::MessagingHandlerData;
 pub use wasmtime::Linker;
 
 /// Guest data for http handler
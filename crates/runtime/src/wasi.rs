@@ -0,0 +1,135 @@
+use std::{any::Any, path::PathBuf};
+
+use async_trait::async_trait;
+use wasi_common::{
+    dir::{OpenResult, ReaddirCursor, ReaddirEntity, WasiDir},
+    file::{FdFlags, Filestat, OFlags},
+    Error, ErrorExt, SystemTimeSpec,
+};
+
+/// WASI settings for the running module.
+#[derive(Clone, Debug, Default)]
+pub struct WasiOptions {
+    /// Host directories visible to the guest. If `None`, the current
+    /// directory is preopened as `.`.
+    pub preopens: Option<Vec<Preopen>>,
+    /// Environment variables for the guest.
+    pub env: Vec<(String, String)>,
+    /// Arguments for the guest, including the program name. If `None`,
+    /// the host's arguments are inherited.
+    pub args: Option<Vec<String>>,
+}
+
+/// A host directory made visible to the guest.
+#[derive(Clone, Debug)]
+pub struct Preopen {
+    /// The directory on the host.
+    pub host_path: PathBuf,
+    /// The path the guest sees the directory under.
+    pub guest_path: String,
+    /// Deny any modification of the directory and its contents.
+    pub read_only: bool,
+}
+
+/// A `WasiDir` that only allows reading files and directories.
+///
+/// Opening a file for writing, creating, or truncating it fails with a
+/// permission error, as does any operation that changes the directory.
+/// Subdirectories are wrapped as well.
+pub(crate) struct ReadOnlyDir(pub(crate) Box<dyn WasiDir>);
+
+#[async_trait]
+impl WasiDir for ReadOnlyDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<OpenResult, Error> {
+        if write || oflags.intersects(OFlags::CREATE | OFlags::TRUNCATE) {
+            return Err(Error::perm());
+        }
+        match self
+            .0
+            .open_file(symlink_follow, path, oflags, read, write, fdflags)
+            .await?
+        {
+            OpenResult::Dir(dir) => Ok(OpenResult::Dir(Box::new(ReadOnlyDir(dir)))),
+            file => Ok(file),
+        }
+    }
+
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        self.0.readdir(cursor).await
+    }
+
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        self.0.read_link(path).await
+    }
+
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        self.0.get_filestat().await
+    }
+
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        self.0.get_path_filestat(path, follow_symlinks).await
+    }
+
+    async fn create_dir(&self, _path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+
+    async fn symlink(&self, _old_path: &str, _new_path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+
+    async fn remove_dir(&self, _path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+
+    async fn unlink_file(&self, _path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+
+    async fn rename(
+        &self,
+        _path: &str,
+        _dest_dir: &dyn WasiDir,
+        _dest_path: &str,
+    ) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+
+    async fn hard_link(
+        &self,
+        _path: &str,
+        _target_dir: &dyn WasiDir,
+        _target_path: &str,
+    ) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+
+    async fn set_times(
+        &self,
+        _path: &str,
+        _atime: Option<SystemTimeSpec>,
+        _mtime: Option<SystemTimeSpec>,
+        _follow_symlinks: bool,
+    ) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+}
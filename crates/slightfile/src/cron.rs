@@ -0,0 +1,353 @@
+//! Cron expressions of the slightfile's `[[schedule]]` entries.
+//!
+//! An expression has five fields: minute (0-59), hour (0-23), day of the
+//! month (1-31), month (1-12 or `JAN`-`DEC`) and day of the week (0-7 or
+//! `SUN`-`SAT`, where both 0 and 7 are Sunday). A field is a comma-separated
+//! list of `*`, values and `a-b` ranges, each optionally followed by a
+//! `/step`. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are
+//! shorthands. Times are in UTC.
+//!
+//! As in classic cron, when both the day of the month and the day of the
+//! week are restricted (i.e., don't start with `*`), a day matches if either
+//! one does.
+
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// How far ahead `next_after` looks. The rarest schedule that can match,
+/// e.g. `0 0 29 2 *`, matches at least once every 8 years.
+const MAX_LOOKAHEAD_DAYS: u64 = 10 * 366;
+
+struct Field {
+    name: &'static str,
+    min: u64,
+    max: u64,
+    /// Names of the values, starting at `min`.
+    names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    names: &[],
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    names: &[],
+};
+const DAY: Field = Field {
+    name: "day of the month",
+    min: 1,
+    max: 31,
+    names: &[],
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: &[
+        "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+    ],
+};
+const WEEKDAY: Field = Field {
+    name: "day of the week",
+    min: 0,
+    max: 7,
+    names: &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"],
+};
+
+impl Field {
+    fn value(&self, s: &str) -> Result<u64> {
+        let value = match self.names.iter().position(|n| n.eq_ignore_ascii_case(s)) {
+            Some(i) => i as u64 + self.min,
+            None => s
+                .parse()
+                .with_context(|| format!("invalid value '{s}' in the {} field", self.name))?,
+        };
+        if value < self.min || value > self.max {
+            bail!(
+                "value {value} of the {} field is not between {} and {}",
+                self.name,
+                self.min,
+                self.max
+            );
+        }
+        Ok(value)
+    }
+
+    /// Parse the field into a set of values, where bit `n` is value `n`.
+    fn parse(&self, s: &str) -> Result<u64> {
+        let mut values = 0;
+        for part in s.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step = step
+                        .parse()
+                        .ok()
+                        .filter(|step| *step > 0)
+                        .with_context(|| {
+                            format!("invalid step '{step}' in the {} field", self.name)
+                        })?;
+                    (range, Some(step))
+                }
+                None => (part, None),
+            };
+            let (start, end) = if range == "*" {
+                (self.min, self.max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (self.value(start)?, self.value(end)?)
+            } else {
+                let start = self.value(range)?;
+                // `5/15` is short for `5-59/15`
+                (start, if step.is_some() { self.max } else { start })
+            };
+            if start > end {
+                bail!("invalid range '{range}' in the {} field", self.name);
+            }
+            for value in (start..=end).step_by(step.unwrap_or(1)) {
+                values |= 1 << value;
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A parsed cron expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for Cron {
+    type Err = anyhow::Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let fields = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            fields => fields,
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!(
+                "invalid cron expression '{expression}': expected 5 fields, found {}",
+                fields.len()
+            );
+        };
+        let parse = |field: &Field, s| {
+            field
+                .parse(s)
+                .with_context(|| format!("invalid cron expression '{expression}'"))
+        };
+        let mut weekdays = parse(&WEEKDAY, weekday)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: parse(&MINUTE, minute)?,
+            hours: parse(&HOUR, hour)?,
+            days: parse(&DAY, day)?,
+            months: parse(&MONTH, month)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+}
+
+impl Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+fn has(values: u64, value: u64) -> bool {
+    values & (1 << value) != 0
+}
+
+impl Cron {
+    /// The first time after `time` that matches the expression, or `None` if
+    /// there is none in the next 10 years (e.g., `0 0 30 2 *`).
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        // the first whole minute after `time`
+        let mut minute = time.duration_since(UNIX_EPOCH).ok()?.as_secs() / 60 + 1;
+        let end = minute + MAX_LOOKAHEAD_DAYS * MINUTES_PER_DAY;
+        while minute < end {
+            let days = minute / MINUTES_PER_DAY;
+            let (year, month, day) = civil_from_days(days);
+            if !has(self.months, month) {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minute = days_from_civil(year, month, 1) * MINUTES_PER_DAY;
+                continue;
+            }
+            // 1970-01-01 was a Thursday
+            let weekday = (days + 4) % 7;
+            if !self.matches_day(day, weekday) {
+                minute = (days + 1) * MINUTES_PER_DAY;
+                continue;
+            }
+            if !has(self.hours, minute % MINUTES_PER_DAY / 60) {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+            if !has(self.minutes, minute % 60) {
+                minute += 1;
+                continue;
+            }
+            return Some(UNIX_EPOCH + Duration::from_secs(minute * 60));
+        }
+        None
+    }
+
+    fn matches_day(&self, day: u64, weekday: u64) -> bool {
+        let day = has(self.days, day);
+        let weekday = has(self.weekdays, weekday);
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+/// The (year, month, day) of the day `days` after 1970-01-01.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The number of days from 1970-01-01 to (year, month, day).
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A time in UTC.
+    fn at(year: u64, month: u64, day: u64, hour: u64, minute: u64) -> SystemTime {
+        UNIX_EPOCH
+            + Duration::from_secs(
+                days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60,
+            )
+    }
+
+    fn next(expression: &str, time: SystemTime) -> Option<SystemTime> {
+        expression.parse::<Cron>().unwrap().next_after(time)
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        for days in [0, 59, 11_016, 11_017, 19_782, 20_000, 40_000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        for expression in [
+            "* * * * *",
+            "*/5 * * * *",
+            "0 9 * * MON-FRI",
+            "0,30 8-18/2 1,15 jan,jul 0-7",
+            "5/15 * * * *",
+            "@daily",
+        ] {
+            assert!(expression.parse::<Cron>().is_ok(), "{expression}");
+        }
+        for expression in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "a * * * *",
+            "1,,2 * * * *",
+            "@often",
+        ] {
+            assert!(expression.parse::<Cron>().is_err(), "{expression}");
+        }
+    }
+
+    #[test]
+    fn test_next_after() {
+        // 2024-01-01 was a Monday
+        let monday = at(2024, 1, 1, 10, 7);
+        assert_eq!(next("* * * * *", monday), Some(at(2024, 1, 1, 10, 8)));
+        assert_eq!(next("*/15 * * * *", monday), Some(at(2024, 1, 1, 10, 15)));
+        assert_eq!(next("0 * * * *", monday), Some(at(2024, 1, 1, 11, 0)));
+        assert_eq!(next("0 9 * * *", monday), Some(at(2024, 1, 2, 9, 0)));
+        assert_eq!(next("30 8 * * SAT", monday), Some(at(2024, 1, 6, 8, 30)));
+        assert_eq!(next("0 0 * * 7", monday), Some(at(2024, 1, 7, 0, 0)));
+        assert_eq!(next("@monthly", monday), Some(at(2024, 2, 1, 0, 0)));
+        assert_eq!(next("0 0 1 1 *", monday), Some(at(2025, 1, 1, 0, 0)));
+        assert_eq!(next("0 0 29 2 *", monday), Some(at(2024, 2, 29, 0, 0)));
+        assert_eq!(
+            next("0 0 29 2 *", at(2024, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 31 * *", at(2024, 4, 1, 0, 0)),
+            Some(at(2024, 5, 31, 0, 0))
+        );
+        // the day of the month or the day of the week
+        assert_eq!(next("0 0 15 * FRI", monday), Some(at(2024, 1, 5, 0, 0)));
+        // the day of the month and the day of the week
+        assert_eq!(next("0 0 */10 * FRI", monday), Some(at(2024, 3, 1, 0, 0)));
+        // a time on the minute is not after itself
+        let on_time = at(2024, 1, 1, 9, 0);
+        assert_eq!(next("0 9 * * *", on_time), Some(at(2024, 1, 2, 9, 0)));
+        assert_eq!(next("0 0 30 2 *", monday), None);
+    }
+}
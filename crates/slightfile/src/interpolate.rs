@@ -0,0 +1,161 @@
+//! Interpolating environment variables into slightfile values.
+//!
+//! `${VAR}` is replaced by the value of the environment variable `VAR`, and
+//! `${VAR:-default}` by `default` if `VAR` is unset or empty. `$${` is a
+//! literal `${`. References with a dot, e.g. `${azapp.KEY}`, name a configs
+//! store and are left for the runtime to resolve.
+
+use anyhow::{bail, Result};
+
+/// Words that make a config or variable name look like it holds a secret.
+const SECRET_WORDS: &[&str] = &[
+    "auth",
+    "connection_string",
+    "credential",
+    "key",
+    "passwd",
+    "password",
+    "secret",
+    "token",
+];
+
+/// Whether the value of `name` should be masked in logs.
+pub fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_WORDS.iter().any(|word| name.contains(word))
+}
+
+/// A string with the environment variables that were interpolated into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interpolated {
+    pub value: String,
+    pub variables: Vec<String>,
+}
+
+impl Interpolated {
+    /// The value to log, masked if any of its variables, or `name`, looks
+    /// like a secret.
+    pub fn display(&self, name: &str) -> String {
+        if is_secret(name) || self.variables.iter().any(|v| is_secret(v)) {
+            "****".to_string()
+        } else {
+            self.value.clone()
+        }
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace the references to environment variables in `value`, looking
+/// them up with `lookup`.
+pub fn interpolate(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<Interpolated> {
+    let mut out = String::with_capacity(value.len());
+    let mut variables = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("$${") {
+            out.push_str("${");
+            rest = &rest[3..];
+            continue;
+        }
+        if !rest.starts_with("${") {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+        let Some(end) = rest.find('}') else {
+            bail!("unterminated variable reference");
+        };
+        let reference = &rest[2..end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+        if name.contains('.') {
+            // resolved by the runtime configs
+            out.push_str(&rest[..=end]);
+        } else if !is_variable_name(name) {
+            bail!("invalid variable reference '${{{reference}}}'");
+        } else {
+            match (lookup(name).filter(|v| !v.is_empty() || default.is_none()), default) {
+                (Some(v), _) => out.push_str(&v),
+                (None, Some(default)) => out.push_str(default),
+                (None, None) => bail!(
+                    "environment variable '{name}' is not set, and has no default (use '${{{name}:-default}}' to give it one)"
+                ),
+            }
+            variables.push(name.to_string());
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Interpolated {
+        value: out,
+        variables,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOST" => Some("db.internal".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate() -> Result<()> {
+        for (value, expected) in [
+            ("localhost", "localhost"),
+            ("${HOST}", "db.internal"),
+            ("redis://${HOST}:${PORT:-6379}", "redis://db.internal:6379"),
+            ("${EMPTY:-fallback}", "fallback"),
+            ("${EMPTY}", ""),
+            ("${PORT:-}", ""),
+            ("${azapp.KEY}", "${azapp.KEY}"),
+            ("$${HOST}", "${HOST}"),
+            ("cost: $5", "cost: $5"),
+        ] {
+            assert_eq!(interpolate(value, lookup)?.value, expected, "{value}");
+        }
+        assert_eq!(
+            interpolate("${HOST}:${PORT:-1}", lookup)?.variables,
+            vec!["HOST", "PORT"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        for (value, error) in [
+            (
+                "${PORT}",
+                "environment variable 'PORT' is not set, and has no default (use '${PORT:-default}' to give it one)",
+            ),
+            ("${HOST", "unterminated variable reference"),
+            ("${1HOST}", "invalid variable reference '${1HOST}'"),
+        ] {
+            assert_eq!(interpolate(value, lookup).unwrap_err().to_string(), error);
+        }
+    }
+
+    #[test]
+    fn test_display() -> Result<()> {
+        let host = interpolate("${HOST}", lookup)?;
+        assert_eq!(host.display("REDIS_HOST"), "db.internal");
+        assert_eq!(host.display("REDIS_PASSWORD"), "****");
+        let token = interpolate("${API_TOKEN:-abc}", lookup)?;
+        assert_eq!(token.display("HEADER"), "****");
+        Ok(())
+    }
+}
@@ -1,12 +1,22 @@
 use anyhow::Result;
-use std::{collections::HashMap, fmt::Display, path::Path};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Deserializer, Serialize};
 
 pub mod capability_store;
+pub mod cron;
+pub mod interpolate;
+mod locate;
+pub mod policy;
 pub mod resource;
+pub mod schema;
 pub mod secret_store;
 pub mod slightfile;
+pub use policy::Policy;
 pub use resource::Resource;
 pub use secret_store::SecretStoreResource;
 pub use slightfile::SlightFileInner;
@@ -27,7 +37,242 @@ pub struct SlightFile {
     pub specversion: SpecVersion,
     pub secret_store: Option<SecretStoreResource>,
     pub secret_settings: Option<Vec<Config>>,
+    /// The key file user secrets are encrypted with. Relative paths are
+    /// resolved against the slightfile's directory. Defaults to
+    /// `.slightkey` next to the slightfile.
+    pub secret_key_file: Option<PathBuf>,
     pub capability: Option<Vec<Capability>>,
+    pub instance_pool: Option<InstancePool>,
+    pub module_cache: Option<ModuleCache>,
+    pub limits: Option<Limits>,
+    pub wasi: Option<Wasi>,
+    pub shutdown: Option<Shutdown>,
+    pub telemetry: Option<Telemetry>,
+    pub metrics: Option<Metrics>,
+    pub messaging_trigger: Option<MessagingTrigger>,
+    pub schedule: Option<Vec<Schedule>>,
+    pub profile: Option<HashMap<String, Profile>>,
+}
+
+/// Settings that apply on top of the rest of the slightfile when the
+/// profile is selected, e.g. with `slight run --profile staging`.
+///
+/// A capability of the profile replaces the one of the same capability and
+/// name, whatever its resource, and the others are added. Every other
+/// section of the profile replaces the one of the slightfile.
+///
+/// ```toml
+/// [[capability]]
+/// resource = "keyvalue.filesystem"
+/// name = "cache"
+///
+/// [[profile.staging.capability]]
+/// resource = "keyvalue.redis"
+/// name = "cache"
+///     [profile.staging.capability.configs]
+///     REDIS_ADDRESS = "redis://${REDIS_HOST}:6379"
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct Profile {
+    pub capability: Option<Vec<Capability>>,
+    pub instance_pool: Option<InstancePool>,
+    pub module_cache: Option<ModuleCache>,
+    pub limits: Option<Limits>,
+    pub wasi: Option<Wasi>,
+    pub shutdown: Option<Shutdown>,
+    pub telemetry: Option<Telemetry>,
+    pub metrics: Option<Metrics>,
+    pub messaging_trigger: Option<MessagingTrigger>,
+    pub schedule: Option<Vec<Schedule>>,
+}
+
+/// Settings for keeping pre-instantiated guest instances warm.
+///
+/// This is used by the http server to avoid instantiating a fresh
+/// module on every request.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct InstancePool {
+    /// The number of warm instances to keep ready.
+    pub size: usize,
+    /// The maximum number of instances that can be alive at the same time.
+    /// Only applies when `pooling_allocator` is enabled.
+    pub max_instances: Option<u32>,
+    /// Use wasmtime's pooling instance allocator. Each memory slot holds
+    /// `limits.max_memory_bytes`, or 10 MiB if it is not set.
+    #[serde(default)]
+    pub pooling_allocator: bool,
+}
+
+/// Settings for caching compiled wasm modules on disk.
+///
+/// When this section is present, compiled modules are serialized to
+/// `directory` and reused by later runs of the same module.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct ModuleCache {
+    /// The cache directory. Relative paths are resolved against the
+    /// slightfile's directory. Defaults to the user's cache directory.
+    pub directory: Option<PathBuf>,
+}
+
+/// Resource limits for guest execution.
+///
+/// `fuel` and `timeout_ms` apply to each invocation. For the http server,
+/// that is each request.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of a linear memory, in bytes.
+    pub max_memory_bytes: Option<u64>,
+    /// The maximum number of elements in a table.
+    pub max_table_elements: Option<u32>,
+    /// The amount of fuel available to an invocation.
+    pub fuel: Option<u64>,
+    /// The wall-clock deadline of an invocation, in milliseconds.
+    pub timeout_ms: Option<u64>,
+}
+
+/// Settings for shutting down on SIGTERM or SIGINT.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Shutdown {
+    /// How long in-flight http requests may run after the signal, in
+    /// milliseconds. Defaults to 30 seconds.
+    pub drain_timeout_ms: Option<u64>,
+}
+
+/// Settings for exporting OpenTelemetry spans of http requests and
+/// capability calls.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Telemetry {
+    /// Where spans are exported to.
+    pub exporter: TelemetryExporter,
+    /// The collector endpoint for the `otlp` exporter. Defaults to
+    /// `http://localhost:4317`.
+    pub endpoint: Option<String>,
+    /// The file spans are appended to for the `file` exporter. Relative
+    /// paths are resolved against the slightfile's directory.
+    pub path: Option<PathBuf>,
+    /// The `service.name` spans are reported under. Defaults to `slight`.
+    pub service_name: Option<String>,
+}
+
+/// An exporter for OpenTelemetry spans.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TelemetryExporter {
+    #[serde(rename = "otlp")]
+    Otlp,
+    #[serde(rename = "file")]
+    File,
+    #[serde(rename = "stdout")]
+    Stdout,
+}
+
+/// Settings for serving Prometheus metrics of http requests, capability
+/// calls and instance builds.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Metrics {
+    /// The address metrics are served on, e.g. `0.0.0.0:9090`.
+    pub address: String,
+}
+
+/// Settings for invoking the guest's `on-message` export for each message
+/// received on a set of subscriptions.
+///
+/// Each message is handled by a fresh instance of the guest. When the
+/// slightfile has a messaging trigger, `slight run` does not call the
+/// module's `_start`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MessagingTrigger {
+    /// The topics to subscribe to.
+    pub subscriptions: Vec<Subscription>,
+    /// The maximum number of messages handled at the same time. Defaults
+    /// to 1, which handles messages in the order they are received.
+    pub concurrency: Option<usize>,
+    /// How many times the handling of a message is retried after it fails.
+    /// Defaults to 0.
+    pub max_retries: Option<u32>,
+    /// The delay before the first retry, in milliseconds. It doubles on
+    /// every following retry. Defaults to 1 second.
+    pub retry_delay_ms: Option<u64>,
+    /// The topic a message is published to once its retries are
+    /// exhausted. If it is not set, the message is dropped.
+    pub dead_letter_topic: Option<String>,
+}
+
+/// A topic the messaging trigger subscribes to.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Subscription {
+    /// The name of the messaging capability to subscribe with.
+    pub capability: String,
+    pub topic: String,
+}
+
+/// An exported guest function that `slight run` calls on a cron schedule.
+///
+/// Each run is handled by a fresh instance of the guest. When the slightfile
+/// has a schedule, `slight run` does not call the module's `_start`.
+///
+/// ```toml
+/// [[schedule]]
+/// cron = "*/5 * * * *"
+/// function = "cleanup"
+/// overlap = "queue"
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Schedule {
+    /// When to call the function, see [`cron::Cron`].
+    pub cron: String,
+    /// The exported function to call. It takes and returns nothing.
+    pub function: String,
+    /// What to do when a run is due while the previous one is still
+    /// running. Defaults to `skip`.
+    #[serde(default)]
+    pub overlap: Overlap,
+}
+
+/// What a schedule does with a run that is due while the previous one is
+/// still running.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum Overlap {
+    /// Don't run it.
+    #[serde(rename = "skip")]
+    #[default]
+    Skip,
+    /// Run it once the previous runs finish.
+    #[serde(rename = "queue")]
+    Queue,
+}
+
+/// WASI settings for the guest.
+///
+/// If `preopens` is not set, the current directory is preopened as `.`.
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Wasi {
+    /// Host directories visible to the guest.
+    pub preopens: Option<Vec<Preopen>>,
+    /// Environment variables for the guest.
+    pub env: Option<HashMap<String, String>>,
+}
+
+/// A host directory made visible to the guest.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Preopen {
+    /// The directory on the host. Relative paths are resolved against the
+    /// slightfile's directory.
+    pub host: PathBuf,
+    /// The path the guest sees the directory under.
+    pub guest: String,
+    /// Whether the guest may modify the directory.
+    #[serde(default)]
+    pub mode: PreopenMode,
+}
+
+/// Access mode of a preopened directory.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum PreopenMode {
+    #[serde(rename = "read-only")]
+    #[default]
+    ReadOnly,
+    #[serde(rename = "read-write")]
+    ReadWrite,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +307,12 @@ impl Capability {
             Capability::V2(c) => c.configs.clone(),
         }
     }
+    pub fn policy(&self) -> Option<Policy> {
+        match self {
+            Capability::V1(_) => None,
+            Capability::V2(c) => c.policy.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +325,7 @@ pub struct CapabilityV2 {
     pub resource: Resource,
     pub name: ResourceName,
     pub configs: Option<HashMap<String, String>>,
+    pub policy: Option<Policy>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -135,12 +387,14 @@ impl Config {
 #[derive(Debug, Clone, Default)]
 pub struct SlightFileBuilder {
     file_content: String,
+    profile: Option<String>,
 }
 
 impl SlightFileBuilder {
     pub fn new() -> Self {
         Self {
             file_content: String::new(),
+            profile: None,
         }
     }
     pub fn path(mut self, path: impl AsRef<Path>) -> Result<Self> {
@@ -148,10 +402,22 @@ impl SlightFileBuilder {
         self.file_content = toml_file_contents;
         Ok(self)
     }
+    /// Apply the `[profile.<name>]` section of the slightfile, if `profile`
+    /// is set.
+    pub fn profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
     pub fn build(self) -> Result<SlightFileInner> {
         let mut slight_file = SlightFileInner::from_toml_string(&self.file_content)?;
+        slight_file.apply_profile(self.profile.as_deref())?;
         slight_file.check_version()?;
+        slight_file.interpolate_env()?;
+        let mut slight_file = slight_file.de_dup()?;
         slight_file.validate_namespace()?;
+        slight_file.validate_policies()?;
+        slight_file.validate_messaging_trigger()?;
+        slight_file.validate_schedule()?;
         Ok(slight_file)
     }
 }
@@ -232,6 +498,270 @@ mod tests {
         assert_eq!(azblob.to_string(), "blobstore.azblob");
     }
 
+    #[test]
+    fn deserialize_instance_pool() -> Result<()> {
+        let path = format!(
+            "{}/tests/good/instance_pool.toml",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        assert_eq!(
+            toml_file.as_ref().instance_pool,
+            Some(InstancePool {
+                size: 8,
+                max_instances: Some(64),
+                pooling_allocator: true,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_wasi() -> Result<()> {
+        let path = format!("{}/tests/good/wasi.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        let wasi = toml_file.as_ref().wasi.clone().unwrap();
+        assert_eq!(
+            wasi.preopens,
+            Some(vec![
+                Preopen {
+                    host: "./static".into(),
+                    guest: "/static".to_string(),
+                    mode: PreopenMode::ReadOnly,
+                },
+                Preopen {
+                    host: "./data".into(),
+                    guest: "/data".to_string(),
+                    mode: PreopenMode::ReadWrite,
+                },
+            ])
+        );
+        assert_eq!(wasi.env.unwrap()["LOG_LEVEL"], "debug");
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_telemetry() -> Result<()> {
+        let path = format!("{}/tests/good/telemetry.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        assert_eq!(
+            toml_file.as_ref().telemetry,
+            Some(Telemetry {
+                exporter: TelemetryExporter::Otlp,
+                endpoint: Some("http://collector:4317".to_string()),
+                path: None,
+                service_name: Some("my-app".to_string()),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_metrics() -> Result<()> {
+        let path = format!("{}/tests/good/metrics.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        assert_eq!(
+            toml_file.as_ref().metrics,
+            Some(Metrics {
+                address: "0.0.0.0:9090".to_string(),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_policy() -> Result<()> {
+        let path = format!("{}/tests/good/policy.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        let capability = toml_file.as_ref().capability.clone().unwrap();
+        assert_eq!(
+            capability[0].policy(),
+            Some(Policy {
+                key_prefixes: Some(vec!["users/".to_string(), "sessions/".to_string()]),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            capability[1].policy().unwrap().topics,
+            Some(vec!["orders.*".to_string()])
+        );
+        assert_eq!(capability[2].policy(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_messaging_trigger() -> Result<()> {
+        let path = format!(
+            "{}/tests/good/messaging_trigger.toml",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        assert_eq!(
+            toml_file.as_ref().messaging_trigger,
+            Some(MessagingTrigger {
+                subscriptions: vec![
+                    Subscription {
+                        capability: "my-broker".to_string(),
+                        topic: "orders".to_string(),
+                    },
+                    Subscription {
+                        capability: "my-broker".to_string(),
+                        topic: "refunds".to_string(),
+                    },
+                ],
+                concurrency: Some(4),
+                max_retries: Some(3),
+                retry_delay_ms: None,
+                dead_letter_topic: Some("orders-failed".to_string()),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_schedule() -> Result<()> {
+        let path = format!("{}/tests/good/schedule.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        assert_eq!(
+            toml_file.as_ref().schedule,
+            Some(vec![
+                Schedule {
+                    cron: "*/5 * * * *".to_string(),
+                    function: "cleanup".to_string(),
+                    overlap: Overlap::Skip,
+                },
+                Schedule {
+                    cron: "0 3 * * MON-FRI".to_string(),
+                    function: "report".to_string(),
+                    overlap: Overlap::Queue,
+                },
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn de_dup() -> Result<()> {
+        let path = format!("{}/tests/good/dup-good-4.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        let capability = toml_file.as_ref().capability.as_ref().unwrap();
+        let names: Vec<(String, String)> = capability
+            .iter()
+            .map(|c| (c.resource().to_string(), c.name().to_string()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("keyvalue.filesystem".to_string(), "my-store".to_string()),
+                ("messaging.mosquitto".to_string(), "*".to_string()),
+            ]
+        );
+
+        let path = format!("{}/tests/bad/dup-4.toml", env!("CARGO_MANIFEST_DIR"));
+        let err = SlightFileBuilder::new().path(path)?.build().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error: capability #2 (resource 'keyvalue.filesystem', name 'my-store') at line 7, column 1 conflicts with capability #1 (resource 'keyvalue.filesystem', name 'my-store') at line 3, column 1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn interpolate_env() -> Result<()> {
+        let path = format!("{}/tests/good/interpolate.toml", env!("CARGO_MANIFEST_DIR"));
+        let toml_file = SlightFileBuilder::new().path(path)?.build()?;
+        let capability = &toml_file.as_ref().capability.as_ref().unwrap()[0];
+        assert_eq!(capability.name().to_string(), "cache");
+        let configs = capability.configs().unwrap();
+        assert_eq!(configs["REDIS_ADDRESS"], "redis://localhost:6379");
+        assert_eq!(configs["REDIS_PASSWORD"], "${azapp.REDIS_PASSWORD}");
+
+        let path = format!("{}/tests/bad/interpolate.toml", env!("CARGO_MANIFEST_DIR"));
+        let err = SlightFileBuilder::new().path(path)?.build().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error: config 'REDIS_ADDRESS' of capability #1 (resource 'keyvalue.redis', name 'cache') at line 7, column 5: environment variable 'SLIGHT_TEST_UNSET_REDIS_HOST' is not set, and has no default (use '${SLIGHT_TEST_UNSET_REDIS_HOST:-default}' to give it one)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn apply_profile() -> Result<()> {
+        let path = format!("{}/tests/good/profile.toml", env!("CARGO_MANIFEST_DIR"));
+        let names = |toml_file: &SlightFileInner| -> Vec<(String, String)> {
+            let capability = toml_file.as_ref().capability.as_ref().unwrap();
+            capability
+                .iter()
+                .map(|c| (c.resource().to_string(), c.name().to_string()))
+                .collect()
+        };
+
+        let toml_file = SlightFileBuilder::new().path(&path)?.build()?;
+        assert_eq!(
+            names(&toml_file),
+            vec![
+                ("keyvalue.filesystem".to_string(), "cache".to_string()),
+                ("messaging.filesystem".to_string(), "orders".to_string()),
+            ]
+        );
+        assert_eq!(
+            toml_file.as_ref().limits.as_ref().unwrap().timeout_ms,
+            Some(1000)
+        );
+        assert!(toml_file.as_ref().profile.is_none());
+
+        let toml_file = SlightFileBuilder::new()
+            .path(&path)?
+            .profile(Some("staging".to_string()))
+            .build()?;
+        assert_eq!(
+            names(&toml_file),
+            vec![
+                ("keyvalue.redis".to_string(), "cache".to_string()),
+                ("messaging.filesystem".to_string(), "orders".to_string()),
+                ("sql.postgres".to_string(), "db".to_string()),
+            ]
+        );
+        let configs = toml_file.as_ref().capability.as_ref().unwrap()[0]
+            .configs()
+            .unwrap();
+        assert_eq!(configs["REDIS_ADDRESS"], "redis://redis:6379");
+        assert_eq!(
+            toml_file.as_ref().limits.as_ref().unwrap().timeout_ms,
+            Some(5000)
+        );
+
+        let err = SlightFileBuilder::new()
+            .path(&path)?
+            .profile(Some("prod".to_string()))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error: the profile 'prod' is not defined, the slightfile has profiles 'staging'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_positions() -> Result<()> {
+        for (file, error) in [
+            (
+                "unknown_resource.toml",
+                "Error: unknown resource 'keyvalue.redsi' at line 8, column 1, expected one of 'keyvalue.awsdynamodb', 'keyvalue.azblob', 'keyvalue.filesystem', 'keyvalue.memory', 'keyvalue.redis', 'keyvalue.dapr'",
+            ),
+            (
+                "schedule.toml",
+                "Error: invalid schedule of function 'cleanup' at line 4, column 1",
+            ),
+        ] {
+            let path = format!("{}/tests/bad/{file}", env!("CARGO_MANIFEST_DIR"));
+            let err = SlightFileBuilder::new().path(path)?.build().unwrap_err();
+            assert_eq!(err.to_string(), error, "{file}");
+        }
+        Ok(())
+    }
+
     #[test]
     fn deserialize_wildcard() -> Result<()> {
         let path = format!("{}/tests/good/msg.toml", env!("CARGO_MANIFEST_DIR"));
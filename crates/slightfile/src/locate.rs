@@ -0,0 +1,205 @@
+//! Finding where tables and keys are in the text of a slightfile, to report
+//! the line and column of validation errors.
+//!
+//! This is not a TOML parser: it reads `[table]` and `[[array]]` headers
+//! and `key = value` lines, which is how slightfiles are written. Tables in
+//! an array, like `[capability.configs]` after `[[capability]]`, belong to
+//! the array's last entry. Keys of inline tables are not found, and their
+//! table's position is used instead.
+
+use std::{collections::HashMap, fmt::Display};
+
+/// A position in the text of a slightfile, starting at line 1, column 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// The positions of the tables and keys of a slightfile.
+#[derive(Debug, Default)]
+pub struct Locator {
+    /// By table, entry of the table if it is an array, and dotted path of
+    /// the key within the entry. The table itself has an empty path.
+    positions: HashMap<(String, usize, String), Position>,
+}
+
+/// `a . "b"` is `a.b`.
+fn normalize(path: &str) -> String {
+    path.split('.')
+        .map(|segment| segment.trim().trim_matches(|c| c == '"' || c == '\''))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+impl Locator {
+    pub fn new(toml: &str) -> Self {
+        let mut positions = HashMap::new();
+        let mut entries: HashMap<String, usize> = HashMap::new();
+        // the table, the entry, and the path within the entry of the
+        // current header
+        let mut table = String::new();
+        let mut entry = 0;
+        let mut path = String::new();
+        // the array of tables whose last entry is open
+        let mut array: Option<(String, usize)> = None;
+        let mut in_multiline_string = false;
+
+        for (i, line) in toml.lines().enumerate() {
+            let quotes = line.matches("\"\"\"").count() + line.matches("'''").count();
+            if in_multiline_string {
+                in_multiline_string = quotes % 2 == 0;
+                continue;
+            }
+            let trimmed = line.trim_start();
+            let position = Position {
+                line: i + 1,
+                column: line[..line.len() - trimmed.len()].chars().count() + 1,
+            };
+
+            if let Some(header) = trimmed.strip_prefix("[[") {
+                let Some((header, _)) = header.split_once("]]") else {
+                    continue;
+                };
+                let header = normalize(header);
+                let n = entries.entry(header.clone()).or_insert(0);
+                (table, entry, path) = (header.clone(), *n, String::new());
+                *n += 1;
+                array = Some((table.clone(), entry));
+            } else if let Some(header) = trimmed.strip_prefix('[') {
+                let Some((header, _)) = header.split_once(']') else {
+                    continue;
+                };
+                let header = normalize(header);
+                match &array {
+                    Some((name, n)) if header.starts_with(&format!("{name}.")) => {
+                        path = header[name.len() + 1..].to_string();
+                        (table, entry) = (name.clone(), *n);
+                    }
+                    _ => {
+                        (table, entry, path) = (header, 0, String::new());
+                        array = None;
+                    }
+                }
+            } else if let Some((key, _)) = trimmed.split_once('=') {
+                if trimmed.starts_with('#') {
+                    continue;
+                }
+                in_multiline_string = quotes % 2 == 1;
+                positions
+                    .entry((table.clone(), entry, join(&path, &normalize(key))))
+                    .or_insert(position);
+                continue;
+            } else {
+                continue;
+            }
+            positions
+                .entry((table.clone(), entry, path.clone()))
+                .or_insert(position);
+        }
+        Self { positions }
+    }
+
+    /// The position of the key at `path` in the `entry`th entry of `table`,
+    /// where `table` is empty for the top-level keys.
+    ///
+    /// If the key is not found, this is the position of the closest table
+    /// or key that contains it.
+    pub fn find(&self, table: &str, entry: usize, path: &str) -> Option<Position> {
+        let mut path = path;
+        loop {
+            let key = (table.to_string(), entry, path.to_string());
+            if let Some(position) = self.positions.get(&key) {
+                return Some(*position);
+            }
+            if path.is_empty() {
+                return None;
+            }
+            path = path
+                .rsplit_once('.')
+                .map(|(parent, _)| parent)
+                .unwrap_or("");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"specversion = "0.2"
+
+[[capability]]
+resource = "keyvalue.filesystem"
+name = "cache"
+
+[[capability]]
+resource = "keyvalue.redis"
+name = "store"
+    [capability.configs]
+    REDIS_ADDRESS = "redis://localhost:6379"
+
+[messaging_trigger]
+concurrency = 0
+subscriptions = [{ capability = "broker", topic = "orders" }]
+
+[[profile.staging.capability]]
+resource = "sql.postgres"
+  name = """
+db"""
+configs = { POSTGRES_CONNECTION_URL = "postgres://" }
+"#;
+
+    fn at(line: usize, column: usize) -> Option<Position> {
+        Some(Position { line, column })
+    }
+
+    #[test]
+    fn test_find() {
+        let locator = Locator::new(TOML);
+        assert_eq!(locator.find("", 0, "specversion"), at(1, 1));
+        assert_eq!(locator.find("capability", 0, ""), at(3, 1));
+        assert_eq!(locator.find("capability", 1, "name"), at(9, 1));
+        assert_eq!(locator.find("capability", 1, "configs"), at(10, 5));
+        assert_eq!(
+            locator.find("capability", 1, "configs.REDIS_ADDRESS"),
+            at(11, 5)
+        );
+        assert_eq!(locator.find("capability", 1, "policy.topics"), at(7, 1));
+        assert_eq!(locator.find("capability", 2, ""), None);
+        assert_eq!(
+            locator.find("messaging_trigger", 0, "concurrency"),
+            at(14, 1)
+        );
+        assert_eq!(
+            locator.find("messaging_trigger.subscriptions", 0, "topic"),
+            None
+        );
+        assert_eq!(
+            locator.find("profile.staging.capability", 0, "name"),
+            at(19, 3)
+        );
+        assert_eq!(
+            locator.find(
+                "profile.staging.capability",
+                0,
+                "configs.POSTGRES_CONNECTION_URL"
+            ),
+            at(21, 1)
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::Resource;
+
+/// Access rules of a capability, enforced by the host before a call reaches
+/// the capability's implementor.
+///
+/// Each rule applies to one interface, and is an allowlist: when a rule is
+/// not set, the guest has access to the whole backing resource.
+///
+/// ```toml
+/// [[capability]]
+/// resource = "keyvalue.redis"
+/// name = "my-store"
+///
+/// [capability.policy]
+/// key_prefixes = ["users/", "sessions/"]
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// keyvalue: the prefixes of the keys the guest may access.
+    pub key_prefixes: Option<Vec<String>>,
+    /// messaging: the topics the guest may publish and subscribe to. A `*`
+    /// matches any sequence of characters, e.g. `orders.*`.
+    pub topics: Option<Vec<String>>,
+    /// blob: the prefixes of the objects the guest may access.
+    pub object_prefixes: Option<Vec<String>>,
+    /// sql: the tables the guest's statements may use.
+    pub tables: Option<Vec<String>>,
+    /// http-client: the URLs the guest may request, e.g.
+    /// `https://*.example.com/v1/*`. The scheme, host and port of a URL are
+    /// matched separately from its path. In the host, a `*` matches one
+    /// label, and in the path, any sequence of characters. A URL without a
+    /// port in its pattern has to use the scheme's default port. Queries
+    /// are not matched.
+    pub urls: Option<Vec<String>>,
+}
+
+impl Policy {
+    pub fn allows_key(&self, key: &str) -> bool {
+        allows(&self.key_prefixes, |prefix| key.starts_with(prefix))
+    }
+
+    pub fn allows_topic(&self, topic: &str) -> bool {
+        allows(&self.topics, |pattern| glob_match(pattern, topic))
+    }
+
+    pub fn allows_object(&self, object: &str) -> bool {
+        allows(&self.object_prefixes, |prefix| object.starts_with(prefix))
+    }
+
+    /// Table names are compared case-insensitively, and a schema-qualified
+    /// name (e.g., `public.users`) is allowed by its unqualified entry.
+    pub fn allows_table(&self, table: &str) -> bool {
+        let unqualified = table.rsplit('.').next().unwrap_or(table);
+        allows(&self.tables, |allowed| {
+            allowed.eq_ignore_ascii_case(table) || allowed.eq_ignore_ascii_case(unqualified)
+        })
+    }
+
+    pub fn allows_url(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return self.urls.is_none();
+        };
+        allows(
+            &self.urls,
+            |pattern| matches!(UrlPattern::parse(pattern), Some(pattern) if pattern.matches(&url)),
+        )
+    }
+
+    /// Check that every rule that is set applies to `resource`.
+    pub fn validate(&self, resource: &Resource) -> Result<()> {
+        let rules = [
+            ("key_prefixes", self.key_prefixes.is_some(), "keyvalue"),
+            ("topics", self.topics.is_some(), "messaging"),
+            ("object_prefixes", self.object_prefixes.is_some(), "blob"),
+            ("tables", self.tables.is_some(), "sql"),
+            ("urls", self.urls.is_some(), "http-client"),
+        ];
+        for (rule, is_set, cap_name) in rules {
+            if is_set && resource.to_cap_name() != cap_name {
+                bail!("Error: the policy rule '{rule}' does not apply to resource '{resource}'");
+            }
+        }
+        for pattern in self.urls.iter().flatten() {
+            if UrlPattern::parse(pattern).is_none() {
+                bail!(
+                    "Error: the url pattern '{pattern}' is invalid, expected e.g. 'https://*.example.com/v1/*'"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn allows(rule: &Option<Vec<String>>, matches: impl Fn(&str) -> bool) -> bool {
+    match rule {
+        Some(entries) => entries.iter().any(|entry| matches(entry)),
+        None => true,
+    }
+}
+
+/// A pattern of the `urls` rule.
+#[derive(Debug, PartialEq, Eq)]
+struct UrlPattern<'a> {
+    scheme: &'a str,
+    host: &'a str,
+    port: Option<u16>,
+    path: &'a str,
+}
+
+impl<'a> UrlPattern<'a> {
+    fn parse(pattern: &'a str) -> Option<Self> {
+        let (scheme, rest) = pattern.split_once("://")?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            // not the colons of an IPv6 address
+            Some((host, port)) if !port.ends_with(']') => (host, Some(port.parse().ok()?)),
+            _ => (authority, None),
+        };
+        if scheme.is_empty() || host.is_empty() || host.contains('@') {
+            return None;
+        }
+        Some(Self {
+            scheme,
+            host,
+            port,
+            path,
+        })
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let port = match self.port {
+            Some(port) => url.port_or_known_default() == Some(port),
+            None => url.port().is_none(),
+        };
+        let host_labels: Vec<&str> = host.split('.').collect();
+        let pattern_labels: Vec<&str> = self.host.split('.').collect();
+        self.scheme.eq_ignore_ascii_case(url.scheme())
+            && port
+            && host_labels.len() == pattern_labels.len()
+            && pattern_labels
+                .iter()
+                .zip(&host_labels)
+                .all(|(pattern, label)| *pattern == "*" || pattern.eq_ignore_ascii_case(label))
+            && glob_match(self.path, url.path())
+    }
+}
+
+/// Match `s` against `pattern`, where `*` matches any sequence of characters.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part.
+    let first = parts.next().unwrap();
+    let Some(mut rest) = s.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // no `*` in the pattern
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::{HttpClientResource, KeyvalueResource, MessagingResource};
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("orders", "orders"));
+        assert!(!glob_match("orders", "orders.new"));
+        assert!(glob_match("orders.*", "orders.new"));
+        assert!(glob_match("orders.*", "orders."));
+        assert!(!glob_match("orders.*", "orders"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.eu.*", "orders.eu.new"));
+        assert!(!glob_match("*.eu.*", "orders.us.new"));
+        assert!(glob_match(
+            "https://api.example.com/*",
+            "https://api.example.com/v1/users"
+        ));
+        assert!(!glob_match(
+            "https://api.example.com/*",
+            "https://api.example.com.evil.com/"
+        ));
+        assert!(!glob_match("a*a", "a"));
+    }
+
+    #[test]
+    fn test_allows_url() {
+        let policy = Policy {
+            urls: Some(vec![
+                "https://*.example.com/*".to_string(),
+                "http://localhost:8080/v1/*".to_string(),
+            ]),
+            ..Default::default()
+        };
+        for url in [
+            "https://api.example.com/",
+            "https://API.example.com:443/v1/users?id=1",
+            "http://localhost:8080/v1/users",
+        ] {
+            assert!(policy.allows_url(url), "{url}");
+        }
+        for url in [
+            "https://evil.com/x.example.com/y",
+            "https://example.com/",
+            "https://a.b.example.com/",
+            "https://api.example.com.evil.com/",
+            "https://api.example.com@evil.com/",
+            "https://api.example.com:8443/",
+            "http://api.example.com/",
+            "http://localhost:8080/v1/../admin",
+            "http://localhost/v1/users",
+            "not a url",
+        ] {
+            assert!(!policy.allows_url(url), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy = Policy {
+            key_prefixes: Some(vec!["users/".to_string()]),
+            tables: Some(vec!["Users".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy.allows_key("users/alice"));
+        assert!(!policy.allows_key("admins/bob"));
+        assert!(policy.allows_table("users"));
+        assert!(policy.allows_table("public.USERS"));
+        assert!(!policy.allows_table("orders"));
+        // rules that are not set allow everything
+        assert!(policy.allows_topic("orders"));
+        assert!(policy.allows_object("hello.txt"));
+        assert!(policy.allows_url("https://example.com"));
+
+        // an empty rule allows nothing
+        let policy = Policy {
+            topics: Some(vec![]),
+            ..Default::default()
+        };
+        assert!(!policy.allows_topic("orders"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let policy = Policy {
+            key_prefixes: Some(vec!["users/".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy
+            .validate(&Resource::Keyvalue(KeyvalueResource::Redis))
+            .is_ok());
+        assert!(policy
+            .validate(&Resource::Messaging(MessagingResource::Nats))
+            .is_err());
+
+        let policy = Policy {
+            urls: Some(vec!["api.example.com/*".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy
+            .validate(&Resource::HttpClient(HttpClientResource::Client))
+            .is_err());
+    }
+}
@@ -8,6 +8,8 @@ pub enum BlobResource {
     AwsS3,
     #[serde(rename = "blobstore.azblob")]
     Azblob,
+    #[serde(rename = "blobstore.memory")]
+    Memory,
 }
 
 impl Display for BlobResource {
@@ -15,6 +17,7 @@ impl Display for BlobResource {
         match self {
             BlobResource::AwsS3 => write!(f, "blobstore.aws_s3"),
             BlobResource::Azblob => write!(f, "blobstore.azblob"),
+            BlobResource::Memory => write!(f, "blobstore.memory"),
         }
     }
 }
@@ -27,6 +30,8 @@ pub enum KeyvalueResource {
     Azblob,
     #[serde(rename = "keyvalue.filesystem")]
     Filesystem,
+    #[serde(rename = "keyvalue.memory")]
+    Memory,
     #[serde(rename = "keyvalue.redis")]
     Redis,
     #[serde(rename = "kv.awsdynamodb")]
@@ -47,6 +52,7 @@ impl Display for KeyvalueResource {
             KeyvalueResource::AwsDynamoDb => write!(f, "keyvalue.awsdynamodb"),
             KeyvalueResource::Azblob => write!(f, "keyvalue.azblob"),
             KeyvalueResource::Filesystem => write!(f, "keyvalue.filesystem"),
+            KeyvalueResource::Memory => write!(f, "keyvalue.memory"),
             KeyvalueResource::Redis => write!(f, "keyvalue.redis"),
             KeyvalueResource::V1AwsDynamoDb => write!(f, "kv.awsdynamodb"),
             KeyvalueResource::V1Azblob => write!(f, "kv.azblob"),
@@ -65,6 +71,8 @@ pub enum MessagingResource {
     ConfluentApacheKafka,
     #[serde(rename = "messaging.filesystem")]
     Filesystem,
+    #[serde(rename = "messaging.memory")]
+    Memory,
     #[serde(rename = "messaging.mosquitto")]
     Mosquitto,
     #[serde(rename = "messaging.nats")]
@@ -83,6 +91,7 @@ impl Display for MessagingResource {
                 write!(f, "messaging.confluent_apache_kafka")
             }
             MessagingResource::Filesystem => write!(f, "messaging.filesystem"),
+            MessagingResource::Memory => write!(f, "messaging.memory"),
             MessagingResource::Mosquitto => write!(f, "messaging.mosquitto"),
             MessagingResource::Nats => write!(f, "messaging.nats"),
             MessagingResource::V1Azsbus => write!(f, "mq.azsbus"),
@@ -126,6 +135,8 @@ pub enum ConfigsResource {
     Azapp,
     #[serde(rename = "configs.envvars")]
     Envvars,
+    #[serde(rename = "configs.memory")]
+    Memory,
     #[serde(rename = "configs.usersecrets")]
     Usersecrets,
 }
@@ -135,6 +146,7 @@ impl Display for ConfigsResource {
         match self {
             ConfigsResource::Azapp => write!(f, "configs.azapp"),
             ConfigsResource::Envvars => write!(f, "configs.envvars"),
+            ConfigsResource::Memory => write!(f, "configs.memory"),
             ConfigsResource::Usersecrets => write!(f, "configs.usersecrets"),
         }
     }
@@ -144,6 +156,8 @@ impl Display for ConfigsResource {
 pub enum DistributedLockingResource {
     #[serde(rename = "distributed_locking.etcd")]
     Etcd,
+    #[serde(rename = "distributed_locking.memory")]
+    Memory,
     #[serde(rename = "lockd.etcd")]
     V1Etcd,
 }
@@ -155,6 +169,7 @@ impl Display for DistributedLockingResource {
                 write!(f, "distributed_locking.etcd")
             }
             DistributedLockingResource::V1Etcd => write!(f, "lockd.etcd"),
+            DistributedLockingResource::Memory => write!(f, "distributed_locking.memory"),
         }
     }
 }
@@ -163,12 +178,15 @@ impl Display for DistributedLockingResource {
 pub enum SqlResource {
     #[serde(rename = "sql.postgres")]
     Postgres,
+    #[serde(rename = "sql.memory")]
+    Memory,
 }
 
 impl Display for SqlResource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             SqlResource::Postgres => write!(f, "sql.postgres"),
+            SqlResource::Memory => write!(f, "sql.memory"),
         }
     }
 }
@@ -208,8 +226,174 @@ impl Resource {
             Resource::Sql(_) => "sql".into(),
         }
     }
+
+    /// The WIT interface this resource implements, as it appears in the
+    /// import section of a wasm module.
+    pub fn interface_name(&self) -> &'static str {
+        match self {
+            Resource::Blob(_) => "blob-store",
+            Resource::Keyvalue(_) => "keyvalue",
+            Resource::Messaging(_) => "messaging",
+            Resource::HttpServer(_) => "http-server",
+            Resource::HttpClient(_) => "http-client",
+            Resource::Configs(_) => "configs",
+            Resource::DistributedLocking(_) => "distributed-locking",
+            Resource::Sql(_) => "sql",
+        }
+    }
+
+    /// The configs the resource's implementor reads from its capability.
+    ///
+    /// Each entry lists alternative config names, at least one of which
+    /// has to be set.
+    pub fn required_configs(&self) -> &'static [&'static [&'static str]] {
+        match self {
+            Resource::Blob(BlobResource::AwsS3)
+            | Resource::Keyvalue(KeyvalueResource::AwsDynamoDb)
+            | Resource::Keyvalue(KeyvalueResource::V1AwsDynamoDb) => &[
+                &["AWS_ACCESS_KEY_ID"],
+                &["AWS_SECRET_ACCESS_KEY"],
+                &["AWS_REGION", "AWS_DEFAULT_REGION"],
+            ],
+            Resource::Blob(BlobResource::Azblob)
+            | Resource::Keyvalue(KeyvalueResource::Azblob)
+            | Resource::Keyvalue(KeyvalueResource::V1Azblob) => {
+                &[&["AZURE_STORAGE_ACCOUNT"], &["AZURE_STORAGE_KEY"]]
+            }
+            Resource::Keyvalue(KeyvalueResource::Redis)
+            | Resource::Keyvalue(KeyvalueResource::V1Redis) => &[&["REDIS_ADDRESS"]],
+            Resource::Messaging(MessagingResource::Azsbus)
+            | Resource::Messaging(MessagingResource::V1Azsbus) => &[
+                &["AZURE_SERVICE_BUS_NAMESPACE"],
+                &["AZURE_POLICY_NAME"],
+                &["AZURE_POLICY_KEY"],
+            ],
+            Resource::Messaging(MessagingResource::ConfluentApacheKafka) => &[
+                &["CAK_ENDPOINT"],
+                &["CAK_SECURITY_PROTOCOL"],
+                &["CAK_SASL_MECHANISMS"],
+                &["CAK_SASL_USERNAME"],
+                &["CAK_SASL_PASSWORD"],
+                &["CAK_GROUP_ID"],
+            ],
+            Resource::Messaging(MessagingResource::Mosquitto) => {
+                &[&["MOSQUITTO_HOST"], &["MOSQUITTO_PORT"]]
+            }
+            Resource::Messaging(MessagingResource::Nats) => &[&["NATS_CREDS"]],
+            Resource::DistributedLocking(DistributedLockingResource::Etcd)
+            | Resource::DistributedLocking(DistributedLockingResource::V1Etcd) => {
+                &[&["ETCD_ENDPOINT"]]
+            }
+            Resource::Sql(SqlResource::Postgres) => &[&["POSTGRES_CONNECTION_URL"]],
+            Resource::Keyvalue(KeyvalueResource::Filesystem)
+            | Resource::Keyvalue(KeyvalueResource::V1Filesystem)
+            | Resource::Keyvalue(KeyvalueResource::Dapr)
+            | Resource::Messaging(MessagingResource::Filesystem)
+            | Resource::Messaging(MessagingResource::V1Filesystem)
+            | Resource::HttpServer(_)
+            | Resource::HttpClient(_)
+            | Resource::Configs(_) => &[],
+            Resource::Blob(BlobResource::Memory)
+            | Resource::Keyvalue(KeyvalueResource::Memory)
+            | Resource::Messaging(MessagingResource::Memory)
+            | Resource::DistributedLocking(DistributedLockingResource::Memory)
+            | Resource::Sql(SqlResource::Memory) => &[],
+        }
+    }
+
+    /// The 0.2 name of a 0.1 resource, e.g. `keyvalue.filesystem` for
+    /// `kv.filesystem`. Other resources are the same in both versions.
+    pub fn to_v2(&self) -> Resource {
+        match self {
+            Resource::Keyvalue(KeyvalueResource::V1AwsDynamoDb) => {
+                Resource::Keyvalue(KeyvalueResource::AwsDynamoDb)
+            }
+            Resource::Keyvalue(KeyvalueResource::V1Azblob) => {
+                Resource::Keyvalue(KeyvalueResource::Azblob)
+            }
+            Resource::Keyvalue(KeyvalueResource::V1Filesystem) => {
+                Resource::Keyvalue(KeyvalueResource::Filesystem)
+            }
+            Resource::Keyvalue(KeyvalueResource::V1Redis) => {
+                Resource::Keyvalue(KeyvalueResource::Redis)
+            }
+            Resource::Messaging(MessagingResource::V1Azsbus) => {
+                Resource::Messaging(MessagingResource::Azsbus)
+            }
+            Resource::Messaging(MessagingResource::V1Filesystem) => {
+                Resource::Messaging(MessagingResource::Filesystem)
+            }
+            Resource::DistributedLocking(DistributedLockingResource::V1Etcd) => {
+                Resource::DistributedLocking(DistributedLockingResource::Etcd)
+            }
+            resource => *resource,
+        }
+    }
+
+    /// The in-memory implementor of the same interface, if there is one.
+    pub fn in_memory(&self) -> Option<Resource> {
+        match self {
+            Resource::Blob(_) => Some(Resource::Blob(BlobResource::Memory)),
+            Resource::Keyvalue(_) => Some(Resource::Keyvalue(KeyvalueResource::Memory)),
+            Resource::Messaging(_) => Some(Resource::Messaging(MessagingResource::Memory)),
+            Resource::Configs(_) => Some(Resource::Configs(ConfigsResource::Memory)),
+            Resource::DistributedLocking(_) => Some(Resource::DistributedLocking(
+                DistributedLockingResource::Memory,
+            )),
+            Resource::Sql(_) => Some(Resource::Sql(SqlResource::Memory)),
+            Resource::HttpServer(_) | Resource::HttpClient(_) => None,
+        }
+    }
 }
 
+/// Every resource, in the order of their declaration.
+pub const RESOURCES: &[Resource] = &[
+    Resource::Blob(BlobResource::AwsS3),
+    Resource::Blob(BlobResource::Azblob),
+    Resource::Blob(BlobResource::Memory),
+    Resource::Keyvalue(KeyvalueResource::AwsDynamoDb),
+    Resource::Keyvalue(KeyvalueResource::Azblob),
+    Resource::Keyvalue(KeyvalueResource::Filesystem),
+    Resource::Keyvalue(KeyvalueResource::Memory),
+    Resource::Keyvalue(KeyvalueResource::Redis),
+    Resource::Keyvalue(KeyvalueResource::V1AwsDynamoDb),
+    Resource::Keyvalue(KeyvalueResource::V1Azblob),
+    Resource::Keyvalue(KeyvalueResource::V1Filesystem),
+    Resource::Keyvalue(KeyvalueResource::V1Redis),
+    Resource::Keyvalue(KeyvalueResource::Dapr),
+    Resource::Messaging(MessagingResource::Azsbus),
+    Resource::Messaging(MessagingResource::ConfluentApacheKafka),
+    Resource::Messaging(MessagingResource::Filesystem),
+    Resource::Messaging(MessagingResource::Memory),
+    Resource::Messaging(MessagingResource::Mosquitto),
+    Resource::Messaging(MessagingResource::Nats),
+    Resource::Messaging(MessagingResource::V1Azsbus),
+    Resource::Messaging(MessagingResource::V1Filesystem),
+    Resource::HttpServer(HttpServerResource::Server),
+    Resource::HttpClient(HttpClientResource::Client),
+    Resource::Configs(ConfigsResource::Azapp),
+    Resource::Configs(ConfigsResource::Envvars),
+    Resource::Configs(ConfigsResource::Memory),
+    Resource::Configs(ConfigsResource::Usersecrets),
+    Resource::DistributedLocking(DistributedLockingResource::Etcd),
+    Resource::DistributedLocking(DistributedLockingResource::Memory),
+    Resource::DistributedLocking(DistributedLockingResource::V1Etcd),
+    Resource::Sql(SqlResource::Postgres),
+    Resource::Sql(SqlResource::Memory),
+];
+
+/// The WIT interfaces that slight capabilities implement.
+pub const INTERFACES: &[&str] = &[
+    "blob-store",
+    "configs",
+    "distributed-locking",
+    "http-client",
+    "http-server",
+    "keyvalue",
+    "messaging",
+    "sql",
+];
+
 impl Display for Resource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -0,0 +1,305 @@
+//! A JSON Schema of the slightfile, for editors to validate and complete
+//! slightfiles with, e.g. with a `#:schema` directive in taplo.
+//!
+//! The schema follows draft 7. Besides the structure of the slightfile, it
+//! lists every resource, and the configs that each resource's implementor
+//! requires of a 0.2 capability.
+
+use serde_json::{json, Map, Value};
+
+use crate::{resource::RESOURCES, secret_store::SECRET_STORES};
+
+fn string_array() -> Value {
+    json!({ "type": "array", "items": { "type": "string" } })
+}
+
+fn non_negative() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn capability() -> Value {
+    let mut required_configs = vec![];
+    for resource in RESOURCES {
+        let configs = resource.required_configs();
+        if configs.is_empty() {
+            continue;
+        }
+        let properties: Map<String, Value> = configs
+            .iter()
+            .flat_map(|alternatives| alternatives.iter())
+            .map(|key| (key.to_string(), json!({ "type": "string" })))
+            .collect();
+        let required: Vec<Value> = configs
+            .iter()
+            .map(|alternatives| match alternatives {
+                [key] => json!({ "required": [key] }),
+                _ => json!({
+                    "anyOf": alternatives
+                        .iter()
+                        .map(|key| json!({ "required": [key] }))
+                        .collect::<Vec<_>>()
+                }),
+            })
+            .collect();
+        required_configs.push(json!({
+            "if": { "properties": { "resource": { "const": resource.to_string() } } },
+            "then": {
+                "required": ["configs"],
+                "properties": {
+                    "configs": { "properties": properties, "allOf": required }
+                }
+            }
+        }));
+    }
+
+    json!({
+        "anyOf": [
+            {
+                "description": "A 0.2 capability.",
+                "type": "object",
+                "required": ["resource", "name"],
+                "properties": {
+                    "resource": { "$ref": "#/definitions/resource" },
+                    "name": {
+                        "description": "The name the guest opens the capability with, or `*` for any name.",
+                        "type": "string"
+                    },
+                    "configs": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" }
+                    },
+                    "policy": { "$ref": "#/definitions/policy" }
+                },
+                "allOf": required_configs
+            },
+            {
+                "description": "A 0.1 capability.",
+                "type": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": { "$ref": "#/definitions/resource" }
+                },
+                "not": { "required": ["resource"] }
+            }
+        ]
+    })
+}
+
+/// The sections that a profile can replace.
+fn sections() -> Map<String, Value> {
+    let sections = json!({
+        "capability": {
+            "type": "array",
+            "items": { "$ref": "#/definitions/capability" }
+        },
+        "instance_pool": {
+            "type": "object",
+            "required": ["size"],
+            "properties": {
+                "size": non_negative(),
+                "max_instances": non_negative(),
+                "pooling_allocator": { "type": "boolean" }
+            }
+        },
+        "module_cache": {
+            "type": "object",
+            "properties": { "directory": { "type": "string" } }
+        },
+        "limits": {
+            "type": "object",
+            "properties": {
+                "max_memory_bytes": non_negative(),
+                "max_table_elements": non_negative(),
+                "fuel": non_negative(),
+                "timeout_ms": non_negative()
+            }
+        },
+        "wasi": {
+            "type": "object",
+            "properties": {
+                "preopens": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["host", "guest"],
+                        "properties": {
+                            "host": { "type": "string" },
+                            "guest": { "type": "string" },
+                            "mode": { "enum": ["read-only", "read-write"] }
+                        }
+                    }
+                },
+                "env": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" }
+                }
+            }
+        },
+        "shutdown": {
+            "type": "object",
+            "properties": { "drain_timeout_ms": non_negative() }
+        },
+        "telemetry": {
+            "type": "object",
+            "required": ["exporter"],
+            "properties": {
+                "exporter": { "enum": ["otlp", "file", "stdout"] },
+                "endpoint": { "type": "string" },
+                "path": { "type": "string" },
+                "service_name": { "type": "string" }
+            }
+        },
+        "metrics": {
+            "type": "object",
+            "required": ["address"],
+            "properties": { "address": { "type": "string" } }
+        },
+        "messaging_trigger": {
+            "type": "object",
+            "required": ["subscriptions"],
+            "properties": {
+                "subscriptions": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["capability", "topic"],
+                        "properties": {
+                            "capability": { "type": "string" },
+                            "topic": { "type": "string" }
+                        }
+                    }
+                },
+                "concurrency": { "type": "integer", "minimum": 1 },
+                "max_retries": non_negative(),
+                "retry_delay_ms": non_negative(),
+                "dead_letter_topic": { "type": "string" }
+            }
+        },
+        "schedule": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["cron", "function"],
+                "properties": {
+                    "cron": { "type": "string" },
+                    "function": { "type": "string" },
+                    "overlap": { "enum": ["skip", "queue"] }
+                }
+            }
+        }
+    });
+    match sections {
+        Value::Object(sections) => sections,
+        _ => unreachable!(),
+    }
+}
+
+/// The JSON Schema of the slightfile.
+pub fn schema() -> Value {
+    let resources: Vec<String> = RESOURCES.iter().map(|r| r.to_string()).collect();
+    let secret_stores: Vec<String> = SECRET_STORES
+        .iter()
+        .map(|s| String::from(s.clone()))
+        .collect();
+
+    let mut properties = Map::new();
+    properties.insert("specversion".into(), json!({ "enum": ["0.1", "0.2"] }));
+    properties.insert("secret_store".into(), json!({ "enum": secret_stores }));
+    properties.insert(
+        "secret_settings".into(),
+        json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["name", "value"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "value": { "type": "string" }
+                }
+            }
+        }),
+    );
+    properties.insert("secret_key_file".into(), json!({ "type": "string" }));
+    properties.extend(sections());
+    properties.insert(
+        "profile".into(),
+        json!({
+            "type": "object",
+            "additionalProperties": { "type": "object", "properties": sections() }
+        }),
+    );
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "slightfile",
+        "type": "object",
+        "required": ["specversion"],
+        "properties": properties,
+        "definitions": {
+            "resource": { "enum": resources },
+            "capability": capability(),
+            "policy": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "key_prefixes": string_array(),
+                    "topics": string_array(),
+                    "object_prefixes": string_array(),
+                    "tables": string_array(),
+                    "urls": string_array()
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Profile, SlightFile};
+
+    fn keys(value: &Value) -> Vec<String> {
+        let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_schema_has_every_section() {
+        let schema = schema();
+        let slightfile = serde_json::to_value(SlightFile::default()).unwrap();
+        assert_eq!(keys(&schema["properties"]), keys(&slightfile));
+        let profile = serde_json::to_value(Profile::default()).unwrap();
+        assert_eq!(
+            keys(&schema["properties"]["profile"]["additionalProperties"]["properties"]),
+            keys(&profile)
+        );
+    }
+
+    #[test]
+    fn test_schema_has_every_resource() {
+        let schema = schema();
+        let resources = schema["definitions"]["resource"]["enum"]
+            .as_array()
+            .unwrap();
+        assert_eq!(resources.len(), RESOURCES.len());
+        for resource in [
+            "keyvalue.awsdynamodb",
+            "distributed_locking.etcd",
+            "kv.redis",
+        ] {
+            assert!(resources.contains(&json!(resource)), "{resource}");
+        }
+
+        let redis = schema["definitions"]["capability"]["anyOf"][0]["allOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["if"]["properties"]["resource"]["const"] == "keyvalue.redis")
+            .unwrap();
+        assert_eq!(
+            redis["then"]["properties"]["configs"]["allOf"],
+            json!([{ "required": ["REDIS_ADDRESS"] }])
+        );
+    }
+}
@@ -13,6 +13,14 @@ pub enum SecretStoreResource {
     Local,
 }
 
+/// Every secret store, in the order of their declaration.
+pub const SECRET_STORES: &[SecretStoreResource] = &[
+    SecretStoreResource::Azapp,
+    SecretStoreResource::Envvars,
+    SecretStoreResource::Usersecrets,
+    SecretStoreResource::Local,
+];
+
 impl TryFrom<String> for SecretStoreResource {
     type Error = anyhow::Error;
 
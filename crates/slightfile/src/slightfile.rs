@@ -1,14 +1,25 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{
-    resource::HttpServerResource, Capability, Resource, ResourceName, SlightFile, SpecVersion,
+    cron::Cron,
+    interpolate::interpolate,
+    locate::Locator,
+    resource::{HttpServerResource, RESOURCES},
+    Capability, Resource, ResourceName, SlightFile, SpecVersion,
 };
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
 #[derive(Debug, Clone, Default)]
 pub struct SlightFileInner {
     inner: SlightFile,
     groups_by_resource: HashMap<String, HashSet<ResourceName>>,
+    /// The text the slightfile was read from, to report the position of
+    /// errors.
+    source: String,
+    /// The array of tables, and the entry in it, each capability comes from.
+    origins: Vec<(String, usize)>,
+    /// The tables of the sections that a profile replaced.
+    profile_sections: HashMap<&'static str, String>,
 }
 
 impl AsRef<SlightFile> for SlightFileInner {
@@ -25,55 +36,282 @@ impl AsMut<SlightFile> for SlightFileInner {
 
 impl SlightFileInner {
     pub fn from_toml_string(toml: &str) -> Result<Self> {
-        let inner = toml::from_str::<SlightFile>(toml)?;
+        let inner = match toml::from_str::<SlightFile>(toml) {
+            Ok(inner) => inner,
+            Err(e) => {
+                // an unknown resource only fails to match the untagged
+                // `Capability`, so name it
+                check_resources(toml)?;
+                return Err(e.into());
+            }
+        };
+        let origins = (0..inner.capability.as_ref().map_or(0, Vec::len))
+            .map(|i| ("capability".to_string(), i))
+            .collect();
         Ok(Self {
             inner,
+            source: toml.to_string(),
+            origins,
             ..Default::default()
         })
     }
+
+    /// ` at line L, column C` of the key at `path` in the `entry`th entry of
+    /// `table`, if it can be found.
+    fn at(&self, table: &str, entry: usize, path: &str) -> String {
+        Locator::new(&self.source)
+            .find(table, entry, path)
+            .map(|position| format!(" at {position}"))
+            .unwrap_or_default()
+    }
+
+    /// ` at line L, column C` of the key at `path` in the `i`th capability.
+    fn at_capability(&self, i: usize, path: &str) -> String {
+        match self.origins.get(i) {
+            Some((table, entry)) => self.at(table, *entry, path),
+            None => String::new(),
+        }
+    }
+
+    /// The table of `section`, which is in a profile if the profile
+    /// replaced it.
+    fn section(&self, section: &'static str) -> String {
+        self.profile_sections
+            .get(section)
+            .cloned()
+            .unwrap_or_else(|| section.to_string())
+    }
+
+    /// Describe the capability at position `i` of the slightfile, and where
+    /// it is, for errors.
+    fn describe(&self, i: usize, cap: &Capability) -> String {
+        format!(
+            "capability #{} (resource '{}', name '{}'){}",
+            i + 1,
+            cap.resource(),
+            cap.name(),
+            self.at_capability(i, "")
+        )
+    }
     pub fn check_version(&self) -> Result<()> {
         // check specversion
+        let capabilities = self.inner.capability.as_deref().unwrap_or_default();
         match self.inner.specversion {
             SpecVersion::V1 => {
-                if self.inner.capability.as_ref().is_some()
-                    && self
-                        .inner
-                        .capability
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .any(|cap| cap.is_v2())
-                {
-                    bail!("Error: you are using a 0.1 specversion, but you are using a 0.2 capability format");
+                if let Some(i) = capabilities.iter().position(|cap| cap.is_v2()) {
+                    bail!(
+                        "Error: you are using a 0.1 specversion, but you are using a 0.2 capability format{}",
+                        self.at_capability(i, "")
+                    );
                 }
             }
             SpecVersion::V2 => {
-                if self.inner.capability.as_ref().is_some()
-                    && self
-                        .inner
-                        .capability
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .any(|cap| cap.is_v1())
-                {
-                    bail!("Error: you are using a 0.2 specversion, but you are using a 0.1 capability format");
+                if let Some(i) = capabilities.iter().position(|cap| cap.is_v1()) {
+                    bail!(
+                        "Error: you are using a 0.2 specversion, but you are using a 0.1 capability format{}",
+                        self.at_capability(i, "")
+                    );
                 }
             }
         };
         Ok(())
     }
 
+    /// Merge the profile `name` into the slightfile, see [`crate::Profile`].
+    ///
+    /// The profiles are dropped from the slightfile, whether one is
+    /// selected or not.
+    pub fn apply_profile(&mut self, name: Option<&str>) -> Result<()> {
+        let mut profiles = self.inner.profile.take().unwrap_or_default();
+        let Some(name) = name else {
+            return Ok(());
+        };
+        let Some(profile) = profiles.remove(name) else {
+            let mut names: Vec<_> = profiles.keys().map(|n| format!("'{n}'")).collect();
+            if names.is_empty() {
+                bail!("Error: the profile '{name}' is not defined, the slightfile has no profiles");
+            }
+            names.sort();
+            bail!(
+                "Error: the profile '{name}' is not defined, the slightfile has profiles {}",
+                names.join(", ")
+            );
+        };
+
+        let table = format!("profile.{name}.capability");
+        for (j, cap) in profile.capability.into_iter().flatten().enumerate() {
+            let capabilities = self.inner.capability.get_or_insert_with(Vec::new);
+            let existing = capabilities.iter().position(|other| {
+                other.resource().to_cap_name() == cap.resource().to_cap_name()
+                    && other.name() == cap.name()
+            });
+            match existing {
+                Some(i) => {
+                    capabilities[i] = cap;
+                    if let Some(origin) = self.origins.get_mut(i) {
+                        *origin = (table.clone(), j);
+                    }
+                }
+                None => {
+                    capabilities.push(cap);
+                    self.origins.push((table.clone(), j));
+                }
+            }
+        }
+        for (section, is_set) in [
+            ("messaging_trigger", profile.messaging_trigger.is_some()),
+            ("schedule", profile.schedule.is_some()),
+        ] {
+            if is_set {
+                self.profile_sections
+                    .insert(section, format!("profile.{name}.{section}"));
+            }
+        }
+        let inner = &mut self.inner;
+        replace(&mut inner.instance_pool, profile.instance_pool);
+        replace(&mut inner.module_cache, profile.module_cache);
+        replace(&mut inner.limits, profile.limits);
+        replace(&mut inner.wasi, profile.wasi);
+        replace(&mut inner.shutdown, profile.shutdown);
+        replace(&mut inner.telemetry, profile.telemetry);
+        replace(&mut inner.metrics, profile.metrics);
+        replace(&mut inner.messaging_trigger, profile.messaging_trigger);
+        replace(&mut inner.schedule, profile.schedule);
+        Ok(())
+    }
+
+    /// Interpolate environment variables into the names and configs values
+    /// of the capabilities, see [`crate::interpolate`].
+    pub fn interpolate_env(&mut self) -> Result<()> {
+        let Some(mut capabilities) = self.inner.capability.take() else {
+            return Ok(());
+        };
+        let res = capabilities
+            .iter_mut()
+            .enumerate()
+            .try_for_each(|(i, cap)| self.interpolate_capability(i, cap));
+        self.inner.capability = Some(capabilities);
+        res
+    }
+
+    fn interpolate_capability(&self, i: usize, cap: &mut Capability) -> Result<()> {
+        let lookup = |name: &str| std::env::var(name).ok();
+        let what = self.describe(i, cap);
+        let Capability::V2(cap) = cap else {
+            return Ok(());
+        };
+        if let ResourceName::Specific(name) = &cap.name {
+            let name = interpolate(name, lookup).map_err(|e| {
+                anyhow!(
+                    "Error: the name of capability #{}{}: {e}",
+                    i + 1,
+                    self.at_capability(i, "name")
+                )
+            })?;
+            if !name.variables.is_empty() {
+                tracing::debug!("{what} is named '{}'", name.value);
+            }
+            cap.name = match name.value.as_str() {
+                "*" => ResourceName::Any,
+                _ => ResourceName::Specific(name.value),
+            };
+        }
+        for (key, value) in cap.configs.iter_mut().flatten() {
+            let interpolated = interpolate(value, lookup).map_err(|e| {
+                anyhow!(
+                    "Error: config '{key}' of capability #{} (resource '{}', name '{}'){}: {e}",
+                    i + 1,
+                    cap.resource,
+                    cap.name,
+                    self.at_capability(i, &format!("configs.{key}"))
+                )
+            })?;
+            if !interpolated.variables.is_empty() {
+                tracing::debug!(
+                    "config '{key}' of {what} is '{}'",
+                    interpolated.display(key)
+                );
+            }
+            *value = interpolated.value;
+        }
+        Ok(())
+    }
+
     /// For each capability, deduplicate the resource names.
     ///
     /// For example, if you have two resources with the same resource name,
     /// this will return only one resource.
     ///
     /// A special case is when you have a resource that uses the
-    /// Any resource name. In this case, all reousrces of the same capability
+    /// Any resource name. In this case, all resources of the same capability
     /// except this one will be removed from the list.
-    pub fn de_dup(self) -> Result<Self> {
-        unimplemented!("de-duplicate logic is not implemented yet");
+    ///
+    /// Entries that cannot be merged return an error naming both of them:
+    /// entries of the same capability with overlapping names, but different
+    /// resources, and entries with the same resource and name, but different
+    /// configs or policies.
+    pub fn de_dup(mut self) -> Result<Self> {
+        let Some(capabilities) = self.inner.capability.take() else {
+            return Ok(self);
+        };
+        // the entries that are kept, with their position in the slightfile
+        let mut kept: Vec<(usize, Capability)> = vec![];
+        for (i, cap) in capabilities.into_iter().enumerate() {
+            let mut keep = true;
+            let mut j = 0;
+            while j < kept.len() {
+                let (k, other) = &kept[j];
+                let overlaps = cap.name() == other.name()
+                    || cap.name() == ResourceName::Any
+                    || other.name() == ResourceName::Any;
+                if cap.resource().to_cap_name() != other.resource().to_cap_name() || !overlaps {
+                    j += 1;
+                    continue;
+                }
+                let same_settings =
+                    cap.configs() == other.configs() && cap.policy() == other.policy();
+                if cap.resource() != other.resource()
+                    || (cap.name() == other.name() && !same_settings)
+                {
+                    bail!(
+                        "Error: {} conflicts with {}",
+                        self.describe(i, &cap),
+                        self.describe(*k, other)
+                    );
+                }
+                if cap.name() == other.name() {
+                    // `cap` is a duplicate of `other`
+                    keep = false;
+                    break;
+                }
+                let any_is_kept = other.name() == ResourceName::Any;
+                if !same_settings {
+                    let (superseded, by) = if any_is_kept {
+                        (self.describe(i, &cap), self.describe(*k, other))
+                    } else {
+                        (self.describe(*k, other), self.describe(i, &cap))
+                    };
+                    tracing::warn!(
+                        "{superseded} has other configs or policy than {by}, which supersedes it"
+                    );
+                }
+                if any_is_kept {
+                    keep = false;
+                    break;
+                }
+                kept.remove(j);
+            }
+            if keep {
+                kept.push((i, cap));
+            }
+        }
+        kept.sort_by_key(|(i, _)| *i);
+        self.origins = kept
+            .iter()
+            .filter_map(|(i, _)| self.origins.get(*i).cloned())
+            .collect();
+        self.inner.capability = Some(kept.into_iter().map(|(_, cap)| cap).collect());
+        Ok(self)
     }
 
     /// Validate the namespace for each resource is unique in the slightfile.
@@ -126,6 +364,81 @@ impl SlightFileInner {
         Ok(())
     }
 
+    /// Validate that the policy of each capability only sets rules that
+    /// apply to the capability's resource.
+    pub fn validate_policies(&self) -> Result<()> {
+        if let Some(capabilities) = &self.inner.capability {
+            for (i, cap) in capabilities.iter().enumerate() {
+                if let Some(policy) = cap.policy() {
+                    policy.validate(&cap.resource()).map_err(|e| {
+                        e.context(format!(
+                            "invalid policy for capability '{}'{}",
+                            cap.name(),
+                            self.at_capability(i, "policy")
+                        ))
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate that each subscription of the messaging trigger uses a
+    /// messaging capability of the slightfile.
+    pub fn validate_messaging_trigger(&self) -> Result<()> {
+        let Some(trigger) = &self.inner.messaging_trigger else {
+            return Ok(());
+        };
+        let table = self.section("messaging_trigger");
+        let capabilities = self.inner.capability.as_deref().unwrap_or_default();
+        for (i, subscription) in trigger.subscriptions.iter().enumerate() {
+            let found = capabilities.iter().any(|cap| {
+                matches!(cap.resource(), Resource::Messaging(_))
+                    && (cap.name() == ResourceName::Any
+                        || cap.name() == ResourceName::Specific(subscription.capability.clone()))
+            });
+            if !found {
+                let at = match self.at(&format!("{table}.subscriptions"), i, "capability") {
+                    at if at.is_empty() => self.at(&table, 0, "subscriptions"),
+                    at => at,
+                };
+                bail!(
+                    "Error: the messaging trigger subscribes to topic '{}' with '{}', which is not a messaging capability of the slightfile{at}",
+                    subscription.topic,
+                    subscription.capability
+                );
+            }
+        }
+        if trigger.concurrency == Some(0) {
+            bail!(
+                "Error: the concurrency of the messaging trigger must be at least 1{}",
+                self.at(&table, 0, "concurrency")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn validate_schedule(&self) -> Result<()> {
+        let table = self.section("schedule");
+        for (i, schedule) in self.inner.schedule.iter().flatten().enumerate() {
+            if schedule.function.is_empty() {
+                bail!(
+                    "Error: the schedule '{}' does not name a function{}",
+                    schedule.cron,
+                    self.at(&table, i, "function")
+                );
+            }
+            schedule.cron.parse::<Cron>().with_context(|| {
+                format!(
+                    "Error: invalid schedule of function '{}'{}",
+                    schedule.function,
+                    self.at(&table, i, "cron")
+                )
+            })?;
+        }
+        Ok(())
+    }
+
     pub fn has_http_cap(&self) -> bool {
         if let Some(capability) = &self.inner.capability {
             capability.iter().any(|cap| match cap {
@@ -142,3 +455,63 @@ impl SlightFileInner {
         }
     }
 }
+
+/// Replace `section` by the one of a profile, if the profile has one.
+fn replace<T>(section: &mut Option<T>, profile: Option<T>) {
+    if profile.is_some() {
+        *section = profile;
+    }
+}
+
+/// Name the first unknown resource of the capabilities of `toml`, and where
+/// it is.
+fn check_resources(toml: &str) -> Result<()> {
+    let Ok(table) = toml::from_str::<toml::Table>(toml) else {
+        return Ok(());
+    };
+    let mut arrays = vec![("capability".to_string(), table.get("capability"))];
+    if let Some(profiles) = table.get("profile").and_then(|p| p.as_table()) {
+        for (name, profile) in profiles {
+            arrays.push((
+                format!("profile.{name}.capability"),
+                profile.get("capability"),
+            ));
+        }
+    }
+    for (name, array) in arrays {
+        let Some(entries) = array.and_then(|a| a.as_array()) else {
+            continue;
+        };
+        for (i, entry) in entries.iter().enumerate() {
+            // 0.1 capabilities name their resource with `name`
+            let key = match entry.get("resource") {
+                Some(_) => "resource",
+                None => "name",
+            };
+            let Some(resource) = entry.get(key).and_then(|r| r.as_str()) else {
+                continue;
+            };
+            if RESOURCES.iter().any(|r| r.to_string() == resource) {
+                continue;
+            }
+            let position = Locator::new(toml)
+                .find(&name, i, key)
+                .map(|position| format!(" at {position}"))
+                .unwrap_or_default();
+            let prefix = resource.split('.').next().unwrap_or(resource);
+            let mut expected: Vec<String> = RESOURCES
+                .iter()
+                .map(|r| r.to_string())
+                .filter(|r| r.split('.').next() == Some(prefix))
+                .collect();
+            if expected.is_empty() {
+                expected = RESOURCES.iter().map(|r| r.to_string()).collect();
+            }
+            bail!(
+                "Error: unknown resource '{resource}'{position}, expected one of '{}'",
+                expected.join("', '")
+            );
+        }
+    }
+    Ok(())
+}
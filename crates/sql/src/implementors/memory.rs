@@ -0,0 +1,71 @@
+//! A canned-answer sql database, meant for tests (e.g., `slight test`).
+//!
+//! Queries are not interpreted: each one is answered with the rows seeded
+//! for that exact (prepared) query, and statements passed to `exec` are
+//! accepted without being run.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use crate::sql::RowItem;
+
+use super::SqlImplementor;
+
+type Databases = Mutex<HashMap<String, HashMap<String, Vec<RowItem>>>>;
+
+static DATABASES: OnceLock<Databases> = OnceLock::new();
+
+fn databases() -> &'static Databases {
+    DATABASES.get_or_init(Default::default)
+}
+
+/// Answer `query` with `rows` on the database opened under `name`.
+pub fn seed(name: &str, query: &str, rows: Vec<RowItem>) {
+    databases()
+        .lock()
+        .unwrap()
+        .entry(name.to_string())
+        .or_default()
+        .insert(query.to_string(), rows);
+}
+
+/// Drop every seeded answer.
+pub fn reset() {
+    databases().lock().unwrap().clear();
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryImplementor {
+    name: String,
+}
+
+impl MemoryImplementor {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SqlImplementor for MemoryImplementor {
+    async fn query(&self, query: &str) -> Result<Vec<RowItem>> {
+        databases()
+            .lock()
+            .unwrap()
+            .get(&self.name)
+            .and_then(|queries| queries.get(query))
+            .cloned()
+            .with_context(|| format!("no rows were seeded for query '{query}'"))
+    }
+
+    async fn exec(&self, query: &str) -> Result<()> {
+        tracing::debug!("accepted statement '{query}'");
+        Ok(())
+    }
+}
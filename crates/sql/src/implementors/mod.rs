@@ -3,6 +3,8 @@ use async_trait::async_trait;
 
 use crate::sql::RowItem;
 
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(feature = "postgres")]
 pub mod postgres;
 
@@ -16,12 +16,10 @@ pub struct PostgresImplementor {
 }
 
 impl PostgresImplementor {
-    pub async fn new(slight_state: &BasicState) -> Self {
-        Self {
-            connection_url: get_from_state("POSTGRES_CONNECTION_URL", slight_state)
-                .await
-                .unwrap(),
-        }
+    pub async fn new(slight_state: &BasicState) -> Result<Self> {
+        Ok(Self {
+            connection_url: get_from_state("POSTGRES_CONNECTION_URL", slight_state).await?,
+        })
     }
 }
 
@@ -1,19 +1,106 @@
 use std::sync::Arc;
 
+use anyhow::{bail, Result};
 use async_trait::async_trait;
 use implementors::SqlImplementor;
-use slight_common::{impl_resource, BasicState};
-use slight_file::{capability_store::CapabilityStore, resource::SqlResource::*, Resource};
+use slight_common::{
+    impl_resource,
+    metrics::{observe_capability_call, ErrorVariant},
+    trace::{self, json, Traced, TracedError, Value},
+    BasicState,
+};
+use slight_file::{capability_store::CapabilityStore, resource::SqlResource::*, Policy, Resource};
 
 mod implementors;
+mod tables;
+#[cfg(feature = "memory")]
+pub use implementors::memory;
 #[cfg(feature = "postgres")]
 use implementors::postgres::PostgresImplementor;
 
-use sql::RowItem;
+use sql::{DataType, RowItem};
 wit_bindgen_wasmtime::export!({paths: ["../../wit/sql.wit"], async: *});
 wit_error_rs::impl_error!(sql::SqlError);
 wit_error_rs::impl_from!(anyhow::Error, sql::SqlError::UnexpectedError);
 
+impl ErrorVariant for sql::SqlError {
+    fn variant(&self) -> &'static str {
+        match self {
+            sql::SqlError::SyntaxError(_) => "syntax-error",
+            sql::SqlError::ConstraintViolation(_) => "constraint-violation",
+            sql::SqlError::AccessViolation(_) => "access-violation",
+            sql::SqlError::UnexpectedError(_) => "unexpected-error",
+        }
+    }
+}
+
+impl TracedError for sql::SqlError {
+    fn message(&self) -> String {
+        match self {
+            sql::SqlError::SyntaxError(message)
+            | sql::SqlError::ConstraintViolation(message)
+            | sql::SqlError::AccessViolation(message)
+            | sql::SqlError::UnexpectedError(message) => message.clone(),
+        }
+    }
+
+    fn from_variant(variant: &str, message: String) -> Self {
+        match variant {
+            "syntax-error" => sql::SqlError::SyntaxError(message),
+            "constraint-violation" => sql::SqlError::ConstraintViolation(message),
+            "access-violation" => sql::SqlError::AccessViolation(message),
+            _ => sql::SqlError::UnexpectedError(message),
+        }
+    }
+}
+
+impl Traced for RowItem {
+    fn to_trace(&self) -> Value {
+        let value = match &self.value {
+            DataType::Int32(v) => json!({ "int32": v }),
+            DataType::Int64(v) => json!({ "int64": v }),
+            DataType::Uint32(v) => json!({ "uint32": v }),
+            DataType::Uint64(v) => json!({ "uint64": v }),
+            DataType::Float(v) => json!({ "float": v }),
+            DataType::Double(v) => json!({ "double": v }),
+            DataType::Str(v) => json!({ "str": v }),
+            DataType::Boolean(v) => json!({ "boolean": v }),
+            DataType::Date(v) => json!({ "date": v }),
+            DataType::Time(v) => json!({ "time": v }),
+            DataType::Timestamp(v) => json!({ "timestamp": v }),
+            DataType::Binary(v) => json!({ "binary": v }),
+            DataType::Null => json!("null"),
+        };
+        json!({ "field_name": self.field_name, "value": value })
+    }
+    fn from_trace(mut value: Value) -> Result<Self> {
+        let field_name = String::from_trace(trace::take_field(&mut value, "field_name"))?;
+        let value = match trace::take_field(&mut value, "value") {
+            Value::Object(variant) if variant.len() == 1 => {
+                let (name, v) = variant.into_iter().next().unwrap();
+                match name.as_str() {
+                    "int32" => DataType::Int32(i32::from_trace(v)?),
+                    "int64" => DataType::Int64(i64::from_trace(v)?),
+                    "uint32" => DataType::Uint32(u32::from_trace(v)?),
+                    "uint64" => DataType::Uint64(u64::from_trace(v)?),
+                    "float" => DataType::Float(f64::from_trace(v)?),
+                    "double" => DataType::Double(f64::from_trace(v)?),
+                    "str" => DataType::Str(String::from_trace(v)?),
+                    "boolean" => DataType::Boolean(bool::from_trace(v)?),
+                    "date" => DataType::Date(String::from_trace(v)?),
+                    "time" => DataType::Time(String::from_trace(v)?),
+                    "timestamp" => DataType::Timestamp(String::from_trace(v)?),
+                    "binary" => DataType::Binary(Vec::from_trace(v)?),
+                    _ => bail!("unknown data type '{name}'"),
+                }
+            }
+            Value::String(s) if s == "null" => DataType::Null,
+            v => bail!("invalid data type '{v}'"),
+        };
+        Ok(Self { field_name, value })
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct Sql {
     implementor: Resource,
@@ -32,15 +119,50 @@ impl Sql {
 #[derive(Clone, Debug)]
 pub struct SqlInner {
     sql_implementor: Arc<dyn SqlImplementor + Send + Sync>,
+    name: String,
+    implementor: Resource,
+    policy: Option<Policy>,
 }
 
 impl SqlInner {
-    async fn new(sql_implementor: SqlImplementors, slight_state: &BasicState) -> Self {
-        Self {
+    async fn new(
+        sql_implementor: SqlImplementors,
+        slight_state: &BasicState,
+        name: &str,
+    ) -> Result<Self> {
+        Ok(Self {
             sql_implementor: match sql_implementor {
                 #[cfg(feature = "postgres")]
-                SqlImplementors::Postgres => Arc::new(PostgresImplementor::new(slight_state).await),
+                SqlImplementors::Postgres => {
+                    Arc::new(PostgresImplementor::new(slight_state).await?)
+                }
+                #[cfg(feature = "memory")]
+                SqlImplementors::Memory => Arc::new(memory::MemoryImplementor::new(name)),
             },
+            name: name.to_string(),
+            implementor: slight_state.implementor,
+            policy: slight_state.policy.clone(),
+        })
+    }
+
+    /// Fail with `access-violation` unless the capability's policy allows
+    /// every table `statement` uses, and they can all be told.
+    fn check_statement(&self, statement: &str) -> Result<(), sql::SqlError> {
+        let Some(policy) = &self.policy else {
+            return Ok(());
+        };
+        let tables = tables::referenced_tables(statement).map_err(|reason| {
+            sql::SqlError::AccessViolation(format!(
+                "the policy of '{}' cannot check the statement, {reason}",
+                self.name
+            ))
+        })?;
+        match tables.into_iter().find(|table| !policy.allows_table(table)) {
+            Some(table) => Err(sql::SqlError::AccessViolation(format!(
+                "the policy of '{}' does not allow table '{table}'",
+                self.name
+            ))),
+            None => Ok(()),
         }
     }
 }
@@ -50,14 +172,20 @@ impl SqlInner {
 pub enum SqlImplementors {
     #[cfg(feature = "postgres")]
     Postgres,
+    #[cfg(feature = "memory")]
+    Memory,
 }
 
-impl From<Resource> for SqlImplementors {
-    fn from(s: Resource) -> Self {
+impl TryFrom<Resource> for SqlImplementors {
+    type Error = anyhow::Error;
+
+    fn try_from(s: Resource) -> Result<Self> {
         match s {
             #[cfg(feature = "postgres")]
-            Resource::Sql(Postgres) => Self::Postgres,
-            p => panic!(
+            Resource::Sql(Postgres) => Ok(Self::Postgres),
+            #[cfg(feature = "memory")]
+            Resource::Sql(Memory) => Ok(Self::Memory),
+            p => bail!(
                 "failed to match provided name (i.e., '{p}') to any known host implementations"
             ),
         }
@@ -74,6 +202,18 @@ impl_resource!(
 #[derive(Debug)]
 pub struct StatementInner {
     query: String,
+    /// Why the statement could not be prepared, reported by `query` and
+    /// `exec` as `prepare` cannot fail.
+    error: Option<String>,
+}
+
+impl StatementInner {
+    fn prepared(&self) -> Result<&str, sql::SqlError> {
+        match &self.error {
+            Some(error) => Err(sql::SqlError::SyntaxError(error.clone())),
+            None => Ok(&self.query),
+        }
+    }
 }
 
 #[async_trait]
@@ -81,47 +221,100 @@ impl sql::Sql for Sql {
     type Sql = SqlInner;
     type Statement = StatementInner;
 
+    #[tracing::instrument(name = "sql.open", skip(self), err)]
     async fn sql_open(&mut self, name: &str) -> Result<Self::Sql, sql::SqlError> {
-        let s = self.implementor.to_string();
-        let state = if let Some(r) = self.capability_store.get(name, "sql") {
-            r.clone()
-        } else if let Some(r) = self.capability_store.get(&s, "sql") {
-            r.clone()
-        } else {
-            panic!(
-                "could not find capability under name '{}' for implementor '{}'",
-                name, &s
-            );
-        };
+        observe_capability_call(
+            self.implementor,
+            "open",
+            trace::open("sql", "open", json!({"name": name}), async {
+                let s = self.implementor.to_string();
+                let state = if let Some(r) = self.capability_store.get(name, "sql") {
+                    r.clone()
+                } else if let Some(r) = self.capability_store.get(&s, "sql") {
+                    r.clone()
+                } else {
+                    return Err(sql::SqlError::UnexpectedError(format!(
+                        "could not find capability under name '{name}' for implementor '{s}'"
+                    )));
+                };
 
-        tracing::log::info!("Opening implementor {}", &state.implementor);
+                tracing::log::info!("Opening implementor {}", &state.implementor);
 
-        let inner = Self::Sql::new(state.implementor.into(), &state).await;
+                let implementor: SqlImplementors = state.implementor.try_into()?;
+                let inner = Self::Sql::new(implementor, &state, name).await?;
 
-        Ok(inner)
+                Ok(inner)
+            }),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "sql.query",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn sql_query(
         &mut self,
         self_: &Self::Sql,
         statement: &Self::Statement,
     ) -> Result<Vec<RowItem>, sql::SqlError> {
-        Ok(self_.sql_implementor.query(&statement.query).await?)
+        observe_capability_call(
+            self_.implementor,
+            "query",
+            trace::call(
+                "sql",
+                "query",
+                json!({"name": self_.name, "statement": statement.query}),
+                async {
+                    let query = statement.prepared()?;
+                    self_.check_statement(query)?;
+                    Ok(self_.sql_implementor.query(query).await?)
+                },
+            ),
+        )
+        .await
     }
+    #[tracing::instrument(
+        name = "sql.exec",
+        skip_all,
+        fields(resource = %self_.name, implementor = %self_.implementor),
+        err
+    )]
     async fn sql_exec(
         &mut self,
         self_: &Self::Sql,
         statement: &Self::Statement,
     ) -> Result<(), sql::SqlError> {
-        Ok(self_.sql_implementor.exec(&statement.query).await?)
+        observe_capability_call(
+            self_.implementor,
+            "exec",
+            trace::call(
+                "sql",
+                "exec",
+                json!({"name": self_.name, "statement": statement.query}),
+                async {
+                    let query = statement.prepared()?;
+                    self_.check_statement(query)?;
+                    Ok(self_.sql_implementor.exec(query).await?)
+                },
+            ),
+        )
+        .await
     }
 
     async fn statement_prepare(&mut self, query: &str, params: Vec<&str>) -> Self::Statement {
         let mut prepared_query = String::from(query);
         let mut param_index = 0;
+        let mut error = None;
         while let Some(start_index) = prepared_query.find('?') {
             let end_index = start_index + 1;
             if params.len() <= param_index {
-                panic!("Not enough parameters provided for the query");
+                error = Some(format!(
+                    "the query has more placeholders than the {} parameters provided",
+                    params.len()
+                ));
+                break;
             }
             let param = &params[param_index];
 
@@ -137,12 +330,16 @@ impl sql::Sql for Sql {
             prepared_query.replace_range(start_index..end_index, &quoted_param);
             param_index += 1;
         }
-        if params.len() != param_index {
-            panic!("Too many parameters provided for the query");
+        if error.is_none() && params.len() != param_index {
+            error = Some(format!(
+                "the query has {param_index} placeholders, but {} parameters were provided",
+                params.len()
+            ));
         }
 
         StatementInner {
             query: prepared_query,
+            error,
         }
     }
 }
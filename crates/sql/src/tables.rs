@@ -0,0 +1,304 @@
+//! Finding the tables a sql statement uses, to enforce a capability's
+//! table allowlist.
+//!
+//! Statements are parsed with `sqlparser`, and every relation they name is a
+//! table, including table functions (e.g., `generate_series(1, 10)`), except
+//! for the names a `WITH` clause defines where they are in scope. Statements
+//! whose tables cannot be told, e.g., `COPY` or `GRANT`, are refused.
+
+use std::{collections::HashSet, ops::ControlFlow};
+
+use sqlparser::{
+    ast::{
+        visit_relations, AlterTableOperation, ColumnOption, Expr, ObjectName, ObjectType, Query,
+        SetExpr, Statement, TableConstraint, TableFactor, TableWithJoins, Visit, Visitor,
+    },
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+
+/// The relations of a statement that name a `WITH` query, by address.
+type CteReferences = HashSet<*const ObjectName>;
+
+/// Finds the queries in the expressions it visits, to look for references
+/// to `WITH` queries in them.
+struct Subqueries<'a> {
+    scope: &'a [String],
+    ctes: &'a mut CteReferences,
+}
+
+impl Visitor for Subqueries<'_> {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        match expr {
+            Expr::Subquery(query)
+            | Expr::ArraySubquery(query)
+            | Expr::InSubquery {
+                subquery: query, ..
+            }
+            | Expr::Exists {
+                subquery: query, ..
+            } => cte_references(query, self.scope, self.ctes),
+            _ => {}
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Look for references to `WITH` queries in the expressions of `node`.
+///
+/// This also visits the queries nested in `node` with the names in scope
+/// of `node`, which are a subset of the names in scope there, so it never
+/// takes a table for a `WITH` query.
+fn subqueries(node: &impl Visit, scope: &[String], ctes: &mut CteReferences) {
+    let _ = node.visit(&mut Subqueries { scope, ctes });
+}
+
+/// Add the relations of `query` that name a `WITH` query in `scope`, or in
+/// the `WITH` clause of `query`, to `ctes`.
+fn cte_references(query: &Query, scope: &[String], ctes: &mut CteReferences) {
+    let mut body_scope = scope.to_vec();
+    if let Some(with) = &query.with {
+        let names: Vec<String> = with
+            .cte_tables
+            .iter()
+            .map(|cte| cte.alias.name.value.clone())
+            .collect();
+        for (i, cte) in with.cte_tables.iter().enumerate() {
+            // a `WITH` query sees the ones before it, or all of them if it
+            // is recursive
+            let mut cte_scope = scope.to_vec();
+            cte_scope.extend_from_slice(if with.recursive { &names } else { &names[..i] });
+            cte_references(&cte.query, &cte_scope, ctes);
+        }
+        body_scope.extend(names);
+    }
+    set_expr_cte_references(&query.body, &body_scope, ctes);
+    subqueries(&query.order_by, &body_scope, ctes);
+}
+
+fn set_expr_cte_references(set_expr: &SetExpr, scope: &[String], ctes: &mut CteReferences) {
+    match set_expr {
+        SetExpr::Select(select) => {
+            for table in &select.from {
+                table_cte_references(table, scope, ctes);
+            }
+            subqueries(select, scope, ctes);
+        }
+        SetExpr::Query(query) => cte_references(query, scope, ctes),
+        SetExpr::SetOperation { left, right, .. } => {
+            set_expr_cte_references(left, scope, ctes);
+            set_expr_cte_references(right, scope, ctes);
+        }
+        SetExpr::Values(values) => subqueries(values, scope, ctes),
+        // the tables of the rest are always checked
+        _ => {}
+    }
+}
+
+fn table_cte_references(table: &TableWithJoins, scope: &[String], ctes: &mut CteReferences) {
+    for factor in std::iter::once(&table.relation).chain(table.joins.iter().map(|j| &j.relation)) {
+        match factor {
+            TableFactor::Table {
+                name, args: None, ..
+            } => {
+                if let [ident] = &name.0[..] {
+                    if scope.contains(&ident.value) {
+                        ctes.insert(name);
+                    }
+                }
+            }
+            TableFactor::Derived { subquery, .. } => cte_references(subquery, scope, ctes),
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => table_cte_references(table_with_joins, scope, ctes),
+            _ => {}
+        }
+    }
+}
+
+fn foreign_tables(constraints: &[TableConstraint]) -> Vec<&ObjectName> {
+    constraints
+        .iter()
+        .filter_map(|constraint| match constraint {
+            TableConstraint::ForeignKey { foreign_table, .. } => Some(foreign_table),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The tables of `statement` that are not relations, e.g., the tables of a
+/// `DROP TABLE`, or an error if `statement` is not one whose tables can be
+/// told.
+fn other_tables(statement: &Statement) -> Result<Vec<&ObjectName>, String> {
+    Ok(match statement {
+        Statement::Query(_)
+        | Statement::Insert { .. }
+        | Statement::Update { .. }
+        | Statement::Truncate { .. } => vec![],
+        Statement::Delete { tables, .. } => tables.iter().collect(),
+        Statement::CreateTable {
+            columns,
+            constraints,
+            like,
+            clone,
+            ..
+        } => {
+            let mut tables = foreign_tables(constraints);
+            tables.extend(columns.iter().flat_map(|column| {
+                column
+                    .options
+                    .iter()
+                    .filter_map(|option| match &option.option {
+                        ColumnOption::ForeignKey { foreign_table, .. } => Some(foreign_table),
+                        _ => None,
+                    })
+            }));
+            tables.extend(like.iter().chain(clone));
+            tables
+        }
+        Statement::CreateView { name, .. } => vec![name],
+        Statement::AlterTable { operation, .. } => match operation {
+            AlterTableOperation::RenameTable { table_name } => vec![table_name],
+            AlterTableOperation::AddConstraint(constraint) => {
+                foreign_tables(std::slice::from_ref(constraint))
+            }
+            _ => vec![],
+        },
+        Statement::Drop {
+            object_type: ObjectType::Table | ObjectType::View,
+            names,
+            ..
+        } => names.iter().collect(),
+        _ => return Err("only queries, and statements on tables and views, are allowed".into()),
+    })
+}
+
+/// The name of `name`, without quotes.
+fn table_name(name: &ObjectName) -> String {
+    name.0
+        .iter()
+        .map(|ident| ident.value.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// The tables `statements` uses, in order of appearance, or why they cannot
+/// be told.
+pub(crate) fn referenced_tables(statements: &str) -> Result<Vec<String>, String> {
+    let statements = Parser::parse_sql(&PostgreSqlDialect {}, statements)
+        .map_err(|e| format!("could not parse the statement: {e}"))?;
+
+    let mut tables: Vec<String> = vec![];
+    for statement in &statements {
+        let mut ctes = CteReferences::new();
+        match statement {
+            Statement::Query(query) => cte_references(query, &[], &mut ctes),
+            Statement::Insert { source, .. } => cte_references(source, &[], &mut ctes),
+            Statement::CreateTable {
+                query: Some(query), ..
+            }
+            | Statement::CreateView { query, .. } => cte_references(query, &[], &mut ctes),
+            _ => {}
+        }
+
+        let mut names: Vec<String> = other_tables(statement)?
+            .into_iter()
+            .map(table_name)
+            .collect();
+        let _ = visit_relations(statement, |relation| {
+            if !ctes.contains(&(relation as *const ObjectName)) {
+                names.push(table_name(relation));
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        for name in names {
+            if !tables.contains(&name) {
+                tables.push(name);
+            }
+        }
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::referenced_tables;
+
+    #[test]
+    fn test_referenced_tables() {
+        for (statement, tables) in [
+            ("SELECT 1", vec![]),
+            ("SELECT name FROM users", vec!["users"]),
+            (
+                "select u.name from public.users u join \"Orders\" as o on o.user_id = u.id",
+                vec!["public.users", "Orders"],
+            ),
+            (
+                "SELECT * FROM users, orders o WHERE 1 = 1",
+                vec!["users", "orders"],
+            ),
+            (
+                "INSERT INTO users (name) VALUES ('from secrets')",
+                vec!["users"],
+            ),
+            ("UPDATE users SET name = 'x' -- FROM secrets", vec!["users"]),
+            ("DELETE FROM users /* FROM secrets */", vec!["users"]),
+            ("CREATE TABLE IF NOT EXISTS users (id INT)", vec!["users"]),
+            (
+                "SELECT EXTRACT(YEAR FROM created_at) FROM users",
+                vec!["users"],
+            ),
+            (
+                "SELECT * FROM users WHERE id IN (SELECT user_id FROM orders)",
+                vec!["users", "orders"],
+            ),
+            (
+                "WITH recent AS (SELECT * FROM orders) SELECT * FROM recent",
+                vec!["orders"],
+            ),
+            (
+                "WITH recent AS (SELECT * FROM orders) \
+                 SELECT * FROM users WHERE id IN (SELECT user_id FROM recent)",
+                vec!["orders", "users"],
+            ),
+            (
+                "SELECT * FROM generate_series(1, 10)",
+                vec!["generate_series"],
+            ),
+            ("SELECT 'it''s FROM secrets' FROM users", vec!["users"]),
+        ] {
+            assert_eq!(referenced_tables(statement).unwrap(), tables, "{statement}");
+        }
+    }
+
+    #[test]
+    fn test_referenced_tables_are_not_hidden() {
+        for statement in [
+            "SELECT ARRAY(SELECT secret FROM secrets)",
+            "SELECT * FROM secrets, (WITH secrets AS (SELECT 1) SELECT * FROM secrets) t",
+            "WITH secrets AS (SELECT * FROM secrets) SELECT * FROM secrets",
+            "CREATE TABLE secrets AS (SELECT * FROM users)",
+            "TRUNCATE secrets",
+            "DROP TABLE users, secrets",
+            "ALTER TABLE users RENAME TO secrets",
+            "DELETE FROM users WHERE id IN (SELECT user_id FROM secrets)",
+            "SELECT 1; SELECT * FROM secrets",
+        ] {
+            let tables = referenced_tables(statement).unwrap();
+            assert!(tables.contains(&"secrets".to_string()), "{statement}");
+        }
+    }
+
+    #[test]
+    fn test_unknown_statements_are_refused() {
+        for statement in [
+            "COPY secrets TO STDOUT",
+            "GRANT SELECT ON secrets TO PUBLIC",
+            "SELECT * FROM",
+        ] {
+            assert!(referenced_tables(statement).is_err(), "{statement}");
+        }
+    }
+}
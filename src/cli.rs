@@ -25,13 +25,72 @@ pub enum Commands {
         module: WasmModule,
         #[clap(short, long)]
         link_all_capabilities: bool,
+        /// Reload the module when it or the slightfile changes. Routes are
+        /// registered at startup, so route changes need a restart
+        #[clap(short, long)]
+        watch: bool,
+        /// Record every capability call to this JSON lines file
+        #[clap(long, conflicts_with = "replay")]
+        record: Option<String>,
+        /// Answer capability calls from a file written by `--record`,
+        /// without connecting to any backend
+        #[clap(long)]
+        replay: Option<String>,
+        /// Apply this `[profile.<name>]` section of the slightfile
+        #[clap(long)]
+        profile: Option<String>,
+        /// Arguments passed to the module, after `--`
+        #[clap(last = true)]
+        args: Vec<String>,
+    },
+    /// Check that a slightfile provides the capabilities a module imports
+    Check {
+        #[clap(index = 1, value_parser = WasmModuleParser)]
+        module: WasmModule,
+    },
+    /// Rewrite a 0.1 slightfile in the 0.2 format, printing the changes
+    /// first
+    MigrateConfig {
+        /// Only print the changes
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Print the JSON Schema of the slightfile, for editors to validate
+    /// slightfiles with
+    Schema,
+    /// Generate a slightfile from the capabilities a module imports
+    InitConfig {
+        #[clap(index = 1, value_parser = WasmModuleParser)]
+        module: WasmModule,
+        /// Overwrite the slightfile if it already exists
+        #[clap(short, long)]
+        force: bool,
     },
-    /// Add a secret to the application
+    /// Run the tests a module exports against in-memory capabilities
+    Test {
+        #[clap(index = 1, value_parser = WasmModuleParser)]
+        module: WasmModule,
+        /// Only run the tests whose name contains this string
+        #[clap(index = 2)]
+        filter: Option<String>,
+        /// A TOML file with the data the in-memory capabilities start with
+        #[clap(short, long)]
+        fixture: Option<String>,
+    },
+    /// Run several apps listed in a manifest in one process
+    Serve {
+        #[clap(index = 1, value_parser)]
+        manifest: String,
+    },
+    /// Add a secret to the application, or manage its secrets
+    #[clap(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
     Secret {
-        #[clap(short, long, value_parser)]
-        key: String,
-        #[clap(short, long, value_parser)]
-        value: String,
+        #[clap(subcommand)]
+        command: Option<SecretCommands>,
+        #[clap(short, long, value_parser, requires = "value")]
+        key: Option<String>,
+        #[clap(short, long, value_parser, requires = "key")]
+        value: Option<String>,
     },
     /// Download a SpiderLightning interface
     Add {
@@ -58,6 +117,30 @@ pub enum Commands {
     },
 }
 
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum SecretCommands {
+    /// List the names of the secrets
+    List,
+    /// Remove a secret
+    Rm {
+        #[clap(index = 1)]
+        key: String,
+    },
+    /// Print the decrypted value of a secret
+    Get {
+        #[clap(index = 1)]
+        key: String,
+        /// Confirm that the value may be printed
+        #[clap(long)]
+        reveal: bool,
+    },
+    /// Re-encrypt every secret under a new key
+    RotateKey,
+    /// Re-encrypt the secrets encrypted by older versions of slight under
+    /// the slightfile's key
+    Migrate,
+}
+
 #[derive(Debug, Subcommand, PartialEq)]
 pub enum Templates {
     /// Start a new C Slight project
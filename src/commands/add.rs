@@ -3,9 +3,11 @@ use std::{
     io::{self, ErrorKind},
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use slight_core::interface_parser::InterfaceAtRelease;
 
+use crate::embedded;
+
 const GITHUB_URL: &str = "https://github.com/deislabs/spiderlightning/releases/download";
 
 const KEYVALUE_DOWNLOADS: [&str; 1] = ["keyvalue"];
@@ -24,15 +26,20 @@ const BLOBSTORE_DOWNLOADS: [&str; 2] = ["blob-store", "blob-types"];
 
 const ERROR_MSG: &str = "invalid interface name (2): currently, slight only supports the download of 'configs', 'keyvalue', 'distributed_locking', 'messaging', 'sql', and 'http'.";
 
+/// Write the interface files of `what_to_add` to `<name>_<release>`. The
+/// interfaces of this binary's release are embedded in it, other releases
+/// are downloaded.
 pub async fn handle_add(
     what_to_add: InterfaceAtRelease,
     folder_prefix: Option<&str>,
 ) -> Result<()> {
+    let embedded = embedded::is_embedded(&what_to_add.version);
     let (interface, release) = (what_to_add.name, what_to_add.version.to_string());
     let folder_name = format!("{interface}_{release}");
     let interfaces = get_interface_downloads_by_name(&interface);
     tracing::info!(
-        "Downloading interface {} for release v{} to {}",
+        "{} interface {} for release v{} to {}",
+        if embedded { "Writing" } else { "Downloading" },
         interface,
         release,
         folder_name
@@ -40,16 +47,13 @@ pub async fn handle_add(
     if !interfaces.is_empty() {
         maybe_recreate_dir(&format!("{}{}", folder_prefix.unwrap_or("./"), folder_name))?;
         for i in interfaces.iter() {
-            let resp = reqwest::get(format!("{GITHUB_URL}/v{release}/{i}.wit")).await?;
-            if !resp.status().is_success() {
-                bail!(
-                    "could not find interface {} for release v{}, pleases see all releases in {}",
-                    interface,
-                    release,
-                    GITHUB_URL
-                );
-            }
-            let resp = resp.text().await?;
+            let contents = if embedded {
+                embedded::wit(i)
+                    .with_context(|| format!("interface {i} is not embedded in slight"))?
+                    .to_string()
+            } else {
+                download_wit(i, &interface, &release).await?
+            };
             tracing::info!("writing {} to {}/{}.wit", i, folder_name, i);
             let mut out = File::create(format!(
                 "{}{}/{}.wit",
@@ -57,7 +61,7 @@ pub async fn handle_add(
                 folder_name,
                 i
             ))?;
-            io::copy(&mut resp.as_bytes(), &mut out)?;
+            io::copy(&mut contents.as_bytes(), &mut out)?;
         }
     } else {
         bail!("{}", ERROR_MSG);
@@ -65,6 +69,19 @@ pub async fn handle_add(
     Ok(())
 }
 
+async fn download_wit(file: &str, interface: &str, release: &str) -> Result<String> {
+    let resp = reqwest::get(format!("{GITHUB_URL}/v{release}/{file}.wit")).await?;
+    if !resp.status().is_success() {
+        bail!(
+            "could not find interface {} for release v{}, pleases see all releases in {}",
+            interface,
+            release,
+            GITHUB_URL
+        );
+    }
+    Ok(resp.text().await?)
+}
+
 fn maybe_recreate_dir(dir_name: &str) -> Result<()> {
     match remove_dir_all(dir_name) {
         Err(e) if e.kind() != ErrorKind::NotFound => {
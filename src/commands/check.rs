@@ -0,0 +1,169 @@
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::{bail, Context, Result};
+use slight_file::{resource::INTERFACES, SlightFile, SlightFileBuilder, SpecVersion};
+use wit_bindgen_wasmtime::wasmtime::{Engine, Module};
+
+/// Imports that are provided by the runtime rather than by a capability.
+const HOST_IMPORTS: &[&str] = &["wasi_snapshot_preview1", "canonical_abi"];
+
+/// A problem found while checking a slightfile against a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The module will fail at runtime.
+    Error(String),
+    /// Something is likely a mistake, but the module will still run.
+    Warning(String),
+}
+
+pub fn handle_check(module: impl AsRef<Path>, toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let toml = SlightFileBuilder::new()
+        .path(toml_file_path.as_ref())?
+        .build()?;
+    let imports = module_imports(&module)?;
+    let findings = check(&imports, toml.as_ref());
+
+    let mut errors = 0;
+    for finding in &findings {
+        match finding {
+            Finding::Error(msg) => {
+                errors += 1;
+                println!("error: {msg}");
+            }
+            Finding::Warning(msg) => println!("warning: {msg}"),
+        }
+    }
+    if errors > 0 {
+        bail!(
+            "{} does not satisfy the imports of {}: {errors} error(s)",
+            toml_file_path.as_ref().display(),
+            module.as_ref().display()
+        );
+    }
+    println!(
+        "{} satisfies the imports of {}",
+        toml_file_path.as_ref().display(),
+        module.as_ref().display()
+    );
+    Ok(())
+}
+
+/// Read the names of the interfaces a wasm module imports.
+pub fn module_imports(module: impl AsRef<Path>) -> Result<BTreeSet<String>> {
+    let module = module.as_ref();
+    let module = Module::from_file(&Engine::default(), module)
+        .with_context(|| format!("could not load module '{}'", module.display()))?;
+    Ok(module
+        .imports()
+        .map(|import| import.module().to_string())
+        .collect())
+}
+
+/// Check that the capabilities of a slightfile match the imports of a
+/// module, and that each capability has the configs its implementor needs.
+pub fn check(imports: &BTreeSet<String>, toml: &SlightFile) -> Vec<Finding> {
+    let mut findings = vec![];
+    let capabilities = toml.capability.clone().unwrap_or_default();
+
+    for import in imports {
+        if HOST_IMPORTS.contains(&import.as_str()) {
+            continue;
+        }
+        if !INTERFACES.contains(&import.as_str()) {
+            findings.push(Finding::Warning(format!(
+                "the module imports '{import}', which is not a slight interface"
+            )));
+            continue;
+        }
+        if !capabilities
+            .iter()
+            .any(|c| c.resource().interface_name() == import)
+        {
+            findings.push(Finding::Error(format!(
+                "the module imports '{import}', but no capability provides it"
+            )));
+        }
+    }
+
+    for c in &capabilities {
+        let resource = c.resource();
+        let interface = resource.interface_name();
+        if !imports.contains(interface) {
+            findings.push(Finding::Warning(format!(
+                "capability '{}' ({resource}) is declared, but the module does not import '{interface}'",
+                c.name()
+            )));
+        }
+
+        // 0.1 slightfiles read every config from the global secret store,
+        // so there is nothing to check in the slightfile itself.
+        if toml.specversion == SpecVersion::V1 {
+            continue;
+        }
+        let configs = c.configs().unwrap_or_default();
+        for alternatives in resource.required_configs() {
+            if !alternatives.iter().any(|key| configs.contains_key(*key)) {
+                findings.push(Finding::Error(format!(
+                    "capability '{}' ({resource}) is missing the config '{}'",
+                    c.name(),
+                    alternatives.join("' or '")
+                )));
+            }
+        }
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod unittest {
+    use std::collections::BTreeSet;
+
+    use slight_file::SlightFileInner;
+
+    use super::{check, Finding};
+
+    fn imports(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_check() -> anyhow::Result<()> {
+        let toml = SlightFileInner::from_toml_string(
+            r#"
+            specversion = "0.2"
+
+            [[capability]]
+            resource = "keyvalue.redis"
+            name = "my-store"
+                [capability.configs]
+                NOT_REDIS = "nope"
+
+            [[capability]]
+            resource = "http"
+            name = "my-rest-api"
+            "#,
+        )?;
+        let findings = check(
+            &imports(&["wasi_snapshot_preview1", "keyvalue", "messaging"]),
+            toml.as_ref(),
+        );
+        assert_eq!(
+            findings,
+            vec![
+                Finding::Error(
+                    "the module imports 'messaging', but no capability provides it".to_string()
+                ),
+                Finding::Error(
+                    "capability 'my-store' (keyvalue.redis) is missing the config 'REDIS_ADDRESS'"
+                        .to_string()
+                ),
+                Finding::Warning(
+                    "capability 'my-rest-api' (http) is declared, but the module does not import 'http-server'"
+                        .to_string()
+                ),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::BTreeSet, fmt::Write, path::Path};
+
+use anyhow::{bail, Result};
+use slight_file::{
+    resource::{
+        BlobResource, ConfigsResource, DistributedLockingResource, HttpClientResource,
+        HttpServerResource, KeyvalueResource, MessagingResource, SqlResource,
+    },
+    Resource,
+};
+
+use crate::commands::check::module_imports;
+
+pub fn handle_init_config(
+    module: impl AsRef<Path>,
+    toml_file_path: impl AsRef<Path>,
+    force: bool,
+) -> Result<()> {
+    let toml_file_path = toml_file_path.as_ref();
+    if toml_file_path.exists() && !force {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            toml_file_path.display()
+        );
+    }
+    let imports = module_imports(&module)?;
+    std::fs::write(toml_file_path, init_config(&imports))?;
+    println!(
+        "wrote {} for {}",
+        toml_file_path.display(),
+        module.as_ref().display()
+    );
+    Ok(())
+}
+
+/// The implementor used for each interface in a generated slightfile.
+///
+/// Where possible, these run without any external service, on the local
+/// filesystem or in memory (blobs and locks do not outlive the process).
+/// The others can point at a local emulator or container.
+fn default_resource(interface: &str) -> Option<Resource> {
+    let resource = match interface {
+        "blob-store" => Resource::Blob(BlobResource::Memory),
+        "configs" => Resource::Configs(ConfigsResource::Envvars),
+        "distributed-locking" => Resource::DistributedLocking(DistributedLockingResource::Memory),
+        "http-client" => Resource::HttpClient(HttpClientResource::Client),
+        "http-server" => Resource::HttpServer(HttpServerResource::Server),
+        "keyvalue" => Resource::Keyvalue(KeyvalueResource::Filesystem),
+        "messaging" => Resource::Messaging(MessagingResource::Filesystem),
+        "sql" => Resource::Sql(SqlResource::Postgres),
+        _ => return None,
+    };
+    Some(resource)
+}
+
+/// Generate a 0.2 slightfile with one capability per imported interface.
+///
+/// Required configs are read from environment variables of the same name,
+/// so the slightfile works as-is once they are exported.
+pub fn init_config(imports: &BTreeSet<String>) -> String {
+    let mut toml = String::from("specversion = \"0.2\"\n");
+    for import in imports {
+        let Some(resource) = default_resource(import) else {
+            continue;
+        };
+        writeln!(toml).unwrap();
+        writeln!(toml, "[[capability]]").unwrap();
+        writeln!(toml, "resource = \"{resource}\"").unwrap();
+        writeln!(toml, "name = \"*\"").unwrap();
+        let configs = resource.required_configs();
+        if !configs.is_empty() {
+            writeln!(toml, "    [capability.configs]").unwrap();
+            for alternatives in configs {
+                let key = alternatives[0];
+                writeln!(toml, "    {key} = \"${{envvars.{key}}}\"").unwrap();
+            }
+        }
+    }
+    toml
+}
+
+#[cfg(test)]
+mod unittest {
+    use std::collections::BTreeSet;
+
+    use slight_file::{SlightFileBuilder, SlightFileInner};
+    use tempfile::tempdir;
+
+    use super::init_config;
+    use crate::commands::check::check;
+
+    #[test]
+    fn test_init_config() -> anyhow::Result<()> {
+        let imports: BTreeSet<String> = ["wasi_snapshot_preview1", "keyvalue", "sql"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let generated = init_config(&imports);
+        assert_eq!(
+            generated,
+            r#"specversion = "0.2"
+
+[[capability]]
+resource = "keyvalue.filesystem"
+name = "*"
+
+[[capability]]
+resource = "sql.postgres"
+name = "*"
+    [capability.configs]
+    POSTGRES_CONNECTION_URL = "${envvars.POSTGRES_CONNECTION_URL}"
+"#
+        );
+
+        // a generated slightfile always passes `slight check`
+        let toml = SlightFileInner::from_toml_string(&generated)?;
+        assert!(check(&imports, toml.as_ref()).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_config_builds() -> anyhow::Result<()> {
+        let imports: BTreeSet<String> = [
+            "blob-store",
+            "configs",
+            "distributed-locking",
+            "http-client",
+            "http-server",
+            "keyvalue",
+            "messaging",
+            "sql",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        let tmp_dir = tempdir()?;
+        let path = tmp_dir.path().join("slightfile.toml");
+        std::fs::write(&path, init_config(&imports))?;
+
+        // `${envvars.X}` names the envvars configs store, so interpolation
+        // leaves it for the runtime to resolve
+        let toml = SlightFileBuilder::new().path(&path)?.build()?;
+        assert!(check(&imports, toml.as_ref()).is_empty());
+        let capabilities = toml.as_ref().capability.as_ref().unwrap();
+        assert_eq!(capabilities.len(), imports.len());
+        let sql = capabilities
+            .iter()
+            .find(|c| c.resource().to_string() == "sql.postgres")
+            .unwrap();
+        assert_eq!(
+            sql.configs().unwrap()["POSTGRES_CONNECTION_URL"],
+            "${envvars.POSTGRES_CONNECTION_URL}"
+        );
+        Ok(())
+    }
+}
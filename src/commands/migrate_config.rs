@@ -0,0 +1,417 @@
+use std::{collections::HashSet, fmt::Write, path::Path};
+
+use anyhow::{bail, Context, Result};
+use slight_file::{resource::RESOURCES, SlightFileInner, SpecVersion};
+use toml_edit::{value, Decor, Document, Item, Table};
+
+/// Lines of unchanged context around each change of the diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// The indentation of the `[capability.configs]` tables the migration adds,
+/// as in the slightfiles of the examples.
+const CONFIGS_INDENT: &str = "    ";
+
+pub fn handle_migrate_config(toml_file_path: impl AsRef<Path>, dry_run: bool) -> Result<()> {
+    let toml_file_path = toml_file_path.as_ref();
+    let old = std::fs::read_to_string(toml_file_path)
+        .with_context(|| format!("could not read '{}'", toml_file_path.display()))?;
+    let toml = SlightFileInner::from_toml_string(&old)?;
+    if toml.as_ref().specversion == SpecVersion::V2 {
+        println!(
+            "{} is already a 0.2 slightfile, there is nothing to migrate",
+            toml_file_path.display()
+        );
+        return Ok(());
+    }
+
+    let migration = migrate_config(&old)?;
+    let name = toml_file_path.display().to_string();
+    print!("{}", diff(&old, &migration.toml, &name));
+    for note in &migration.notes {
+        println!("note: {note}");
+    }
+    if dry_run {
+        return Ok(());
+    }
+    std::fs::write(toml_file_path, &migration.toml)
+        .with_context(|| format!("could not write '{}'", toml_file_path.display()))?;
+    println!("migrated {} to specversion 0.2", toml_file_path.display());
+    Ok(())
+}
+
+/// A 0.1 slightfile rewritten in the 0.2 format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub toml: String,
+    /// What the user should check by hand.
+    pub notes: Vec<String>,
+}
+
+/// Rewrite a 0.1 slightfile in the 0.2 format.
+///
+/// Each capability gets the 0.2 name of its resource, and the name `*`, as
+/// a 0.1 capability serves any name the guest opens. The configs its
+/// implementor requires, and does not have yet, are read from the global
+/// `secret_store`, e.g. `${azapp.REDIS_ADDRESS}`, which is removed. The
+/// rest of the slightfile, including comments, is kept as it is.
+pub fn migrate_config(toml: &str) -> Result<Migration> {
+    let old = SlightFileInner::from_toml_string(toml)?;
+    old.check_version()?;
+    let secret_store = old.as_ref().secret_store.clone().map(|store| {
+        let store: String = store.into();
+        store.trim_start_matches("configs.").to_string()
+    });
+
+    let mut document: Document = toml.parse().context("could not parse the slightfile")?;
+    set_str(&mut document["specversion"], "0.2");
+    document.remove("secret_store");
+
+    let mut notes = vec![];
+    if let Some(item) = document.get_mut("capability") {
+        // `capability = [{ ... }]` becomes an array of tables
+        let mut tables = match std::mem::take(item).into_array_of_tables() {
+            Ok(tables) => tables,
+            Err(_) => bail!("capability must be an array of tables"),
+        };
+        let mut cap_names = HashSet::new();
+        for table in tables.iter_mut() {
+            migrate_capability(table, secret_store.as_deref(), &mut cap_names, &mut notes)?;
+        }
+        *item = Item::ArrayOfTables(tables);
+        // the spacing around the `=` of an inline array
+        if let Some(decor) = document.key_decor_mut("capability") {
+            *decor = Decor::default();
+        }
+    }
+    let migrated_toml = document.to_string();
+
+    let new = SlightFileInner::from_toml_string(&migrated_toml)
+        .context("the migrated slightfile is invalid")?;
+    new.check_version()
+        .context("the migrated slightfile is invalid")?;
+    if let Some(store) = &secret_store {
+        notes.push(format!(
+            "only the configs of [capability.configs] are read now, add the other configs your capabilities read from '{store}'"
+        ));
+    }
+    Ok(Migration {
+        toml: migrated_toml,
+        notes,
+    })
+}
+
+/// Rewrite the 0.1 capability `table`, whose `name` is its resource.
+fn migrate_capability(
+    table: &mut Table,
+    secret_store: Option<&str>,
+    cap_names: &mut HashSet<String>,
+    notes: &mut Vec<String>,
+) -> Result<()> {
+    let Some((key, mut name)) = table.remove_entry("name") else {
+        bail!("every capability must have a name");
+    };
+    let Some(old_name) = name.as_str().map(str::to_string) else {
+        bail!("the name of a capability must be a string");
+    };
+    let Some(resource) = RESOURCES.iter().find(|r| r.to_string() == old_name) else {
+        bail!("unknown resource '{old_name}'");
+    };
+    let resource = resource.to_v2();
+    // 0.1 has one capability of each type, but in case there are more,
+    // only the first one serves any name
+    if cap_names.insert(resource.to_cap_name()) {
+        set_str(&mut name, "*");
+    } else {
+        notes.push(format!(
+            "there is more than one {} capability, '{old_name}' is named '{old_name}'",
+            resource.to_cap_name()
+        ));
+    }
+    // write `resource` first, and `name` after it
+    table.insert("resource", value(resource.to_string()));
+    table.insert_formatted(&key, name);
+
+    let required = resource.required_configs();
+    match secret_store {
+        _ if required.is_empty() => {}
+        Some(store) => {
+            let configs = table.entry("configs").or_insert_with(|| {
+                let mut configs = Table::new();
+                configs.decor_mut().set_prefix(CONFIGS_INDENT);
+                Item::Table(configs)
+            });
+            let Some(configs) = configs.as_table_like_mut() else {
+                bail!("the configs of capability '{old_name}' must be a table");
+            };
+            for alternatives in required {
+                if alternatives.iter().any(|key| configs.contains_key(key)) {
+                    continue;
+                }
+                let key = alternatives[0];
+                configs.insert(key, value(format!("${{{store}.{key}}}")));
+                if let Some(decor) = configs.key_decor_mut(key) {
+                    decor.set_prefix(CONFIGS_INDENT);
+                }
+            }
+        }
+        None => notes.push(format!(
+            "the slightfile has no secret_store, add the configs '{}' of {resource} to its [capability.configs]",
+            required
+                .iter()
+                .map(|alternatives| alternatives[0])
+                .collect::<Vec<_>>()
+                .join("', '")
+        )),
+    }
+    Ok(())
+}
+
+/// Replace the value of `item` with `new`, keeping the comment after it.
+fn set_str(item: &mut Item, new: &str) {
+    let decor = item.as_value().map(|value| value.decor().clone());
+    *item = value(new);
+    if let (Some(decor), Some(value)) = (decor, item.as_value_mut()) {
+        *value.decor_mut() = decor;
+    }
+}
+
+/// A unified diff of the lines of `old` and `new`.
+pub fn diff(old: &str, new: &str, name: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    // (tag, line of old, line of new)
+    let mut ops: Vec<(char, usize, usize)> = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', i, j));
+            i += 1;
+        } else {
+            ops.push(('+', i, j));
+            j += 1;
+        }
+    }
+
+    let mut out = String::new();
+    if ops.iter().all(|(tag, _, _)| *tag == ' ') {
+        return out;
+    }
+    writeln!(out, "--- {name}").unwrap();
+    writeln!(out, "+++ {name}").unwrap();
+    let mut k = 0;
+    while k < ops.len() {
+        let Some(first) = ops[k..].iter().position(|(tag, _, _)| *tag != ' ') else {
+            break;
+        };
+        let start = (k + first).saturating_sub(DIFF_CONTEXT);
+        // extend the hunk while changes are closer than twice the context
+        let mut end = k + first;
+        let mut unchanged = 0;
+        let mut m = end;
+        while m < ops.len() {
+            if ops[m].0 == ' ' {
+                unchanged += 1;
+                if unchanged > 2 * DIFF_CONTEXT {
+                    break;
+                }
+            } else {
+                unchanged = 0;
+                end = m;
+            }
+            m += 1;
+        }
+        let end = (end + 1 + DIFF_CONTEXT).min(ops.len());
+
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|(tag, _, _)| *tag != '+').count();
+        let new_len = hunk.iter().filter(|(tag, _, _)| *tag != '-').count();
+        let (_, old_start, new_start) = hunk[0];
+        writeln!(
+            out,
+            "@@ -{},{old_len} +{},{new_len} @@",
+            old_start + 1,
+            new_start + 1
+        )
+        .unwrap();
+        for (tag, i, j) in hunk {
+            let line = match tag {
+                '+' => new[*j],
+                _ => old[*i],
+            };
+            writeln!(out, "{tag}{line}").unwrap();
+        }
+        k = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod unittest {
+    use super::{diff, migrate_config};
+
+    #[test]
+    fn test_migrate_config() -> anyhow::Result<()> {
+        let migration = migrate_config(
+            r#"specversion = "0.1"
+secret_store = "configs.azapp"
+
+# the store of the users
+[[capability]]
+name = "kv.redis" # shared with the api
+
+# the inbox
+[[capability]]
+name = "mq.filesystem"
+
+[[capability]]
+name = "lockd.etcd"
+"#,
+        )?;
+        assert_eq!(
+            migration.toml,
+            r#"specversion = "0.2"
+
+# the store of the users
+[[capability]]
+resource = "keyvalue.redis"
+name = "*" # shared with the api
+    [capability.configs]
+    REDIS_ADDRESS = "${azapp.REDIS_ADDRESS}"
+
+# the inbox
+[[capability]]
+resource = "messaging.filesystem"
+name = "*"
+
+[[capability]]
+resource = "distributed_locking.etcd"
+name = "*"
+    [capability.configs]
+    ETCD_ENDPOINT = "${azapp.ETCD_ENDPOINT}"
+"#
+        );
+        assert_eq!(migration.notes.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_config_without_secret_store() -> anyhow::Result<()> {
+        let migration = migrate_config(
+            r#"specversion = "0.1"
+
+[[capability]]
+name = "kv.azblob"
+"#,
+        )?;
+        assert_eq!(
+            migration.toml,
+            r#"specversion = "0.2"
+
+[[capability]]
+resource = "keyvalue.azblob"
+name = "*"
+"#
+        );
+        assert_eq!(
+            migration.notes,
+            vec!["the slightfile has no secret_store, add the configs 'AZURE_STORAGE_ACCOUNT', 'AZURE_STORAGE_KEY' of keyvalue.azblob to its [capability.configs]"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_inline_capabilities() -> anyhow::Result<()> {
+        let migration = migrate_config(
+            r#"specversion = "0.1"
+secret_store = "configs.envvars"
+capability = [{ name = "kv.filesystem" }, { name = "lockd.etcd" }]
+"#,
+        )?;
+        assert_eq!(
+            migration.toml,
+            r#"specversion = "0.2"
+
+[[capability]]
+resource = "keyvalue.filesystem"
+name = "*"
+
+[[capability]]
+resource = "distributed_locking.etcd"
+name = "*"
+    [capability.configs]
+    ETCD_ENDPOINT = "${envvars.ETCD_ENDPOINT}"
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_config_keeps_existing_configs() -> anyhow::Result<()> {
+        let migration = migrate_config(
+            r#"specversion = "0.1"
+secret_store = "configs.azapp"
+
+[[capability]]
+name = "mq.azsbus"
+    [capability.configs]
+    AZURE_SERVICE_BUS_NAMESPACE = "my-namespace"
+"#,
+        )?;
+        assert_eq!(
+            migration.toml,
+            r#"specversion = "0.2"
+
+[[capability]]
+resource = "messaging.azsbus"
+name = "*"
+    [capability.configs]
+    AZURE_SERVICE_BUS_NAMESPACE = "my-namespace"
+    AZURE_POLICY_NAME = "${azapp.AZURE_POLICY_NAME}"
+    AZURE_POLICY_KEY = "${azapp.AZURE_POLICY_KEY}"
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            diff(old, new, "slightfile.toml"),
+            "--- slightfile.toml
++++ slightfile.toml
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -8,3 +8,4 @@
+ h
+ i
+ j
++k
+"
+        );
+        assert_eq!(diff(old, old, "slightfile.toml"), "");
+    }
+}
@@ -1,5 +1,11 @@
 pub mod add;
 pub mod buildjs;
+pub mod check;
+pub mod init_config;
+pub mod migrate_config;
 pub mod new;
 pub mod run;
+pub mod schema;
 pub mod secret;
+pub mod serve;
+pub mod test;
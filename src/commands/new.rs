@@ -3,12 +3,13 @@ use flate2::bufread::GzDecoder;
 use slight_core::interface_parser::InterfaceAtRelease;
 use std::io::Write;
 use std::{
-    fs::{read_to_string, File},
+    fs::{create_dir_all, read_to_string, File},
     io::BufReader,
+    path::Path,
 };
 use tar::Archive;
 
-use crate::cli::Templates;
+use crate::{cli::Templates, embedded};
 
 use super::add::handle_add;
 
@@ -21,17 +22,23 @@ pub async fn handle_new(name_at_release: &InterfaceAtRelease, template: &Templat
         bail!("project name cannot be c, js, or rust");
     }
 
-    let resp = reqwest::get(format!(
-        "https://github.com/deislabs/spiderlightning/releases/download/v{release}/{template}-template.tar.gz"
-    ))
-    .await?;
-    if resp.status() == 404 {
-        bail!("could not find template {} for release {}, pleases see all releases in https://github.com/deislabs/spiderlightning/releases", template, release);
-    }
+    // The templates of this binary's release are embedded in it, other
+    // releases are downloaded.
+    if embedded::is_embedded(&name_at_release.version) {
+        unpack_embedded_template(template)?;
+    } else {
+        let resp = reqwest::get(format!(
+            "https://github.com/deislabs/spiderlightning/releases/download/v{release}/{template}-template.tar.gz"
+        ))
+        .await?;
+        if resp.status() == 404 {
+            bail!("could not find template {} for release {}, pleases see all releases in https://github.com/deislabs/spiderlightning/releases", template, release);
+        }
 
-    let resp = resp.bytes().await?;
+        let resp = resp.bytes().await?;
 
-    Archive::new(GzDecoder::new(BufReader::new(resp.as_ref()))).unpack("./")?;
+        Archive::new(GzDecoder::new(BufReader::new(resp.as_ref()))).unpack("./")?;
+    }
 
     match template {
         Templates::C => setup_c_template(&project_name, &release)?,
@@ -50,6 +57,20 @@ pub async fn handle_new(name_at_release: &InterfaceAtRelease, template: &Templat
     Ok(())
 }
 
+/// Write the embedded files of `template` to `./<template>`, as unpacking
+/// its release tarball does.
+fn unpack_embedded_template(template: &Templates) -> Result<()> {
+    for (path, contents) in embedded::template(&template.to_string()) {
+        let path = Path::new("./").join(path);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        std::fs::write(&path, contents)
+            .with_context(|| format!("could not write {}", path.display()))?;
+    }
+    Ok(())
+}
+
 fn setup_js_template(project_name: &str) -> Result<()> {
     better_rename("js", project_name)?;
 
@@ -1,35 +1,42 @@
 use std::{
     collections::HashSet,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use as_any::Downcast;
 #[cfg(feature = "blob-store")]
 use slight_blob_store::{BlobStore, BLOB_STORE_SCHEME_NAME};
-use slight_common::{BasicState, Capability, Ctx as _, WasmtimeBuildable};
+use slight_common::{BasicState, Capability, Ctx as _, LimitExceeded, WasmtimeBuildable};
 #[cfg(feature = "distributed-locking")]
 use slight_distributed_locking::DistributedLocking;
 use slight_file::{
-    capability_store::CapabilityStore, Capability as TomlCapability, Resource, SecretStoreResource,
-    SlightFile, SlightFileBuilder, SpecVersion,
+    capability_store::CapabilityStore, Capability as TomlCapability, PreopenMode, Resource,
+    SecretStoreResource, SlightFile, SlightFileBuilder, SpecVersion,
 };
 #[cfg(feature = "http-client")]
 use slight_http_client::HttpClient;
 
 #[cfg(feature = "http-server")]
-use slight_http_server::{HttpServer, HttpServerInit};
+use slight_http_server::{HttpServer, HttpServerInit, HttpServerShutdown, DEFAULT_DRAIN_TIMEOUT};
 
 #[cfg(feature = "keyvalue")]
 use slight_keyvalue::Keyvalue;
 #[cfg(feature = "messaging")]
-use slight_messaging::Messaging;
-use slight_runtime::{Builder, Ctx};
+use slight_messaging::{Messaging, MessagingHandler, MessagingTrigger};
+use slight_runtime::{
+    default_cache_dir, Builder, Ctx, InstancePoolConfig, Limits, Preopen, RuntimeOptions,
+    WasiOptions,
+};
 #[cfg(feature = "runtime-configs")]
 use slight_runtime_configs::Configs;
 #[cfg(feature = "sql")]
 use slight_sql::Sql;
-use wit_bindgen_wasmtime::wasmtime::Store;
+use tokio::task::JoinHandle;
+use wit_bindgen_wasmtime::wasmtime::{Instance, Store};
+
+use crate::{commands::test::in_memory, schedule::Scheduler};
 
 pub type IORedirects = slight_runtime::IORedirects;
 
@@ -39,15 +46,53 @@ pub struct RunArgs {
     pub slightfile: PathBuf,
     pub io_redirects: Option<IORedirects>,
     pub link_all_capabilities: bool,
+    /// Arguments passed to the guest after the module path.
+    pub guest_args: Vec<String>,
+    /// Reload the http guest when the module or the slightfile changes.
+    pub watch: bool,
+    /// Serve http on this address instead of the one the guest asks for.
+    pub listen: Option<String>,
+    /// Record every capability call to this file.
+    pub record: Option<PathBuf>,
+    /// Answer capability calls from this file, written by `record`.
+    pub replay: Option<PathBuf>,
+    /// Apply this profile of the slightfile.
+    pub profile: Option<String>,
 }
 
+/// How often `--watch` checks the module and the slightfile for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
 pub async fn handle_run(args: RunArgs) -> Result<()> {
     let toml = SlightFileBuilder::new()
         .path(args.slightfile.clone())?
+        .profile(args.profile.clone())
         .build()?;
     let http_enabled = toml.has_http_cap();
+    if let Some(trace) = &args.record {
+        slight_common::trace::record_to(trace)?;
+    }
+    if let Some(trace) = &args.replay {
+        slight_common::trace::replay_from(trace)?;
+        tracing::info!("replaying capability calls from {}", trace.display());
+    }
+    let toml = capabilities_to_run(&args, toml.as_ref());
     tracing::info!("Starting slight");
-    let mut host_builder = Builder::from_module(&args.module)?;
+    if let Some(telemetry) = &toml.telemetry {
+        crate::telemetry::configure(telemetry, &args.slightfile)?;
+    }
+    if let Some(metrics) = &toml.metrics {
+        crate::metrics::serve(&metrics.address)?;
+    }
+    // The host instance only runs `on_server_init` or `_start`, so it never
+    // needs warm instances of its own.
+    let mut host_options = runtime_options(&toml, &args.slightfile);
+    if let Some(pool_config) = host_options.instance_pool.as_mut() {
+        pool_config.size = 0;
+    }
+    let wasi = wasi_options(&toml, &args.slightfile, &args.module, &args.guest_args);
+    let mut host_builder =
+        Builder::from_module_with_options(&args.module, &host_options)?.set_wasi(wasi.clone());
     let mut linked_capabilities: HashSet<String> = HashSet::new();
 
     if args.link_all_capabilities {
@@ -56,7 +101,7 @@ pub async fn handle_run(args: RunArgs) -> Result<()> {
     }
 
     build_store_instance(
-        toml.as_ref(),
+        &toml,
         &args.slightfile,
         &mut host_builder,
         &mut linked_capabilities,
@@ -67,18 +112,24 @@ pub async fn handle_run(args: RunArgs) -> Result<()> {
         tracing::info!("slight io redirects were specified");
         host_builder = host_builder.set_io(io_redirects);
     }
-    let (mut store, instance) = host_builder.build().await;
+    let (mut store, instance) = host_builder.build().await?;
+    let messaging_trigger =
+        spawn_messaging_trigger(&toml, &args, wasi.clone(), &mut store, &instance).await?;
+    let scheduler = spawn_scheduler(&toml, &args, wasi.clone(), &mut store, &instance).await?;
+    let triggers: Vec<_> = messaging_trigger.into_iter().chain(scheduler).collect();
 
     // looking for the http capability.
     if cfg!(feature = "http-server") && http_enabled {
         log::debug!("Http capability enabled");
         update_http_states(
-            toml.as_ref(),
-            args.slightfile,
+            &toml,
+            &args.slightfile,
             &args.module,
             &mut store,
-            args.io_redirects,
+            args.io_redirects.clone(),
+            wasi,
             args.link_all_capabilities,
+            args.listen.clone(),
         )
         .await?;
 
@@ -93,12 +144,30 @@ pub async fn handle_run(args: RunArgs) -> Result<()> {
         }
 
         log::info!("waiting for http to finish...");
-        close_http_server(store).await;
+        close_http_server(
+            store,
+            &instance,
+            host_options.limits,
+            args.watch.then_some(&args),
+        )
+        .await?;
+        for trigger in triggers {
+            trigger.await??;
+        }
+    } else if !triggers.is_empty() {
+        log::info!("waiting for triggers...");
+        for trigger in triggers {
+            trigger.await??;
+        }
     } else {
         instance
             .get_typed_func::<(), _>(&mut store, "_start")?
             .call_async(&mut store, ())
-            .await?;
+            .await
+            .map_err(|e| match LimitExceeded::from_error(&e) {
+                Some(limit) => anyhow::anyhow!(limit),
+                None => e,
+            })?;
     }
     Ok(())
 }
@@ -121,9 +190,40 @@ async fn update_http_states(
     module: impl AsRef<Path>,
     store: &mut Store<slight_runtime::RuntimeContext>,
     maybe_stdio: Option<IORedirects>,
+    wasi: WasiOptions,
     link_all: bool,
+    listen: Option<String>,
 ) -> Result<(), anyhow::Error> {
-    let mut guest_builder = Builder::from_module(module)?;
+    let guest_builder =
+        build_guest_builder(toml, toml_file_path, module, maybe_stdio, wasi, link_all).await?;
+    let http_api_resource: &mut HttpServer<Builder> = get_resource(store, "http");
+    http_api_resource.update_state(slight_common::Builder::new(guest_builder))?;
+    http_api_resource.set_drain_timeout(
+        toml.shutdown
+            .as_ref()
+            .and_then(|shutdown| shutdown.drain_timeout_ms)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DRAIN_TIMEOUT),
+    );
+    if let Some(listen) = listen {
+        http_api_resource.set_address(listen);
+    }
+    Ok(())
+}
+
+/// Create the builder the http server, the messaging trigger and the
+/// scheduler use to instantiate the guest for each request, message or run.
+async fn build_guest_builder(
+    toml: &SlightFile,
+    toml_file_path: impl AsRef<Path>,
+    module: impl AsRef<Path>,
+    maybe_stdio: Option<IORedirects>,
+    wasi: WasiOptions,
+    link_all: bool,
+) -> Result<Builder> {
+    let mut guest_builder =
+        Builder::from_module_with_options(module, &runtime_options(toml, &toml_file_path))?
+            .set_wasi(wasi);
     let mut linked_capabilities = HashSet::new();
 
     if link_all {
@@ -140,21 +240,304 @@ async fn update_http_states(
         tracing::info!("setting HTTP guest builder io redirects");
         guest_builder = guest_builder.set_io(ioredirects);
     }
-    let http_api_resource: &mut HttpServer<Builder> = get_resource(store, "http");
-    http_api_resource.update_state(slight_common::Builder::new(guest_builder))?;
-    Ok(())
+    guest_builder.prewarm().await?;
+    Ok(guest_builder)
+}
+
+/// Subscribe the messaging trigger of the slightfile, if it has one. The
+/// returned task handles messages until a shutdown signal.
+#[cfg(feature = "messaging")]
+async fn spawn_messaging_trigger(
+    toml: &SlightFile,
+    args: &RunArgs,
+    wasi: WasiOptions,
+    store: &mut Store<slight_runtime::RuntimeContext>,
+    instance: &Instance,
+) -> Result<Option<JoinHandle<Result<()>>>> {
+    let Some(config) = &toml.messaging_trigger else {
+        return Ok(None);
+    };
+    if !MessagingHandler::is_exported(&mut *store, instance) {
+        bail!(
+            "the slightfile has a messaging trigger, but the module does not export `on-message`"
+        );
+    }
+    let guest_builder = build_guest_builder(
+        toml,
+        &args.slightfile,
+        &args.module,
+        args.io_redirects.clone(),
+        wasi,
+        args.link_all_capabilities,
+    )
+    .await?;
+    let capability_store = capability_store(toml, &args.slightfile)?;
+    let trigger = MessagingTrigger::new(config, &capability_store, guest_builder).await?;
+    Ok(Some(tokio::spawn(trigger.run(shutdown_signal()))))
+}
+
+#[cfg(not(feature = "messaging"))]
+async fn spawn_messaging_trigger(
+    toml: &SlightFile,
+    _args: &RunArgs,
+    _wasi: WasiOptions,
+    _store: &mut Store<slight_runtime::RuntimeContext>,
+    _instance: &Instance,
+) -> Result<Option<JoinHandle<Result<()>>>> {
+    if toml.messaging_trigger.is_some() {
+        bail!("the slightfile has a messaging trigger, but the messaging feature is not enabled");
+    }
+    Ok(None)
+}
+
+/// Start the schedules of the slightfile, if it has any. The returned task
+/// runs them until a shutdown signal.
+async fn spawn_scheduler(
+    toml: &SlightFile,
+    args: &RunArgs,
+    wasi: WasiOptions,
+    store: &mut Store<slight_runtime::RuntimeContext>,
+    instance: &Instance,
+) -> Result<Option<JoinHandle<Result<()>>>> {
+    let Some(schedules) = toml.schedule.as_deref().filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+    for schedule in schedules {
+        instance
+            .get_typed_func::<(), ()>(&mut *store, &schedule.function)
+            .with_context(|| {
+                format!(
+                    "the slightfile schedules `{}`, but the module does not export it as a function that takes and returns nothing",
+                    schedule.function
+                )
+            })?;
+    }
+    let guest_builder = build_guest_builder(
+        toml,
+        &args.slightfile,
+        &args.module,
+        args.io_redirects.clone(),
+        wasi,
+        args.link_all_capabilities,
+    )
+    .await?;
+    let scheduler = Scheduler::new(schedules, guest_builder)?;
+    Ok(Some(tokio::spawn(scheduler.run(shutdown_signal()))))
 }
 
 #[cfg(not(feature = "http-server"))]
-async fn close_http_server(_store: Store<slight_runtime::RuntimeContext>) {
+async fn close_http_server(
+    _store: Store<slight_runtime::RuntimeContext>,
+    _instance: &Instance,
+    _limits: Limits,
+    _watch: Option<&RunArgs>,
+) -> Result<()> {
     log::debug!("http-server feature is not enabled");
+    Ok(())
 }
 
 #[cfg(feature = "http-server")]
-async fn close_http_server(mut store: Store<slight_runtime::RuntimeContext>) {
-    shutdown_signal().await;
+async fn close_http_server(
+    mut store: Store<slight_runtime::RuntimeContext>,
+    instance: &Instance,
+    limits: Limits,
+    watch: Option<&RunArgs>,
+) -> Result<()> {
     let http_api_resource: &mut HttpServer<Builder> = get_resource(&mut store, "http");
-    http_api_resource.close();
+    match watch {
+        Some(args) => {
+            // The clone shares its builder with the running server.
+            let http_server = http_api_resource.clone();
+            tokio::select! {
+                _ = shutdown_signal() => {},
+                _ = watch_and_reload(args, http_server) => {},
+            }
+        }
+        None => shutdown_signal().await,
+    }
+    // stop accepting connections and let in-flight requests finish
+    http_api_resource.shutdown().await;
+
+    // give the guest a chance to flush its state, with the fuel and deadline
+    // of a fresh invocation rather than what is left from `on_server_init`
+    if HttpServerShutdown::is_exported(&mut store, instance) {
+        limits.arm(&mut store)?;
+        let http_server = HttpServerShutdown::new(&mut store, instance, |ctx| {
+            ctx.get_http_server_shutdown_mut()
+        })?;
+        if let Err(e) = http_server.on_server_shutdown(&mut store).await? {
+            bail!(e);
+        }
+    }
+    Ok(())
+}
+
+/// Watch the module and the slightfile, and swap the http guest builder
+/// whenever one of them changes.
+///
+/// The listening socket is kept open. Requests that are already running
+/// finish on the old instance, new requests use the new builder. If the
+/// reload fails, the previous builder stays in place.
+///
+/// `on_server_init` does not run again, so the routes the guest registered
+/// at startup are kept: a changed handler is picked up, but adding, removing
+/// or renaming a route needs a restart of slight.
+#[cfg(feature = "http-server")]
+async fn watch_and_reload(args: &RunArgs, mut http_server: HttpServer<Builder>) {
+    let paths = [args.module.clone(), args.slightfile.clone()];
+    let mut last_modified = modified_times(&paths);
+    let mut pending = false;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    tracing::info!("watching {:?} for changes", paths);
+    loop {
+        interval.tick().await;
+        let modified = modified_times(&paths);
+        if modified != last_modified {
+            // Wait for the files to settle, so that a module that is still
+            // being written is not loaded.
+            last_modified = modified;
+            pending = true;
+            continue;
+        }
+        if !pending {
+            continue;
+        }
+        pending = false;
+
+        tracing::info!("change detected, reloading {}", args.module.display());
+        match reload_guest_builder(args).await {
+            Ok(guest_builder) => {
+                match http_server.update_state(slight_common::Builder::new(guest_builder)) {
+                    Ok(_) => tracing::info!(
+                        "reloaded {}, restart slight to pick up route changes",
+                        args.module.display()
+                    ),
+                    Err(e) => tracing::error!("failed to swap the guest builder: {e}"),
+                }
+            }
+            Err(e) => tracing::error!("failed to reload, keeping the previous version: {e:#}"),
+        }
+    }
+}
+
+#[cfg(feature = "http-server")]
+async fn reload_guest_builder(args: &RunArgs) -> Result<Builder> {
+    let toml = SlightFileBuilder::new()
+        .path(args.slightfile.clone())?
+        .profile(args.profile.clone())
+        .build()?;
+    let toml = capabilities_to_run(args, toml.as_ref());
+    let wasi = wasi_options(&toml, &args.slightfile, &args.module, &args.guest_args);
+    build_guest_builder(
+        &toml,
+        &args.slightfile,
+        &args.module,
+        args.io_redirects.clone(),
+        wasi,
+        args.link_all_capabilities,
+    )
+    .await
+}
+
+/// A replay needs no backend, so its capabilities are swapped for
+/// in-memory ones. Their calls are answered from the trace anyway.
+fn capabilities_to_run(args: &RunArgs, toml: &SlightFile) -> SlightFile {
+    if args.replay.is_some() {
+        in_memory(toml)
+    } else {
+        toml.clone()
+    }
+}
+
+fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Map the runtime sections of the slightfile to `RuntimeOptions`.
+pub(crate) fn runtime_options(
+    toml: &SlightFile,
+    toml_file_path: impl AsRef<Path>,
+) -> RuntimeOptions {
+    let instance_pool = toml.instance_pool.as_ref().map(|pool| InstancePoolConfig {
+        size: pool.size,
+        max_instances: pool.max_instances,
+        pooling_allocator: pool.pooling_allocator,
+    });
+    let module_cache_dir = toml
+        .module_cache
+        .as_ref()
+        .map(|cache| match &cache.directory {
+            Some(dir) => toml_file_path
+                .as_ref()
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join(dir),
+            None => default_cache_dir(),
+        });
+    let limits = toml
+        .limits
+        .as_ref()
+        .map(|limits| Limits {
+            max_memory_bytes: limits
+                .max_memory_bytes
+                .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX)),
+            max_table_elements: limits.max_table_elements,
+            fuel: limits.fuel,
+            timeout: limits.timeout_ms.map(Duration::from_millis),
+        })
+        .unwrap_or_default();
+    RuntimeOptions {
+        instance_pool,
+        module_cache_dir,
+        limits,
+    }
+}
+
+/// Map the `[wasi]` section of the slightfile and the guest arguments to
+/// `WasiOptions`.
+pub(crate) fn wasi_options(
+    toml: &SlightFile,
+    toml_file_path: impl AsRef<Path>,
+    module: impl AsRef<Path>,
+    guest_args: &[String],
+) -> WasiOptions {
+    let base_dir = toml_file_path
+        .as_ref()
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_owned();
+    let wasi = toml.wasi.clone().unwrap_or_default();
+    let preopens = wasi.preopens.map(|preopens| {
+        preopens
+            .into_iter()
+            .map(|preopen| Preopen {
+                host_path: base_dir.join(preopen.host),
+                guest_path: preopen.guest,
+                read_only: preopen.mode == PreopenMode::ReadOnly,
+            })
+            .collect()
+    });
+    let mut env: Vec<(String, String)> = wasi.env.unwrap_or_default().into_iter().collect();
+    env.sort();
+    // Only override the host's arguments when the guest was given some, so
+    // that modules see the same argv as before when none are passed.
+    let args = if guest_args.is_empty() {
+        None
+    } else {
+        Some(
+            std::iter::once(module.as_ref().display().to_string())
+                .chain(guest_args.iter().cloned())
+                .collect(),
+        )
+    };
+    WasiOptions {
+        preopens,
+        env,
+        args,
+    }
 }
 
 fn get_resource<'a, T>(store: &'a mut Store<Ctx>, scheme_name: &'a str) -> &'a mut T
@@ -176,10 +559,27 @@ where
 }
 
 async fn shutdown_signal() {
-    // Wait for the CTRL+C signal
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to install CTRL+C signal handler");
+    // Wait for the CTRL+C signal, or SIGTERM from an orchestrator
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install CTRL+C signal handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM signal handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("received shutdown signal");
 }
 
 fn link_all_caps(builder: &mut Builder, linked_capabilities: &mut HashSet<String>) -> Result<()> {
@@ -207,7 +607,7 @@ fn link_all_caps(builder: &mut Builder, linked_capabilities: &mut HashSet<String
         .add_to_builder("http".to_string(), http);
     linked_capabilities.insert("http".to_string());
 
-    let http_client = HttpClient::new();
+    let http_client = HttpClient::new(None);
     builder
         .link_capability::<HttpClient>()?
         .add_to_builder("http-client".to_string(), http_client);
@@ -216,13 +616,16 @@ fn link_all_caps(builder: &mut Builder, linked_capabilities: &mut HashSet<String
     Ok(())
 }
 
-async fn build_store_instance(
+pub(crate) async fn build_store_instance(
     toml: &SlightFile,
     toml_file_path: impl AsRef<Path>,
     builder: &mut Builder,
     linked_capabilities: &mut HashSet<String>,
 ) -> Result<()> {
     let mut capability_store = CapabilityStore::<BasicState>::new();
+    // The name of the http-client capability whose policy the guest gets.
+    #[cfg(feature = "http-client")]
+    let mut http_client_policy: Option<String> = None;
 
     builder.link_wasi()?;
     for c in toml.capability.as_ref().unwrap() {
@@ -322,13 +725,26 @@ async fn build_store_instance(
             }
             #[cfg(feature = "http-client")]
             Resource::HttpClient(_) => {
-                if !linked_capabilities.contains("http-client") {
-                    let http_client = HttpClient::new();
-                    builder
-                        .link_capability::<HttpClient>()?
-                        .add_to_builder("http-client".to_string(), http_client);
+                let policy = c.policy();
+                if policy.is_some() {
+                    if let Some(other) = &http_client_policy {
+                        bail!(
+                            "the http-client capabilities '{other}' and '{}' both have a policy, only one may",
+                            c.name()
+                        );
+                    }
+                    http_client_policy = Some(c.name().to_string());
+                }
+                let linked = linked_capabilities.contains("http-client");
+                if !linked {
+                    builder.link_capability::<HttpClient>()?;
                     linked_capabilities.insert("http-client".to_string());
                 }
+                // The client added last is the one the guest gets, so a policy
+                // replaces the unrestricted client of `--link-all-capabilities`.
+                if !linked || policy.is_some() {
+                    builder.add_to_builder("http-client".to_string(), HttpClient::new(policy));
+                }
             }
         }
     }
@@ -336,6 +752,29 @@ async fn build_store_instance(
     Ok(())
 }
 
+/// Collect the state of every capability of the slightfile.
+#[cfg(feature = "messaging")]
+fn capability_store(
+    toml: &SlightFile,
+    toml_file_path: impl AsRef<Path>,
+) -> Result<CapabilityStore<BasicState>> {
+    let mut capability_store = CapabilityStore::<BasicState>::new();
+    for c in toml.capability.iter().flatten() {
+        let resource_type = c.resource();
+        if !matches!(resource_type, Resource::HttpServer(_)) {
+            maybe_add_named_capability_to_store(
+                toml.specversion,
+                toml.secret_store.clone(),
+                &mut capability_store,
+                c.clone(),
+                &toml_file_path,
+                &resource_type,
+            )?;
+        }
+    }
+    Ok(capability_store)
+}
+
 fn maybe_add_named_capability_to_store(
     specversion: SpecVersion,
     secret_store: Option<SecretStoreResource>,
@@ -350,6 +789,7 @@ fn maybe_add_named_capability_to_store(
             c.resource(),
             c.name().to_string(),
             c.configs(),
+            c.policy(),
             toml_file_path,
         ),
         SpecVersion::V2 => BasicState::new(
@@ -357,6 +797,7 @@ fn maybe_add_named_capability_to_store(
             *resource_type,
             c.name().to_string(),
             c.configs(),
+            c.policy(),
             toml_file_path,
         ),
     };
@@ -367,6 +808,8 @@ fn maybe_add_named_capability_to_store(
 #[cfg(test)]
 mod unittest {
     use crate::commands::run::{handle_run, RunArgs};
+    #[cfg(feature = "http-server")]
+    use crate::commands::run::{reload_guest_builder, watch_and_reload, WATCH_INTERVAL};
     use rand::distributions::Alphanumeric;
     use rand::Rng;
     use slight_runtime::IORedirects;
@@ -405,6 +848,12 @@ mod unittest {
                 stderr_path: Some(PathBuf::from(&stderr_path)),
             }),
             link_all_capabilities: false,
+            guest_args: vec![],
+            watch: false,
+            listen: None,
+            record: None,
+            replay: None,
+            profile: None,
         };
 
         handle_run(args).await?;
@@ -414,4 +863,147 @@ mod unittest {
         assert_eq!(stderr_output, format!("error: {canary}"));
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_handle_run_appends_to_io_redirects() -> anyhow::Result<()> {
+        let tmp_dir = tempdir()?;
+        let stdin_path = tmp_dir.path().join("stdin");
+        let stdout_path = tmp_dir.path().join("stdout");
+        fs::write(&stdin_path, "canary").await?;
+        let _ = File::create(&stdout_path)?;
+
+        let args = RunArgs {
+            module: PathBuf::from("./src/commands/test/io-test.wasm"),
+            slightfile: PathBuf::from("./src/commands/test/slightfile.toml"),
+            io_redirects: Some(IORedirects {
+                stdin_path: Some(stdin_path),
+                stdout_path: Some(stdout_path.clone()),
+                stderr_path: None,
+            }),
+            ..Default::default()
+        };
+
+        // every run builds a new instance, which opens the redirects again
+        handle_run(args.clone()).await?;
+        handle_run(args).await?;
+        let stdout_output = fs::read_to_string(&stdout_path).await?;
+        assert_eq!(stdout_output, "canarycanary");
+        Ok(())
+    }
+
+    #[cfg(feature = "http-client")]
+    #[tokio::test]
+    async fn test_link_all_capabilities_keeps_the_http_client_policy() -> anyhow::Result<()> {
+        use std::collections::HashSet;
+
+        use slight_file::SlightFileBuilder;
+        use slight_http_client::{
+            http_client::{HttpClient as _, HttpError, Method, Request},
+            HttpClient,
+        };
+        use slight_runtime::Builder;
+
+        use crate::commands::run::{build_store_instance, get_resource, link_all_caps};
+
+        let tmp_dir = tempdir()?;
+        let slightfile = tmp_dir.path().join("slightfile.toml");
+        fs::write(
+            &slightfile,
+            r#"specversion = "0.2"
+
+[[capability]]
+resource = "http-client"
+name = "my-client"
+    [capability.policy]
+    urls = ["https://allowed.example.com/*"]
+"#,
+        )
+        .await?;
+        let toml = SlightFileBuilder::new().path(&slightfile)?.build()?;
+
+        let mut builder = Builder::from_module("./src/commands/test/io-test.wasm")?;
+        let mut linked_capabilities = HashSet::new();
+        link_all_caps(&mut builder, &mut linked_capabilities)?;
+        build_store_instance(
+            toml.as_ref(),
+            &slightfile,
+            &mut builder,
+            &mut linked_capabilities,
+        )
+        .await?;
+        let (mut store, _) = builder.build().await?;
+
+        let http_client = get_resource::<HttpClient>(&mut store, "http-client");
+        let res = http_client
+            .request(Request {
+                method: Method::Get,
+                uri: "https://denied.example.com/",
+                headers: vec![],
+                params: vec![],
+                body: None,
+            })
+            .await;
+        assert!(matches!(res, Err(HttpError::InvalidUrl(_))));
+        Ok(())
+    }
+
+    #[cfg(feature = "http-server")]
+    #[tokio::test]
+    async fn test_watch_and_reload_swaps_the_guest_builder() -> anyhow::Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        use slight_http_server::HttpServer;
+        use slight_runtime::Builder;
+
+        let tmp_dir = tempdir()?;
+        let module = tmp_dir.path().join("module.wasm");
+        let slightfile = tmp_dir.path().join("slightfile.toml");
+        fs::copy("./src/commands/test/io-test.wasm", &module).await?;
+        fs::copy("./src/commands/test/slightfile.toml", &slightfile).await?;
+        let args = RunArgs {
+            module: module.clone(),
+            slightfile,
+            watch: true,
+            ..Default::default()
+        };
+
+        let mut http_server = HttpServer::<Builder>::default();
+        http_server.update_state(slight_common::Builder::new(
+            reload_guest_builder(&args).await?,
+        ))?;
+        let reloaded = |http_server: &HttpServer<Builder>| {
+            http_server
+                .guest_builder()
+                .map(|builder| {
+                    builder
+                        .inner()
+                        .module()
+                        .get_export("canonical_abi_realloc")
+                        .is_some()
+                })
+                .unwrap_or(false)
+        };
+        assert!(!reloaded(&http_server));
+
+        let watcher = http_server.clone();
+        tokio::select! {
+            _ = watch_and_reload(&args, watcher) => unreachable!("the watcher never returns"),
+            res = async {
+                tokio::time::sleep(WATCH_INTERVAL).await;
+                fs::copy("./crates/runtime/test/keyvalue-test.wasm", &module).await?;
+                File::options()
+                    .write(true)
+                    .open(&module)?
+                    .set_modified(SystemTime::now() + Duration::from_secs(1))?;
+                tokio::time::timeout(Duration::from_secs(10), async {
+                    while !reloaded(&http_server) {
+                        tokio::time::sleep(WATCH_INTERVAL / 5).await;
+                    }
+                })
+                .await?;
+                anyhow::Ok(())
+            } => res?,
+        }
+        Ok(())
+    }
 }
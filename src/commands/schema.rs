@@ -0,0 +1,7 @@
+use anyhow::Result;
+
+/// Print the JSON Schema of the slightfile.
+pub fn handle_schema() -> Result<()> {
+    println!("{:#}", slight_file::schema::schema());
+    Ok(())
+}
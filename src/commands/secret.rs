@@ -1,16 +1,15 @@
-use anyhow::Result;
-use slight_core::secret::create_secret;
+use anyhow::{bail, Result};
+use slight_core::secret::{create_secret, get_secret, migrate_secrets, remove_secret, rotate_key};
 use slight_file::{SecretStoreResource, SlightFile, SpecVersion};
-use std::{fs::OpenOptions, path::Path};
+use std::path::Path;
 
-pub fn handle_secret(key: &str, value: &str, toml_file_path: impl AsRef<Path>) -> Result<()> {
-    let mut toml_file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(&toml_file_path)?;
+fn read_slightfile(toml_file_path: impl AsRef<Path>) -> Result<SlightFile> {
     let toml_file_contents = std::fs::read_to_string(&toml_file_path)?;
-    let mut toml = toml::from_str::<SlightFile>(&toml_file_contents)?;
+    Ok(toml::from_str::<SlightFile>(&toml_file_contents)?)
+}
+
+pub fn handle_secret(key: &str, value: &str, toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let mut toml = read_slightfile(&toml_file_path)?;
 
     // if specversion is 0.1 -- set secret_store to configs.usersecrets
     if let SpecVersion::V1 = toml.specversion {
@@ -18,5 +17,44 @@ pub fn handle_secret(key: &str, value: &str, toml_file_path: impl AsRef<Path>) -
     }
 
     // removed global secret_store
-    create_secret(key, value, &mut toml, &mut toml_file)
+    create_secret(key, value, &mut toml, toml_file_path)
+}
+
+pub fn handle_list_secrets(toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let toml = read_slightfile(toml_file_path)?;
+    for secret in toml.secret_settings.iter().flatten() {
+        println!("{}", secret.name);
+    }
+    Ok(())
+}
+
+pub fn handle_remove_secret(key: &str, toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let mut toml = read_slightfile(&toml_file_path)?;
+    remove_secret(key, &mut toml, toml_file_path)
+}
+
+/// Print the value of a secret. It is only printed with `reveal`, so that
+/// it does not end up in a terminal or log by accident.
+pub fn handle_get_secret(key: &str, reveal: bool, toml_file_path: impl AsRef<Path>) -> Result<()> {
+    if !reveal {
+        bail!("refusing to print the value of secret '{key}', pass --reveal to confirm");
+    }
+    let toml = read_slightfile(&toml_file_path)?;
+    let value = get_secret(key, &toml, toml_file_path)?;
+    println!("{}", String::from_utf8_lossy(&value));
+    Ok(())
+}
+
+pub fn handle_rotate_key(toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let mut toml = read_slightfile(&toml_file_path)?;
+    let rotated = rotate_key(&mut toml, toml_file_path)?;
+    println!("re-encrypted {rotated} secret(s) under a new key");
+    Ok(())
+}
+
+pub fn handle_migrate_secrets(toml_file_path: impl AsRef<Path>) -> Result<()> {
+    let mut toml = read_slightfile(&toml_file_path)?;
+    let migrated = migrate_secrets(&mut toml, &toml_file_path)?;
+    println!("re-encrypted {migrated} secret(s)");
+    Ok(())
 }
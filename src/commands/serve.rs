@@ -0,0 +1,224 @@
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+use crate::commands::run::{handle_run, IORedirects, RunArgs};
+
+/// A manifest of apps hosted by a single `slight serve` process.
+///
+/// ```toml
+/// [[app]]
+/// name = "hello"
+/// module = "hello/hello.wasm"
+/// slightfile = "hello/slightfile.toml"
+/// listen = "0.0.0.0:3001"
+/// ```
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    #[serde(rename = "app")]
+    pub apps: Vec<App>,
+}
+
+/// An app in a manifest. Relative paths are resolved against the
+/// manifest's directory.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct App {
+    /// A unique name, shown in the logs of the app.
+    pub name: String,
+    pub module: PathBuf,
+    pub slightfile: PathBuf,
+    /// Serve http on this address instead of the one the guest asks for.
+    pub listen: Option<String>,
+    /// Write the guest's stdout to this file instead of slight's stdout.
+    pub stdout: Option<PathBuf>,
+    /// Write the guest's stderr to this file instead of slight's stderr.
+    pub stderr: Option<PathBuf>,
+    /// Apply this profile of the app's slightfile.
+    pub profile: Option<String>,
+}
+
+impl Manifest {
+    pub fn from_toml_string(toml: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(toml)?;
+        let mut names = HashSet::new();
+        let mut addresses = HashSet::new();
+        for app in &manifest.apps {
+            if !names.insert(&app.name) {
+                bail!("the app name '{}' is used more than once", app.name);
+            }
+            if let Some(listen) = &app.listen {
+                if !addresses.insert(listen) {
+                    bail!("the listen address '{listen}' is used by more than one app");
+                }
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+impl App {
+    fn run_args(&self, base: &Path) -> RunArgs {
+        let io_redirects = (self.stdout.is_some() || self.stderr.is_some()).then(|| IORedirects {
+            stdout_path: self.stdout.as_ref().map(|path| base.join(path)),
+            stderr_path: self.stderr.as_ref().map(|path| base.join(path)),
+            stdin_path: None,
+        });
+        RunArgs {
+            module: base.join(&self.module),
+            slightfile: base.join(&self.slightfile),
+            io_redirects,
+            listen: self.listen.clone(),
+            profile: self.profile.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Run every app of a manifest in this process.
+///
+/// Each app has its own capabilities and guest instances, and runs until it
+/// exits or slight is asked to shut down. An app failing does not stop the
+/// others. Apps with the same engine settings (i.e., `instance_pool` and
+/// `limits`) share an `Engine`.
+///
+/// Some settings are process-wide rather than per app:
+///   - metrics are served on the `[metrics]` address of the first app that
+///     has one, and include the capability calls of every app,
+///   - spans are exported as configured by the first app with a
+///     `[telemetry]` section,
+///   - `configs.envvars` reads and writes the environment of the process,
+///     which all apps share,
+///   - the in-memory implementors (`keyvalue.memory`, `messaging.memory`,
+///     `blobstore.memory`, `sql.memory`, `configs.memory` and
+///     `distributed_locking.memory`) keep their data in the process, so
+///     apps that open the same name see the same data, and all apps share
+///     the same configs and locks.
+pub async fn handle_serve(manifest_path: impl AsRef<Path>) -> Result<()> {
+    let manifest_path = manifest_path.as_ref();
+    let toml = std::fs::read_to_string(manifest_path)
+        .with_context(|| format!("could not read '{}'", manifest_path.display()))?;
+    let manifest = Manifest::from_toml_string(&toml)
+        .with_context(|| format!("invalid manifest '{}'", manifest_path.display()))?;
+    let base = manifest_path.parent().unwrap_or(Path::new("."));
+
+    let mut apps = JoinSet::new();
+    for app in &manifest.apps {
+        let name = app.name.clone();
+        let args = app.run_args(base);
+        if let Some(io_redirects) = &args.io_redirects {
+            for path in [&io_redirects.stdout_path, &io_redirects.stderr_path]
+                .into_iter()
+                .flatten()
+            {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("could not create '{}'", path.display()))?;
+            }
+        }
+        apps.spawn(
+            async move {
+                tracing::info!("starting app");
+                (name, handle_run(args).await)
+            }
+            .instrument(tracing::info_span!("app", name = %app.name)),
+        );
+    }
+
+    let mut failed = 0;
+    while let Some(res) = apps.join_next().await {
+        match res {
+            Ok((name, Ok(()))) => tracing::info!("app '{name}' exited"),
+            Ok((name, Err(e))) => {
+                failed += 1;
+                tracing::error!("app '{name}' failed: {e:#}");
+            }
+            Err(e) => {
+                failed += 1;
+                tracing::error!("an app panicked: {e}");
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} of {} app(s) failed", manifest.apps.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod unittest {
+    use std::path::{Path, PathBuf};
+
+    use super::Manifest;
+
+    #[test]
+    fn test_manifest() -> anyhow::Result<()> {
+        let manifest = Manifest::from_toml_string(
+            r#"
+            [[app]]
+            name = "hello"
+            module = "hello/hello.wasm"
+            slightfile = "hello/slightfile.toml"
+            listen = "0.0.0.0:3001"
+            stdout = "logs/hello.out"
+
+            [[app]]
+            name = "worker"
+            module = "/opt/worker.wasm"
+            slightfile = "worker.toml"
+            "#,
+        )?;
+        assert_eq!(manifest.apps.len(), 2);
+
+        let hello = manifest.apps[0].run_args(Path::new("/srv"));
+        assert_eq!(hello.module, PathBuf::from("/srv/hello/hello.wasm"));
+        assert_eq!(
+            hello.slightfile,
+            PathBuf::from("/srv/hello/slightfile.toml")
+        );
+        assert_eq!(hello.listen.as_deref(), Some("0.0.0.0:3001"));
+        let io_redirects = hello.io_redirects.unwrap();
+        assert_eq!(
+            io_redirects.stdout_path,
+            Some(PathBuf::from("/srv/logs/hello.out"))
+        );
+        assert_eq!(io_redirects.stderr_path, None);
+
+        let worker = manifest.apps[1].run_args(Path::new("/srv"));
+        assert_eq!(worker.module, PathBuf::from("/opt/worker.wasm"));
+        assert!(worker.io_redirects.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_duplicates() {
+        let err = Manifest::from_toml_string(
+            r#"
+            [[app]]
+            name = "a"
+            module = "a.wasm"
+            slightfile = "a.toml"
+            listen = "0.0.0.0:3000"
+
+            [[app]]
+            name = "b"
+            module = "b.wasm"
+            slightfile = "b.toml"
+            listen = "0.0.0.0:3000"
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the listen address '0.0.0.0:3000' is used by more than one app"
+        );
+    }
+}
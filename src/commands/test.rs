@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use slight_common::LimitExceeded;
+use slight_file::{Capability, SlightFile, SlightFileBuilder};
+use slight_runtime::Builder;
+use wit_bindgen_wasmtime::wasmtime::{ExternType, Module};
+
+use crate::commands::run::{build_store_instance, runtime_options, wasi_options};
+
+/// The data the in-memory capabilities start each test with.
+///
+/// ```toml
+/// [keyvalue.my-store]
+/// greeting = "hello"
+///
+/// [messaging.my-broker]
+/// orders = ["order-1", "order-2"]
+///
+/// [blob.my-container]
+/// "hello.txt" = "hello, world"
+///
+/// [configs]
+/// API_KEY = "not-a-secret"
+///
+/// [[sql.my-db]]
+/// query = "SELECT name FROM users"
+/// rows = [{ name = "alice" }, { name = "bob" }]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Fixture {
+    /// Store name -> key -> value
+    #[serde(default)]
+    pub keyvalue: HashMap<String, HashMap<String, String>>,
+    /// Broker name -> topic -> messages, oldest first
+    #[serde(default)]
+    pub messaging: HashMap<String, HashMap<String, Vec<String>>>,
+    /// Container name -> object name -> contents
+    #[serde(default)]
+    pub blob: HashMap<String, HashMap<String, String>>,
+    /// Key -> value, shared by every configs capability
+    #[serde(default)]
+    pub configs: HashMap<String, String>,
+    /// Database name -> canned answers
+    #[serde(default)]
+    pub sql: HashMap<String, Vec<SqlFixture>>,
+}
+
+/// The rows a query is answered with.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SqlFixture {
+    pub query: String,
+    #[serde(default)]
+    pub rows: Vec<toml::value::Table>,
+}
+
+impl Fixture {
+    pub fn from_toml_string(toml: &str) -> Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Empty the in-memory capabilities, and fill them with this fixture.
+    fn seed(&self) -> Result<()> {
+        #[cfg(feature = "keyvalue")]
+        {
+            slight_keyvalue::memory::reset();
+            for (name, entries) in &self.keyvalue {
+                slight_keyvalue::memory::seed(
+                    name,
+                    entries
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.as_bytes().to_vec())),
+                );
+            }
+        }
+        #[cfg(feature = "messaging")]
+        {
+            slight_messaging::memory::reset();
+            for (name, topics) in &self.messaging {
+                for (topic, messages) in topics {
+                    slight_messaging::memory::seed(
+                        name,
+                        topic,
+                        messages.iter().map(|m| m.as_bytes().to_vec()),
+                    );
+                }
+            }
+        }
+        #[cfg(feature = "blob-store")]
+        {
+            slight_blob_store::memory::reset();
+            for (name, objects) in &self.blob {
+                slight_blob_store::memory::seed(
+                    name,
+                    objects
+                        .iter()
+                        .map(|(k, v)| (k.clone(), v.as_bytes().to_vec())),
+                );
+            }
+        }
+        #[cfg(feature = "runtime-configs")]
+        {
+            use slight_runtime_configs::implementors::memory::Memory;
+            Memory::reset();
+            Memory::seed(
+                self.configs
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_bytes().to_vec())),
+            );
+        }
+        #[cfg(feature = "distributed-locking")]
+        slight_distributed_locking::memory::reset();
+        #[cfg(feature = "sql")]
+        {
+            slight_sql::memory::reset();
+            for (name, answers) in &self.sql {
+                for answer in answers {
+                    slight_sql::memory::seed(name, &answer.query, sql_rows(answer)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Flatten the rows of a sql fixture into the `row-item`s a query returns.
+#[cfg(feature = "sql")]
+fn sql_rows(answer: &SqlFixture) -> Result<Vec<slight_sql::sql::RowItem>> {
+    use slight_sql::sql::{DataType, RowItem};
+    use toml::Value;
+
+    let mut items = vec![];
+    for row in &answer.rows {
+        for (field_name, value) in row {
+            let value = match value {
+                Value::String(s) => DataType::Str(s.clone()),
+                Value::Integer(i) => DataType::Int64(*i),
+                Value::Float(f) => DataType::Double(*f),
+                Value::Boolean(b) => DataType::Boolean(*b),
+                Value::Datetime(d) => DataType::Timestamp(d.to_string()),
+                Value::Array(_) | Value::Table(_) => bail!(
+                    "column '{field_name}' of query '{}' must be a string, number, boolean or datetime",
+                    answer.query
+                ),
+            };
+            items.push(RowItem {
+                field_name: field_name.clone(),
+                value,
+            });
+        }
+    }
+    Ok(items)
+}
+
+/// Run the tests a module exports, with every capability swapped for its
+/// in-memory implementor.
+///
+/// Tests are exported functions whose name starts with `test`, and that take
+/// and return nothing. Each test gets a fresh instance, and capabilities
+/// seeded from `fixture`. A test fails if it traps (e.g., panics).
+pub async fn handle_test(
+    module: impl AsRef<Path>,
+    toml_file_path: impl AsRef<Path>,
+    fixture: Option<impl AsRef<Path>>,
+    filter: Option<&str>,
+) -> Result<()> {
+    let module = module.as_ref();
+    let toml_file_path = toml_file_path.as_ref();
+    let toml = SlightFileBuilder::new().path(toml_file_path)?.build()?;
+    let toml = in_memory(toml.as_ref());
+    let fixture = match fixture {
+        Some(path) => {
+            let path = path.as_ref();
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("could not read fixture '{}'", path.display()))?;
+            Fixture::from_toml_string(&content)
+                .with_context(|| format!("invalid fixture '{}'", path.display()))?
+        }
+        None => Fixture::default(),
+    };
+
+    // compiled once, through the module cache, and cloned for every test
+    let builder =
+        Builder::from_module_with_options(module, &runtime_options(&toml, toml_file_path))?;
+    let tests: Vec<String> = module_tests(builder.module())
+        .into_iter()
+        .filter(|name| filter.map_or(true, |filter| name.contains(filter)))
+        .collect();
+    println!("running {} test(s)", tests.len());
+
+    let mut failed = vec![];
+    for name in &tests {
+        fixture.seed()?;
+        match run_test(&builder, module, &toml, toml_file_path, name).await {
+            Ok(()) => println!("test {name} ... ok"),
+            Err(e) => {
+                println!("test {name} ... FAILED");
+                failed.push((name, e));
+            }
+        }
+    }
+
+    for (name, e) in &failed {
+        println!("\n---- {name} ----\n{e:?}");
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        tests.len() - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        bail!("{} of {} test(s) failed", failed.len(), tests.len());
+    }
+    Ok(())
+}
+
+/// Read the names of the tests a wasm module exports.
+pub fn module_tests(module: &Module) -> Vec<String> {
+    module
+        .exports()
+        .filter(|export| export.name().starts_with("test"))
+        .filter(|export| match export.ty() {
+            ExternType::Func(ty) => ty.params().len() == 0 && ty.results().len() == 0,
+            _ => false,
+        })
+        .map(|export| export.name().to_string())
+        .collect()
+}
+
+/// Swap the implementor of every capability for the in-memory one of the
+/// same interface. Capabilities without one (i.e., http) are kept.
+pub fn in_memory(toml: &SlightFile) -> SlightFile {
+    let mut toml = toml.clone();
+    if let Some(capabilities) = toml.capability.as_mut() {
+        for c in capabilities.iter_mut() {
+            match c {
+                Capability::V1(c) => c.name = c.name.in_memory().unwrap_or(c.name),
+                Capability::V2(c) => c.resource = c.resource.in_memory().unwrap_or(c.resource),
+            }
+        }
+    }
+    // The in-memory implementors do not read secrets.
+    toml.secret_store = None;
+    toml
+}
+
+async fn run_test(
+    builder: &Builder,
+    module: &Path,
+    toml: &SlightFile,
+    toml_file_path: &Path,
+    name: &str,
+) -> Result<()> {
+    let wasi = wasi_options(toml, toml_file_path, module, &[]);
+    let mut builder = builder.clone().set_wasi(wasi);
+    let mut linked_capabilities = HashSet::new();
+    build_store_instance(toml, toml_file_path, &mut builder, &mut linked_capabilities).await?;
+    let (mut store, instance) = builder.build().await?;
+
+    instance
+        .get_typed_func::<(), ()>(&mut store, name)?
+        .call_async(&mut store, ())
+        .await
+        .map_err(|e| match LimitExceeded::from_error(&e) {
+            Some(limit) => anyhow::anyhow!(limit),
+            None => e,
+        })
+}
+
+#[cfg(test)]
+mod unittest {
+    use slight_file::{
+        resource::{KeyvalueResource, MessagingResource},
+        Resource, SlightFileBuilder,
+    };
+
+    use super::{in_memory, Fixture};
+
+    #[test]
+    fn test_fixture() -> anyhow::Result<()> {
+        let fixture = Fixture::from_toml_string(
+            r#"
+            [keyvalue.my-store]
+            greeting = "hello"
+
+            [messaging.my-broker]
+            orders = ["order-1", "order-2"]
+
+            [configs]
+            API_KEY = "not-a-secret"
+
+            [[sql.my-db]]
+            query = "SELECT name FROM users"
+            rows = [{ name = "alice" }, { name = "bob" }]
+            "#,
+        )?;
+        assert_eq!(fixture.keyvalue["my-store"]["greeting"], "hello");
+        assert_eq!(
+            fixture.messaging["my-broker"]["orders"],
+            vec!["order-1", "order-2"]
+        );
+        assert_eq!(fixture.configs["API_KEY"], "not-a-secret");
+        assert_eq!(fixture.sql["my-db"][0].rows.len(), 2);
+        assert!(fixture.blob.is_empty());
+
+        assert!(Fixture::from_toml_string("[kv.my-store]\nfoo = \"bar\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_memory() -> anyhow::Result<()> {
+        for (path, resource) in [
+            (
+                "./crates/slightfile/tests/good/msg.toml",
+                Resource::Messaging(MessagingResource::Memory),
+            ),
+            (
+                "./crates/slightfile/tests/good/keyvalue_azblob_slightfile_v01.toml",
+                Resource::Keyvalue(KeyvalueResource::Memory),
+            ),
+        ] {
+            let toml = SlightFileBuilder::new().path(path)?.build()?;
+            let toml = in_memory(toml.as_ref());
+            assert!(toml.secret_store.is_none());
+            assert_eq!(toml.capability.unwrap()[0].resource(), resource);
+        }
+        Ok(())
+    }
+}
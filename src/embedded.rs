@@ -0,0 +1,68 @@
+//! The `wit/` and `templates/` trees of this release, embedded at build
+//! time so that `slight add` and `slight new` work offline.
+
+use semver::Version;
+
+include!(concat!(env!("OUT_DIR"), "/embedded.rs"));
+
+/// Whether `version` is the release of this binary, i.e., whether its
+/// interfaces and templates are embedded.
+pub fn is_embedded(version: &Version) -> bool {
+    version.to_string() == env!("CARGO_PKG_VERSION")
+}
+
+/// The contents of the interface file `name`, e.g. `keyvalue` for
+/// `keyvalue.wit`.
+pub fn wit(name: &str) -> Option<&'static str> {
+    WIT.iter()
+        .find(|(wit_name, _)| *wit_name == name)
+        .map(|(_, contents)| *contents)
+}
+
+/// The files of the template `name`, with paths that start with `name/`.
+pub fn template(name: &str) -> impl Iterator<Item = (&'static str, &'static [u8])> + '_ {
+    TEMPLATES
+        .iter()
+        .filter(move |(path, _)| {
+            path.strip_prefix(name)
+                .map_or(false, |rest| rest.starts_with('/'))
+        })
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::add::get_interface_downloads_by_name;
+
+    #[test]
+    fn test_embedded_wit() {
+        assert!(wit("keyvalue").unwrap().contains("resource keyvalue"));
+        assert!(wit("missing").is_none());
+        for interface in [
+            "keyvalue",
+            "configs",
+            "http-server",
+            "http-client",
+            "distributed-locking",
+            "messaging",
+            "sql",
+            "blob-store",
+        ] {
+            for name in get_interface_downloads_by_name(interface) {
+                assert!(wit(name).is_some(), "{name}.wit is not embedded");
+            }
+        }
+    }
+
+    #[test]
+    fn test_embedded_templates() {
+        let rust: Vec<&str> = template("rust").map(|(path, _)| path).collect();
+        assert!(rust.contains(&"rust/Cargo.toml"));
+        assert!(rust.contains(&"rust/src/main.rs"));
+        assert!(template("rus").next().is_none());
+        assert!(is_embedded(
+            &Version::parse(env!("CARGO_PKG_VERSION")).unwrap()
+        ));
+    }
+}
@@ -1,2 +1,6 @@
 pub mod cli;
 pub mod commands;
+pub mod embedded;
+pub mod metrics;
+pub mod schedule;
+pub mod telemetry;
@@ -4,39 +4,101 @@ use anyhow::Result;
 use clap::Parser;
 
 use slight_lib::{
-    cli::{Args, Commands},
+    cli::{Args, Commands, SecretCommands},
     commands::{
         add::handle_add,
         buildjs::handle_buildjs,
+        check::handle_check,
+        init_config::handle_init_config,
+        migrate_config::handle_migrate_config,
         new::handle_new,
         run::{handle_run, RunArgs},
-        secret::handle_secret,
+        schema::handle_schema,
+        secret::{
+            handle_get_secret, handle_list_secrets, handle_migrate_secrets, handle_remove_secret,
+            handle_rotate_key, handle_secret,
+        },
+        serve::handle_serve,
+        test::handle_test,
     },
+    telemetry,
 };
 
 /// The entry point for slight CLI
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_writer(std::io::stderr)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
     let args = Args::parse();
+    telemetry::init()?;
 
-    match &args.command {
+    let res = match &args.command {
         Commands::Run {
             module,
             link_all_capabilities,
+            watch,
+            record,
+            replay,
+            profile,
+            args: guest_args,
         } => {
             let run_args = RunArgs {
                 module: PathBuf::from(&module.path),
                 slightfile: PathBuf::from(args.config.unwrap()),
                 link_all_capabilities: *link_all_capabilities,
+                guest_args: guest_args.clone(),
+                watch: *watch,
+                record: record.as_ref().map(PathBuf::from),
+                replay: replay.as_ref().map(PathBuf::from),
+                profile: profile.clone(),
                 ..Default::default()
             };
             handle_run(run_args).await
         }
-        Commands::Secret { key, value } => handle_secret(key, value, args.config.unwrap()),
+        Commands::Check { module } => handle_check(
+            &module.path,
+            args.config.unwrap_or_else(|| "slightfile.toml".to_string()),
+        ),
+        Commands::MigrateConfig { dry_run } => handle_migrate_config(
+            args.config.unwrap_or_else(|| "slightfile.toml".to_string()),
+            *dry_run,
+        ),
+        Commands::Schema => handle_schema(),
+        Commands::InitConfig { module, force } => handle_init_config(
+            &module.path,
+            args.config.unwrap_or_else(|| "slightfile.toml".to_string()),
+            *force,
+        ),
+        Commands::Test {
+            module,
+            filter,
+            fixture,
+        } => {
+            handle_test(
+                &module.path,
+                args.config.unwrap_or_else(|| "slightfile.toml".to_string()),
+                fixture.as_ref(),
+                filter.as_deref(),
+            )
+            .await
+        }
+        Commands::Serve { manifest } => handle_serve(manifest).await,
+        Commands::Secret {
+            command,
+            key,
+            value,
+        } => {
+            let slightfile = args.config.unwrap();
+            match (command, key, value) {
+                (Some(SecretCommands::List), _, _) => handle_list_secrets(slightfile),
+                (Some(SecretCommands::Rm { key }), _, _) => handle_remove_secret(key, slightfile),
+                (Some(SecretCommands::Get { key, reveal }), _, _) => {
+                    handle_get_secret(key, *reveal, slightfile)
+                }
+                (Some(SecretCommands::RotateKey), _, _) => handle_rotate_key(slightfile),
+                (Some(SecretCommands::Migrate), _, _) => handle_migrate_secrets(slightfile),
+                (None, Some(key), Some(value)) => handle_secret(key, value, slightfile),
+                (None, _, _) => unreachable!("clap requires a subcommand, or a key and a value"),
+            }
+        }
         Commands::Add {
             interface_at_release,
         } => handle_add(interface_at_release.to_owned(), None).await,
@@ -49,5 +111,7 @@ async fn main() -> Result<()> {
             engine,
             output,
         } => handle_buildjs(&engine.path, src, &output.path),
-    }
+    };
+    telemetry::shutdown();
+    res
 }
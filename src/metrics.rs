@@ -0,0 +1,101 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Mutex};
+
+use anyhow::{Context, Result};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use slight_common::metrics;
+
+/// The address metrics are served on, once they are.
+static SERVING: Mutex<Option<SocketAddr>> = Mutex::new(None);
+
+/// Serve Prometheus metrics on `address` under `/metrics`, until the
+/// process exits.
+///
+/// Metrics are process-wide, so they are served on one address per
+/// process. Later calls, e.g., for other apps of `slight serve`, reuse it,
+/// and a warning is logged if they ask for another address.
+pub fn serve(address: &str) -> Result<()> {
+    let addr: SocketAddr = address
+        .parse()
+        .with_context(|| format!("invalid metrics address '{address}'"))?;
+    let mut serving = SERVING.lock().unwrap();
+    if let Some(current) = *serving {
+        if current != addr {
+            tracing::warn!("metrics are already served on {current}, ignoring {addr}");
+        }
+        return Ok(());
+    }
+    let make_svc =
+        make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle_metrics_request)) });
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("could not serve metrics on {addr}"))?
+        .serve(make_svc);
+
+    tracing::info!("serving metrics on http://{addr}/metrics");
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            tracing::error!("metrics server error: {e}");
+        }
+    });
+    *serving = Some(addr);
+    Ok(())
+}
+
+async fn handle_metrics_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let res = if req.uri().path() == "/metrics" {
+        Response::builder()
+            .header(CONTENT_TYPE, metrics::CONTENT_TYPE)
+            .body(Body::from(metrics::gather()))
+    } else {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+    };
+    Ok(res.expect("response is valid"))
+}
+
+#[cfg(test)]
+mod unittest {
+    use hyper::{body::to_bytes, header::CONTENT_TYPE, Body, Request, StatusCode};
+    use slight_common::metrics::{self, observe_capability_call, ErrorVariant};
+
+    use super::handle_metrics_request;
+
+    struct KeyNotFound;
+
+    impl ErrorVariant for KeyNotFound {
+        fn variant(&self) -> &'static str {
+            "key-not-found"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_request() -> anyhow::Result<()> {
+        let ok: Result<(), KeyNotFound> =
+            observe_capability_call("keyvalue.metrics-test", "get", async { Ok(()) }).await;
+        assert!(ok.is_ok());
+        let err: Result<(), KeyNotFound> =
+            observe_capability_call("keyvalue.metrics-test", "get", async { Err(KeyNotFound) })
+                .await;
+        assert!(err.is_err());
+
+        let res = handle_metrics_request(Request::get("/metrics").body(Body::empty())?).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_TYPE], metrics::CONTENT_TYPE);
+        let text = String::from_utf8(to_bytes(res.into_body()).await?.to_vec())?;
+        for line in [
+            r#"slight_capability_calls_total{implementor="keyvalue.metrics-test",operation="get"} 2"#,
+            r#"slight_capability_errors_total{implementor="keyvalue.metrics-test",operation="get",variant="key-not-found"} 1"#,
+            r#"slight_capability_call_duration_seconds_count{implementor="keyvalue.metrics-test",operation="get"} 2"#,
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} not in\n{text}");
+        }
+
+        let res = handle_metrics_request(Request::get("/other").body(Body::empty())?).await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Calling exported guest functions on the cron schedules of the
+//! slightfile's `[[schedule]]` entries.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use slight_common::{LimitExceeded, WasmtimeBuildable};
+use slight_file::{cron::Cron, Overlap, Schedule};
+use tokio::{sync::Mutex, task::JoinSet};
+use tracing::Instrument;
+
+/// Calls the functions of a slightfile's schedules when they are due.
+///
+/// Every run is handled by a fresh instance built from the guest builder.
+pub struct Scheduler<T: WasmtimeBuildable> {
+    builder: T,
+    jobs: Vec<Job>,
+}
+
+struct Job {
+    cron: Cron,
+    function: String,
+    overlap: Overlap,
+    /// Held for the duration of a run.
+    running: Arc<Mutex<()>>,
+}
+
+impl<T: WasmtimeBuildable + Send + Sync + 'static> Scheduler<T> {
+    pub fn new(schedules: &[Schedule], builder: T) -> Result<Self> {
+        let jobs = schedules
+            .iter()
+            .map(|schedule| {
+                Ok(Job {
+                    cron: schedule.cron.parse().with_context(|| {
+                        format!("invalid schedule of function '{}'", schedule.function)
+                    })?,
+                    function: schedule.function.clone(),
+                    overlap: schedule.overlap,
+                    running: Arc::new(Mutex::new(())),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { builder, jobs })
+    }
+
+    /// Call the functions on schedule until `shutdown` completes, then wait
+    /// for the runs in progress. Queued runs are dropped.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let stopping = Arc::new(AtomicBool::new(false));
+        let jobs: Vec<Arc<Job>> = self.jobs.into_iter().map(Arc::new).collect();
+        let mut tickers = JoinSet::new();
+        for job in &jobs {
+            tracing::info!("scheduled `{}` on '{}'", job.function, job.cron);
+            tickers.spawn(tick(job.clone(), self.builder.clone(), stopping.clone()));
+        }
+
+        shutdown.await;
+        tracing::info!("stopping the scheduler");
+        stopping.store(true, Ordering::SeqCst);
+        tickers.abort_all();
+        // the lock is fair, so this waits for the run in progress, and for
+        // the queued runs to notice they are dropped
+        for job in &jobs {
+            let _ = job.running.lock().await;
+        }
+        Ok(())
+    }
+}
+
+/// Start a run of `job` whenever it is due.
+async fn tick<T: WasmtimeBuildable + Send + Sync + 'static>(
+    job: Arc<Job>,
+    builder: T,
+    stopping: Arc<AtomicBool>,
+) {
+    let mut after = SystemTime::now();
+    loop {
+        let Some(due) = job.cron.next_after(after) else {
+            tracing::warn!("`{}` is never due again on '{}'", job.function, job.cron);
+            return;
+        };
+        if let Ok(delay) = due.duration_since(SystemTime::now()) {
+            tokio::time::sleep(delay).await;
+        }
+        // don't start the same run twice if the clock is behind the timer
+        after = due.max(SystemTime::now());
+        start(&job, &builder, &stopping);
+    }
+}
+
+/// Start a run of `job` on its own task, according to its overlap policy.
+fn start<T: WasmtimeBuildable + Send + Sync + 'static>(
+    job: &Arc<Job>,
+    builder: &T,
+    stopping: &Arc<AtomicBool>,
+) {
+    let span = tracing::info_span!(
+        "schedule.run",
+        function = %job.function,
+        cron = %job.cron,
+    );
+    let job = job.clone();
+    let builder = builder.clone();
+    match job.overlap {
+        Overlap::Skip => match job.running.clone().try_lock_owned() {
+            Ok(running) => {
+                tokio::spawn(
+                    async move {
+                        run(&job, builder).await;
+                        drop(running);
+                    }
+                    .instrument(span),
+                );
+            }
+            Err(_) => tracing::warn!(
+                parent: &span,
+                "skipping a run of `{}`, the previous run is still in progress",
+                job.function
+            ),
+        },
+        Overlap::Queue => {
+            let stopping = stopping.clone();
+            tokio::spawn(
+                async move {
+                    let running = job.running.clone().lock_owned().await;
+                    if stopping.load(Ordering::SeqCst) {
+                        tracing::info!(
+                            "dropping a queued run of `{}`, the scheduler is stopping",
+                            job.function
+                        );
+                        return;
+                    }
+                    run(&job, builder).await;
+                    drop(running);
+                }
+                .instrument(span),
+            );
+        }
+    }
+}
+
+/// Call the function of `job`, and log the outcome.
+async fn run<T: WasmtimeBuildable>(job: &Job, builder: T) {
+    let start = Instant::now();
+    match call(builder, &job.function).await {
+        Ok(()) => tracing::info!(
+            "scheduled run of `{}` succeeded in {:?}",
+            job.function,
+            start.elapsed()
+        ),
+        Err(e) => tracing::error!(
+            "scheduled run of `{}` failed after {:?}: {e:#}",
+            job.function,
+            start.elapsed()
+        ),
+    }
+}
+
+/// Call `function` on a fresh instance of the guest.
+async fn call<T: WasmtimeBuildable>(builder: T, function: &str) -> Result<()> {
+    let (mut store, instance) = builder.build().await?;
+    instance
+        .get_typed_func::<(), ()>(&mut store, function)?
+        .call_async(&mut store, ())
+        .await
+        .map_err(|e| match LimitExceeded::from_error(&e) {
+            Some(limit) => anyhow!(limit),
+            None => e,
+        })
+}
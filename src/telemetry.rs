@@ -0,0 +1,134 @@
+use std::{
+    fs::OpenOptions,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{bail, Context, Result};
+use opentelemetry::{
+    global,
+    sdk::{export::trace::stdout, trace, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use slight_file::{Telemetry, TelemetryExporter};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer,
+    Registry,
+};
+
+const DEFAULT_OTLP_ENDPOINT: &str = "http://localhost:4317";
+const DEFAULT_SERVICE_NAME: &str = "slight";
+
+type SpanLayer = Option<OpenTelemetryLayer<Registry, trace::Tracer>>;
+
+/// Swaps in the span exporter once a slightfile asks for one.
+static SPANS: OnceLock<reload::Handle<SpanLayer, Registry>> = OnceLock::new();
+
+/// The telemetry settings spans are exported with, if any.
+static CONFIGURED: Mutex<Option<Telemetry>> = Mutex::new(None);
+
+/// Set up logging to stderr. Spans are not exported until `configure` is
+/// called with the `[telemetry]` section of a slightfile.
+pub fn init() -> Result<()> {
+    let (spans, handle) = reload::Layer::new(None);
+    tracing_subscriber::registry()
+        .with(spans.with_filter(LevelFilter::INFO))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        .init();
+    let _ = SPANS.set(handle);
+    Ok(())
+}
+
+/// Export spans as configured by the `[telemetry]` section of `slightfile`,
+/// after its profile and interpolation are applied.
+///
+/// Spans are exported by one exporter per process. If another slightfile
+/// already configured a different one, e.g., for another app of
+/// `slight serve`, a warning is logged and its exporter is kept.
+pub fn configure(telemetry: &Telemetry, slightfile: &Path) -> Result<()> {
+    let mut telemetry = telemetry.clone();
+    telemetry.path = telemetry
+        .path
+        .map(|path| slightfile.parent().unwrap_or(Path::new(".")).join(path));
+
+    let mut configured = CONFIGURED.lock().unwrap();
+    match configured.as_ref() {
+        Some(current) if *current == telemetry => return Ok(()),
+        Some(_) => {
+            tracing::warn!(
+                "spans are already exported with other settings, ignoring the telemetry of '{}'",
+                slightfile.display()
+            );
+            return Ok(());
+        }
+        None => {}
+    }
+    let Some(spans) = SPANS.get() else {
+        bail!("logging is not set up");
+    };
+    let tracer = tracer(&telemetry)?;
+    spans
+        .reload(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+        .context("could not set up the span exporter")?;
+    *configured = Some(telemetry);
+    Ok(())
+}
+
+/// Flush the spans that have not been exported yet.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+fn tracer(telemetry: &Telemetry) -> Result<trace::Tracer> {
+    let service_name = telemetry
+        .service_name
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+    let config = trace::config().with_resource(Resource::new(vec![KeyValue::new(
+        "service.name",
+        service_name,
+    )]));
+
+    let tracer = match telemetry.exporter {
+        TelemetryExporter::Otlp => {
+            let endpoint = telemetry
+                .endpoint
+                .as_deref()
+                .unwrap_or(DEFAULT_OTLP_ENDPOINT);
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(config)
+                .install_batch(opentelemetry::runtime::Tokio)
+                .with_context(|| format!("could not set up the OTLP exporter for '{endpoint}'"))?
+        }
+        TelemetryExporter::File => {
+            let Some(path) = &telemetry.path else {
+                bail!("the file telemetry exporter requires a `path`");
+            };
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("could not open '{}' for spans", path.display()))?;
+            stdout::new_pipeline()
+                .with_writer(file)
+                .with_trace_config(config)
+                .install_simple()
+        }
+        TelemetryExporter::Stdout => stdout::new_pipeline()
+            .with_trace_config(config)
+            .install_simple(),
+    };
+    Ok(tracer)
+}